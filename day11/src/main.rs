@@ -1,8 +1,9 @@
-use computer::*;
 use std::collections::HashMap;
+use std::error::Error;
 use std::fmt;
 
-mod computer;
+use intcode::frame::{Frame, Frames};
+use intcode::{Computer, ProgramIO};
 
 // 0 = black, 1 = white
 #[derive(Copy, Clone)]
@@ -89,15 +90,28 @@ impl Position {
     }
 }
 
-enum NextRobotInput {
-    Paint, Turn,
+/// Every 2 outputs are the color to paint the panel under the robot, then the direction to turn.
+struct RobotCommand {
+    color: PanelColor,
+    turn: TurnDirection,
+}
+
+impl Frame for RobotCommand {
+    const ARITY: usize = 2;
+
+    fn decode(values: &[i64]) -> RobotCommand {
+        RobotCommand {
+            color: PanelColor::from(values[0]),
+            turn: TurnDirection::from(values[1]),
+        }
+    }
 }
 
 struct RobotState {
     position: Position,
     direction: RobotDirection,
     panels: HashMap<Position, PanelColor>,
-    next_input: NextRobotInput
+    commands: Frames<RobotCommand>,
 }
 
 impl RobotState {
@@ -106,13 +120,13 @@ impl RobotState {
             position: Position {x: 0, y: 0},
             direction: RobotDirection::Up,
             panels: HashMap::new(),
-            next_input: NextRobotInput::Paint,
+            commands: Frames::new(),
         }
     }
 
     /// Paints the square that the robot is currently on.
     fn paint(&mut self, color: PanelColor) {
-        self.panels.insert(self.position.clone(), color);
+        self.panels.insert(self.position, color);
     }
 
     /// Turns the given direction, then moves forward exactly one panel
@@ -158,38 +172,32 @@ impl ProgramIO for RobotState {
         self.color(&self.position).value()
     }
 
-    /// Output that moves the robot.  Toggles between two states when called - the first call paints
-    /// the square under the robot, the second call turns the robot left or right.
+    /// Output that paints the square under the robot, then turns the robot left or right.
     fn output(&mut self, value: i64) {
-        match self.next_input {
-            NextRobotInput::Paint => {
-                self.paint(PanelColor::from(value));
-                self.next_input = NextRobotInput::Turn;
-
-            },
-            NextRobotInput::Turn => {
-                self.turn(TurnDirection::from(value));
-                self.next_input = NextRobotInput::Paint;
-            },
+        if let Some(command) = self.commands.push(value) {
+            self.paint(command.color);
+            self.turn(command.turn);
         }
     }
 }
 
-fn main() -> std::io::Result<()> {
+fn main() -> Result<(), Box<dyn Error>> {
     // Part 1: how many squares did the robot paint at least once?
-    let mut part1_computer = Computer::from_file("input.txt")?;
+    let mut part1_computer = Computer::load("input.txt")?;
     let mut part1_robot = RobotState::new();
 
-    part1_computer.run(&mut part1_robot);
+    part1_computer.run_io(&mut part1_robot);
+    part1_robot.commands.finish()?;
 
     println!("Part 1: {}", part1_robot.num_squares_painted());
 
     // Part 2: what does the robot print when it starts on a white square?
-    let mut part2_computer = Computer::from_file("input.txt")?;
+    let mut part2_computer = Computer::load("input.txt")?;
     let mut part2_robot = RobotState::new();
 
     part2_robot.paint(PanelColor::White);
-    part2_computer.run(&mut part2_robot);
+    part2_computer.run_io(&mut part2_robot);
+    part2_robot.commands.finish()?;
 
     println!("Part 2:");
     part2_robot.print();
@@ -1,35 +1,28 @@
 extern crate pancurses;
 
-use std::{fmt, thread, time};
-use std::collections::HashMap;
+use std::{env, fmt, thread, time};
 use std::fmt::{Error, Formatter};
 
+use intcode::display::{Display, Palette};
+use intcode::heatmap::Heatmap;
+use intcode::{Computer, ProgramIO, ProgramState};
+use intcode::scan::{Filter, Search};
 use pancurses::{cbreak, endwin, initscr, noecho, Window};
 
-use computer::*;
-
-mod computer;
-
-#[derive(Eq, PartialEq)]
+#[derive(Eq, PartialEq, Copy, Clone)]
 enum Tile {
     Empty, Wall, Block, Paddle, Ball,
 }
 
 impl Tile {
-    fn parse(value: i64) -> Tile {
-        use Tile::*;
+    const ALL: [Tile; 5] = [Tile::Empty, Tile::Wall, Tile::Block, Tile::Paddle, Tile::Ball];
 
-        match value {
-            0 => Empty,
-            1 => Wall,
-            2 => Block,
-            3 => Paddle,
-            4 => Ball,
-            n => panic!("Invalid tile: {}", n),
-        }
+    /// Returns the id the program draws this tile with.
+    fn id(self) -> i64 {
+        self as i64
     }
 
-    fn char(&self) -> char {
+    fn char(self) -> char {
         use Tile::*;
 
         match self {
@@ -40,192 +33,274 @@ impl Tile {
             Ball => 'o',
         }
     }
-}
 
-impl fmt::Display for Tile {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        write!(f, "{}", self.char())
-    }
-}
+    fn color(self) -> [u8; 3] {
+        use Tile::*;
 
-struct Game<'a> {
-    input: GameInputState,
-    tiles: HashMap<Position, Tile>,
-    title: &'a str,
-    score: i64,
-    window: Window,
+        match self {
+            Empty => [0, 0, 0],
+            Wall => [128, 128, 128],
+            Block => [200, 80, 40],
+            Paddle => [255, 255, 255],
+            Ball => [255, 220, 0],
+        }
+    }
 }
 
-struct GameInputState {
-    next_state: NextGameInputState,
-    x: Option<i64>,
-    y: Option<i64>,
-}
+/// The arcade draws the score at x=-1, y=0 instead of a tile.
+const SCORE: (i64, i64) = (-1, 0);
 
-enum NextGameInputState {
-    X, Y, TileId
+/// Returns a screen that draws tiles the way the arcade cabinet does.
+fn screen() -> Display {
+    let palette = Tile::ALL.iter().fold(Palette::new(), |palette, &tile| palette.tile(tile.id(), tile.char(), tile.color()));
+    Display::new(palette).with_special(SCORE.0, SCORE.1)
 }
 
-#[derive(Eq, PartialEq, Hash, Debug, Copy, Clone)]
-struct Position {
-    x: i64,
-    y: i64,
+struct Game<'a> {
+    screen: Display,
+    title: &'a str,
+    /// Window the game is drawn to while it's played, or None to play without a terminal.
+    window: Option<Window>,
 }
 
 impl fmt::Display for Game<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        writeln!(f, "{} - Blocks: {} - Score: {}", self.title, self.num_blocks(), self.score)?;
-
-        // Figure out how large the printed rectangle should be.  y is positive in the down direction.
-        let (left, right, top, bottom) = self.bounds();
-
-        for y in top ..= bottom {
-            for x in left ..= right {
-                let tile = self.tiles.get(&Position {x, y}).unwrap_or(&Tile::Empty);
-
-                write!(f, "{}", tile)?;
-            }
-            writeln!(f)?;
-        }
-
-        Ok(())
+        writeln!(f, "{} - Blocks: {} - Score: {}", self.title, self.num_blocks(), self.score())?;
+        write!(f, "{}", self.screen)
     }
 }
 
 impl Game<'_> {
-    fn new(title: &str) -> Game {
+    fn new(title: &str) -> Game<'_> {
+        Game {
+            screen: screen(),
+            title,
+            window: None,
+        }
+    }
+
+    /// Draws the game in a curses window as it's played.
+    fn with_window(mut self) -> Self {
         let window = initscr();
         cbreak(); // Disable line buffering - we want arrow keys as soon as they're typed.
         noecho(); // Don't echo input back to the screen.
         window.clear();
 
-        Game {
-            input: GameInputState {
-                next_state: NextGameInputState::X,
-                x: None,
-                y: None,
-            },
-            tiles: HashMap::new(),
-            title,
-            score: 0,
-            window,
-        }
+        self.window = Some(window);
+        self
     }
 
     fn num_blocks(&self) -> usize {
-        self.tiles.values()
-            .filter(|&tile| *tile == Tile::Block)
-            .count()
+        self.screen.count(Tile::Block.id())
     }
 
-    fn bounds(&self) -> (i64, i64, i64, i64) {
-        self.tiles.keys()
-            .fold((0, 0, 0, 0), |(bound_left, bound_right, bound_top, bound_bottom), panel| (
-                if panel.x < bound_left { panel.x } else { bound_left },
-                if panel.x > bound_right { panel.x } else { bound_right },
-                if panel.y < bound_top { panel.y } else { bound_top },
-                if panel.y > bound_bottom { panel.y } else { bound_bottom },
-            ))
+    fn score(&self) -> i64 {
+        self.screen.special(SCORE.0, SCORE.1).unwrap_or(0)
     }
 
-    /// Draws the current game state to the window.
+    /// Draws the current game state to the window, if there is one.
     fn render(&self) {
-        self.window.mvaddstr(0, 0, format!("{} - Blocks: {} - Score: {}", self.title, self.num_blocks(), self.score));
-        self.window.clrtoeol();
+        let window = match &self.window {
+            Some(window) => window,
+            None => return,
+        };
 
-        // Figure out how large the printed rectangle should be.  y is positive in the down direction.
-        let (left, right, top, bottom) = self.bounds();
+        window.mvaddstr(0, 0, format!("{} - Blocks: {} - Score: {}", self.title, self.num_blocks(), self.score()));
+        window.clrtoeol();
 
-        for y in top ..= bottom {
-            for x in left ..= right {
-                let tile = self.tiles.get(&Position {x, y}).unwrap_or(&Tile::Empty);
-                self.window.mvaddch(y as i32 + 1, x as i32, tile.char());
-            }
+        for (y, line) in self.screen.to_string().lines().enumerate() {
+            window.mvaddstr(y as i32 + 1, 0, line);
         }
 
-        if bottom >= 20 && right >= 39 {
+        // Slow down once the whole screen has been drawn, so the game can be watched.
+        if matches!(self.screen.bounds(), Some(bounds) if bounds.max_y >= 20 && bounds.max_x >= 39) {
             thread::sleep(time::Duration::from_millis(5));
         }
 
-        self.window.refresh();
+        window.refresh();
     }
 }
 
 impl ProgramIO for Game<'_> {
     fn input(&mut self) -> i64 {
         // Instead of having a human play, keep the paddle under the ball.
-        let ball = self.tiles.iter()
-            .find_map(|(position, tile)| if tile == &Tile::Ball { Some(position) } else { None })
-            .unwrap();
-
-        let paddle = self.tiles.iter()
-            .find_map(|(position, tile)| if tile == &Tile::Paddle { Some(position) } else { None })
-            .unwrap();
-
-        (ball.x - paddle.x).signum()
+        let (ball_x, _) = self.screen.find(Tile::Ball.id()).unwrap();
+        let (paddle_x, _) = self.screen.find(Tile::Paddle.id()).unwrap();
 
+        (ball_x - paddle_x).signum()
     }
 
     fn output(&mut self, value: i64) {
-        use NextGameInputState::*;
-
-        // Every 3 outputs are x, y, and tile id
-        // x=-1, y=0 means that the third argument is a score, not a tile.
-        match self.input.next_state {
-            X => {
-                self.input.x = Some(value);
-                self.input.next_state = Y;
-            },
-            Y => {
-                self.input.y = Some(value);
-                self.input.next_state = TileId;
-            },
-            TileId => {
-                let position = Position {
-                    x: self.input.x.unwrap(),
-                    y: self.input.y.unwrap(),
-                };
-
-                if position == (Position {x: -1, y: 0}) {
-                    self.score = value;
-                } else {
-                    self.tiles.insert(position, Tile::parse(value));
-                }
-
-                self.input.x = None;
-                self.input.y = None;
-                self.input.next_state = X;
-                self.render();
+        if self.screen.push(value) {
+            self.render();
+        }
+    }
+}
+
+/// Addresses where the arcade program keeps the game state.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+struct GameCells {
+    score: usize,
+    ball_x: usize,
+    paddle_x: usize,
+}
+
+impl GameCells {
+    /// Finds the game state in the arcade's memory.  Plays the game, keeping the addresses that
+    /// hold the score and ball and paddle positions shown on the screen until one address is left for each.
+    fn find(computer: &Computer) -> Option<GameCells> {
+        let mut computer = computer.clone();
+        computer.memory.set(0, 2).unwrap(); // Insert quarters.
+        computer.run();
+
+        let mut score = Search::new(&computer.memory);
+        let mut ball_x = Search::new(&computer.memory);
+        let mut paddle_x = Search::new(&computer.memory);
+        let mut screen = screen();
+
+        while computer.state() != &ProgramState::Done {
+            screen.extend(computer.dump_output());
+            let screen_score = screen.special(SCORE.0, SCORE.1).unwrap_or(0);
+            let screen_ball_x = screen.find(Tile::Ball.id()).map_or(0, |(x, _)| x);
+            let screen_paddle_x = screen.find(Tile::Paddle.id()).map_or(0, |(x, _)| x);
+
+            score.filter(&computer.memory, Filter::Equals(screen_score));
+            ball_x.filter(&computer.memory, Filter::Equals(screen_ball_x));
+            paddle_x.filter(&computer.memory, Filter::Equals(screen_paddle_x));
+
+            if let (Some(score), Some(ball_x), Some(paddle_x)) = (score.found(), ball_x.found(), paddle_x.found()) {
+                return Some(GameCells { score, ball_x, paddle_x });
             }
+
+            computer.input((screen_ball_x - screen_paddle_x).signum());
+            computer.run();
+        }
+
+        None
+    }
+
+    /// Plays the game without looking at the screen, keeping the paddle under the ball by reading
+    /// their positions out of memory.  Returns the final score.
+    fn play(&self, computer: &Computer) -> i64 {
+        let mut computer = computer.clone();
+        computer.memory.set(0, 2).unwrap(); // Insert quarters.
+        computer.run();
+
+        while computer.state() != &ProgramState::Done {
+            computer.output.clear();
+
+            let joystick = (computer.memory.get(self.ball_x) - computer.memory.get(self.paddle_x)).signum();
+            computer.input(joystick);
+            computer.run();
         }
+
+        computer.memory.get(self.score)
     }
 }
 
-fn main() -> std::io::Result<()> {
+/// Plays the game without a terminal, counting the arcade's memory accesses, and saves them to
+/// heatmap.csv and heatmap.ppm.
+fn save_heatmap() -> Result<(), Box<dyn std::error::Error>> {
+    let mut computer = Computer::load("input.txt")?;
+    computer.memory.set(0, 2).unwrap(); // Insert quarters.
+
+    let mut game = Game::new("Heatmap");
+    let mut heatmap = Heatmap::new();
+    heatmap.run(&mut computer)?;
+
+    while computer.state() != &ProgramState::Done {
+        game.screen.extend(computer.dump_output());
+        computer.input(game.input());
+        heatmap.run(&mut computer)?;
+    }
+
+    heatmap.save_csv("heatmap.csv")?;
+    heatmap.save_ppm("heatmap.ppm", 64, 8)?;
+    Ok(())
+}
+
+// `cargo run -- inspect` steps through the game in the intcode inspector instead of playing it,
+// `cargo run -- headless` plays part 2 without a terminal and saves the final screen to screen.ppm,
+// and `cargo run -- heatmap` maps the arcade's memory by saving a heatmap of a game.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let headless = args.iter().any(|arg| arg == "headless");
+
+    if args.iter().any(|arg| arg == "inspect") {
+        let mut computer = Computer::load("input.txt")?;
+        computer.memory.set(0, 2).unwrap(); // Insert quarters.
+        intcode::inspector::inspect(computer);
+        return Ok(());
+    }
+
+    if args.iter().any(|arg| arg == "heatmap") {
+        return save_heatmap();
+    }
+
     // Part 1: how many block tiles are on the screen when the game exits?
-    /*
-    let mut computer = Computer::from_file("input.txt")?;
+    let mut computer = Computer::load("input.txt")?;
     let mut game = Game::new("Part 1");
 
-    computer.run(&mut game);
-    game.window.getch();
-    endwin();
-    println!("Part 1: {}\n{}", game.num_blocks(), game);
-    */
+    computer.run_io(&mut game);
+    game.screen.finish()?;
+    println!("Part 1: {}", game.num_blocks());
 
     // Part 2: after inserting a quarter (2 -> memory address 0), what's the score when you win the game?
-    let mut computer = Computer::from_file("input.txt")?;
+    let mut computer = Computer::load("input.txt")?;
     let mut game = Game::new("Part 2");
-    computer.memory.set_addr(0, 2); // Insert quarters.
-    computer.run(&mut game);
-    game.window.getch();
-    endwin();
+    if !headless {
+        game = game.with_window();
+    }
+
+    computer.memory.set(0, 2).unwrap(); // Insert quarters.
+    computer.run_io(&mut game);
+    game.screen.finish()?;
+
+    if let Some(window) = &game.window {
+        window.getch();
+        endwin();
+    } else {
+        game.screen.save_ppm("screen.ppm", 8)?;
+    }
 
     if game.num_blocks() == 0 {
-        println!("Part 2: {} ", game.score);
+        println!("Part 2: {} ", game.score());
     } else {
         println!("Part 2: destroy all of the blocks to get the answer.  Left and right arrows move the paddle.");
     }
 
+    // The game can also be played by finding the ball and paddle in memory instead of decoding the screen.
+    let program = Computer::load("input.txt")?;
+    if let Some(cells) = GameCells::find(&program) {
+        println!("Part 2 (from memory): {} - {:?}", cells.play(&program), cells);
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn play_from_memory() {
+        let computer = Computer::load("input.txt").unwrap();
+        let cells = GameCells::find(&computer).unwrap();
+
+        assert_eq!(GameCells { score: 386, ball_x: 388, paddle_x: 392 }, cells);
+        assert_eq!(11641, cells.play(&computer));
+    }
+
+    #[test]
+    fn play_headless() {
+        let mut computer = Computer::load("input.txt").unwrap();
+        computer.memory.set(0, 2).unwrap(); // Insert quarters.
+
+        let mut game = Game::new("Test");
+        computer.run_io(&mut game);
+
+        assert_eq!(Ok(()), game.screen.finish());
+        assert_eq!((0, 11641), (game.num_blocks(), game.score()));
+        assert!(game.to_string().starts_with("Test - Blocks: 0 - Score: 11641\n........"));
+    }
+}
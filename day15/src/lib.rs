@@ -13,31 +13,20 @@
 // 1: moved one step in the requested direction.
 // 2: moved one step in the requested direction, new position is the location of the oxygen system.
 
-use crate::computer::Computer;
+use intcode::Computer;
 use std::collections::{VecDeque, HashSet, HashMap};
+use std::iter;
 use std::ops::Add;
 
 // What is the fewest number of movement commands required to move the repair droid from
 // its starting position to the location fo the oxygen system?
-pub mod computer;
-
 #[derive(Debug, Eq, PartialEq, Clone)]
 enum Direction {
     North, South, West, East,
 }
 
 impl Direction {
-    fn from_code(code: i32) -> Direction {
-        match code {
-            1 => Direction::North,
-            2 => Direction::South,
-            3 => Direction::West,
-            4 => Direction::East,
-            _ => panic!("Invalid direction code."),
-        }
-    }
-
-    fn to_code(&self) -> i32 {
+    fn to_code(&self) -> i64 {
         match self {
             Direction::North => 1,
             Direction::South => 2,
@@ -67,7 +56,7 @@ pub enum Status {
 }
 
 impl Status {
-    fn from_code(code: i32) -> Status {
+    fn from_code(code: i64) -> Status {
         match code {
             0 => Status::Wall,
             1 => Status::Open,
@@ -75,14 +64,6 @@ impl Status {
             _ => panic!("Invalid status code"),
         }
     }
-
-    fn to_code(&self) -> i32 {
-        match self {
-            Status::Wall => 0,
-            Status::Open => 1,
-            Status::Oxygen => 2,
-        }
-    }
 }
 
 /// Position in space.
@@ -120,8 +101,11 @@ impl Add<&Direction> for &Position {
 
 /// Moves the droid in the given direction, returning its status.
 fn move_droid(computer: &mut Computer, direction: &Direction) -> Status {
-    computer.input(direction.to_code());
-    Status::from_code(computer.run().unwrap())
+    let status = computer.outputs(iter::once(direction.to_code())).next()
+        .expect("Droid stopped without reporting a status")
+        .unwrap();
+
+    Status::from_code(status)
 }
 
 /// Returns the fewest number of movement commands required to move the repair droid from its
@@ -258,10 +242,6 @@ pub fn oxygen(oxygen_dist: &HashMap<Position, usize>) -> usize {
 /// Computes the shortest path from oxygen to each open square in the map.
 pub fn oxygen_distance(map: &HashMap<Position, Status>) -> HashMap<Position, usize> {
     // DFS through open nodes from the oxygen system until all of the open squares are explored.
-    let num_open = map.values()
-        .filter(|&square| *square == Status::Open)
-        .count();
-
     let oxygen_pos = map.iter()
         .filter_map(|(pos, square)| if *square == Status::Oxygen { Some(pos.clone()) } else { None })
         .next()
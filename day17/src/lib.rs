@@ -1,8 +1,7 @@
 use std::fmt::{Display, Formatter};
+use std::iter;
 
-use crate::computer::Computer;
-
-pub mod computer;
+use intcode::{Computer, IntcodeError};
 
 #[derive(Debug, Eq, PartialEq)]
 enum Square {
@@ -56,15 +55,15 @@ impl Position {
 }
 
 /// Boots the program, returning the sum of the alignment parameters for the scaffold intersections.
-pub fn calibration(computer: &mut Computer) -> usize {
+pub fn calibration(computer: &mut Computer) -> Result<usize, IntcodeError> {
     // Running the program for the first time prints the map.
-    computer.run();
+    let output: Vec<i64> = computer.outputs(iter::empty()).collect::<Result<_, _>>()?;
 
     // Load the output into a map.
     let mut map: Vec<Vec<Square>> = Vec::new();
     let mut line = Vec::new();
 
-    for &value in &computer.output {
+    for value in output {
         if value as u8 as char == '\n' {
             if !line.is_empty() {
                 map.push(line);
@@ -88,17 +87,17 @@ pub fn calibration(computer: &mut Computer) -> usize {
         }
     }
 
-    intersections.iter().map(|pos| pos.row * pos.col).sum()
+    Ok(intersections.iter().map(|pos| pos.row * pos.col).sum())
 }
 
 /// Returns whether the square at the given row and column is an intersection.
 /// Intersections never show up on the edge of the map, so row and col shouldn't be edges.
-fn is_intersection(map: &Vec<Vec<Square>>, pos: &Position) -> bool {
+fn is_intersection(map: &[Vec<Square>], pos: &Position) -> bool {
     if map[pos.row][pos.col] != Square::Scaffold {
         return false;
     }
 
-    for (row_offset, col_offset) in vec![(-1, 0), (1, 0), (0, -1), (0, 1)] {
+    for (row_offset, col_offset) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
         let neighbor_row = (pos.row as isize + row_offset) as usize;
         let neighbor_col = (pos.col as isize + col_offset) as usize;
 
@@ -1,15 +1,17 @@
+use std::error::Error;
+
 use day17::calibration;
-use day17::computer::Computer;
+use intcode::Computer;
 
-fn main() {
-    let computer = Computer::load("input.txt");
+fn main() -> Result<(), Box<dyn Error>> {
+    let computer = Computer::load("input.txt")?;
 
     // Part 1: sum the alignment parameters.
-    println!("Part 1: {}", calibration(&mut computer.clone()));
+    println!("Part 1: {}", calibration(&mut computer.clone())?);
 
     // Part 2: run the robot along the scaffold, collecting robots.
     let mut notify_robot = computer.clone();
-    notify_robot.memory.set(0, 2); // Interactive mode.
+    notify_robot.memory.set(0, 2)?; // Interactive mode.
 
     // Working out the sequence by hand:
     /*
@@ -28,31 +30,27 @@ A: R,8,L,4,R,4,R,10,R,8
 A,A,B,C,B,C,B,C,C,A
      */
 
-    // Main movement routine.
-    notify_robot.run();
-    notify_robot.print_output();
-    notify_robot.text_input("A,A,B,C,B,C,B,C,C,A\n");
-
-    // Subroutine A.
-    notify_robot.run();
-    notify_robot.print_output();
-    notify_robot.text_input("R,8,L,4,R,4,R,10,R,8\n");
-
-    // Subroutine B.
-    notify_robot.run();
-    notify_robot.print_output();
-    notify_robot.text_input("L,12,L,12,R,8,R,8\n");
-
-    // Subroutine C.
-    notify_robot.run();
-    notify_robot.print_output();
-    notify_robot.text_input("R,10,R,4,R,4\n");
-
-    // Continuous video feed?.
-    notify_robot.run();
-    notify_robot.print_output();
-    notify_robot.text_input("n\n");
-
-    // Run the program.
-    println!("Part 2: {:?}", notify_robot.run());
+    // The program asks for the main movement routine, subroutines A, B, and C, and whether to
+    // show a continuous video feed, reading each answer as a line of ASCII.
+    let answers = [
+        "A,A,B,C,B,C,B,C,C,A",
+        "R,8,L,4,R,4,R,10,R,8",
+        "L,12,L,12,R,8,R,8",
+        "R,10,R,4,R,4",
+        "n",
+    ];
+    let input = answers.iter().flat_map(|answer| answer.bytes().chain(Some(b'\n'))).map(i64::from);
+
+    // The program prints its prompts and the map, then the amount of dust collected.
+    let mut dust = None;
+    for value in notify_robot.outputs(input) {
+        match value? {
+            value if value < 256 => print!("{}", value as u8 as char),
+            value => dust = Some(value),
+        }
+    }
+
+    println!("Part 2: {:?}", dust);
+
+    Ok(())
 }
@@ -0,0 +1,12 @@
+use std::env;
+use std::path::Path;
+
+// Transpiles the beam scanner program into Rust, since part 2 scans it thousands of times.
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    intcode::transpile::transpile_file("input.txt", Path::new(&out_dir).join("beam.rs"))
+        .unwrap_or_else(|e| panic!("Couldn't transpile input.txt: {}", e));
+
+    println!("cargo:rerun-if-changed=input.txt");
+}
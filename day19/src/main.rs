@@ -1,8 +1,11 @@
-use crate::computer::Computer;
+use crate::beam::Computer;
+use intcode::search::{product, InputSearch};
 use std::fmt::{Display, Formatter};
-use std::time::Instant;
 
-mod computer;
+/// The beam scanner program in input.txt, transpiled to Rust by build.rs.
+mod beam {
+    include!(concat!(env!("OUT_DIR"), "/beam.rs"));
+}
 
 #[derive(Debug, Eq, PartialEq)]
 enum Point {
@@ -44,22 +47,16 @@ fn scan(computer: &mut Computer, row: i64, col: i64) -> Point {
 }
 
 /// Part 1: Returns the number of points that the tractor beam pulls in a 50x50 square.
-fn num_pulled(computer: &mut Computer) -> i32 {
-    let mut num_pulled = 0;
-    for row in 0..50 {
-        for col in 0..50 {
-            if scan(computer, row, col) == Point::Pulled {
-                num_pulled += 1;
-            }
-        }
-    }
-
-    num_pulled
+fn num_pulled(computer: &Computer) -> i32 {
+    InputSearch::new(computer, product(&[0..50, 0..50]))
+        .all(|computer, point| scan(computer, point[0], point[1]) == Point::Pulled)
+        .len() as i32
 }
 
 /// Prints the beam in a rows x cols rectange.
+#[allow(dead_code)]
 fn print_beam(computer: &mut Computer) {
-    'row: for row in 0..1200 {
+    for row in 0..1200 {
 
         print!("{row:>width$} ", row=row, width=6);
 
@@ -81,6 +78,7 @@ fn print_beam(computer: &mut Computer) {
     }
 }
 
+#[allow(dead_code)]
 fn beam_start(computer: &mut Computer, rows: i64) {
 
     for row in 0..rows {
@@ -131,16 +129,59 @@ fn find_square(side: i32) -> i32 {
 }
 
 fn main() {
-    let mut computer = Computer::load("input.txt");
+    let computer = Computer::new();
 
-    println!("Part 1: {}", num_pulled(&mut computer));
+    println!("Part 1: {}", num_pulled(&computer));
 
     // Part 2 - find the top left corner of the 100x100 square in the tractor beam.
 
     // Ran `cargo run >> output.txt` with this block to produce a bigger sample square.
-    // let start = Instant::now();
-    // print_beam(&mut computer);
+    // let start = std::time::Instant::now();
+    // print_beam(&mut computer.clone());
     // println!("Took {} ms", start.elapsed().as_millis());
 
     println!("Part 2: {}", find_square(100));
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn transpiled_matches_interpreter() {
+        let mut transpiled = Computer::new();
+        let mut interpreted = intcode::Computer::load("input.txt").unwrap();
+
+        for row in 0..50 {
+            for col in 0..50 {
+                let expected = scan_interpreted(&mut interpreted, row, col);
+                assert_eq!(Point::from(expected), scan(&mut transpiled, row, col), "row {} col {}", row, col);
+            }
+        }
+    }
+
+    #[test]
+    fn test_num_pulled() {
+        assert_eq!(num_pulled(&Computer::new()), num_pulled_interpreted());
+    }
+
+    /// Counts the points pulled in a 50x50 square with the intcode interpreter.
+    fn num_pulled_interpreted() -> i32 {
+        let mut computer = intcode::Computer::load("input.txt").unwrap();
+        let mut num_pulled = 0;
+
+        for row in 0..50 {
+            for col in 0..50 {
+                num_pulled += scan_interpreted(&mut computer, row, col) as i32;
+            }
+        }
+
+        num_pulled
+    }
+
+    /// Scans a point with the intcode interpreter, returning 1 if it's pulled by the beam.
+    fn scan_interpreted(computer: &mut intcode::Computer, row: i64, col: i64) -> i64 {
+        computer.reset();
+        computer.outputs(vec![row, col]).next().unwrap().unwrap()
+    }
+}
@@ -1,77 +1,34 @@
-use std::io::BufReader;
-use std::io::prelude::*;
-use std::fs::File;
-
-fn run_program(program: &mut Vec<i32>) {
-    let mut pc = 0;
-
-    while program[pc] != 99 {
-        let source_index_1 = program[pc + 1] as usize;
-        let source_index_2 = program[pc + 2] as usize;
-        let result_index = program[pc + 3] as usize;
-        
-        match program[pc] {
-            1 => { 
-                // 1 means add the numbers at positions pc + 1 and pc + 2, and store them in pc + 3
-                program[result_index] = program[source_index_1] + program[source_index_2]
-            }
-            2 => { 
-                // 2 means multiply the numbers at position pc + 1 and pc + 2, and store them in pc + 3
-                program[result_index] = program[source_index_1] * program[source_index_2]
-            }
-            _ => {
-                panic!("Unknown opcode {} at position {} - {:?}", program[pc], pc, program);
-            }
-        }
-
-        pc += 4
-    };
+use intcode::Computer;
+use intcode::goal::GoalSeek;
+use intcode::isa::Isa;
+use intcode::program::ProgramError;
+
+/// Runs the program with the given noun (position 1) and verb (position 2), returning the value
+/// left at position 0 after the program halts.
+fn run_program(computer: &mut Computer, noun: i64, verb: i64) -> i64 {
+    computer.memory.set(1, noun).unwrap();
+    computer.memory.set(2, verb).unwrap();
+    computer.run();
+
+    computer.memory.get(0)
 }
 
-fn main() -> std::io::Result<()> {
-    let f = File::open("input.txt")?;
-    let mut reader = BufReader::new(f);
-    let mut line = String::new();
-    reader.read_line(&mut line)?;
-    line = line.trim().to_string();
-
-    let nums = line.split(",").flat_map(str::parse::<i32>).collect::<Vec<_>>();
+fn main() -> Result<(), ProgramError> {
+    // The gravity assist program only uses the day 2 instructions.
+    let computer = Computer::load("input.txt")?.with_isa(Isa::Day2).with_strict_writes();
 
     // Part 1: replace position 1 with 12 and position 2 with 2 - what value is left at position 0 after the program halts?
-    let mut part1 = nums.clone();
-    part1[1] = 12;
-    part1[2] = 2;
-    run_program(&mut part1);
-    
-    println!("Part 1: {}", part1[0]);
+    println!("Part 1: {}", run_program(&mut computer.clone(), 12, 2));
 
     // Part 2: what pair of inputs (replacing values 1 and 2) produces the output (value 0) 19690720?
     // Calculate 100 * noun (value 1) * verb (value 2) that produce 19690720
+    let solution = GoalSeek::new(&computer, 0, 19690720)
+        .patch(1, 0..100)
+        .patch(2, 0..100)
+        .seek()
+        .expect("No noun and verb produce 19690720");
 
-    let mut i = 0;
-    let mut part2 = nums.clone();
-    while part2[0] != 19690720 {
-        let mut j = 0;
-
-        while j <= i && part2[0] != 19690720 {
-            j += 1;
-            part2 = nums.clone();
-            part2[1] = i;
-            part2[2] = j;
-            run_program(&mut part2);
-
-            if part2[0] != 19690720 {
-                part2 = nums.clone();
-                part2[1] = j;
-                part2[2] = i;
-                run_program(&mut part2);
-            }
-        }
-
-        i += 1;
-    }
-
-    println!("Part 2: {}", 100 * part2[1] + part2[2]);
+    println!("Part 2: {} ({:?})", 100 * solution.values[0] + solution.values[1], solution.strategy);
 
     Ok(())
 }
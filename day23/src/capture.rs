@@ -0,0 +1,332 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use intcode::frame::Frames;
+use intcode::{Computer, IntcodeError};
+
+use crate::network::{complete_packets, take_turn, Network, Packet};
+
+// Capture files record every packet sent and delivered on a network, one per line, in the order
+// it happened:
+//
+//   # event,tick,src,dst,x,y
+//   sent,0,0,19,76,23
+//   delivered,1,0,19,76,23
+//   sent,3,255,0,23,17874
+//
+// A packet is delivered on the first tick its receiver can read it, which is the tick after it was
+// sent if the receiver already had its turn.  Replaying a capture boots a single node and feeds it
+// the packets that were delivered to its address on the ticks they were delivered, so one node can
+// be debugged without running the rest of the network.
+
+/// Whether a captured packet was sent or delivered.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Event {
+    Sent,
+    Delivered,
+}
+
+impl Event {
+    /// Returns the event's name in a capture file.
+    fn name(self) -> &'static str {
+        match self {
+            Event::Sent => "sent",
+            Event::Delivered => "delivered",
+        }
+    }
+}
+
+/// A packet that was sent or delivered, and the tick it happened on.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Captured {
+    pub event: Event,
+    pub tick: usize,
+    pub packet: Packet,
+}
+
+/// A line in a capture file couldn't be read.  Line is 1-indexed.
+#[derive(Debug)]
+pub enum CaptureError {
+    Io(io::Error),
+    Parse { line: usize, text: String },
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::Io(e) => write!(f, "couldn't read capture: {}", e),
+            CaptureError::Parse { line, text } => write!(f, "line {}: expected event,tick,src,dst,x,y but got '{}'", line, text),
+        }
+    }
+}
+
+impl Error for CaptureError {}
+
+impl From<io::Error> for CaptureError {
+    fn from(e: io::Error) -> Self {
+        CaptureError::Io(e)
+    }
+}
+
+/// Every packet sent and delivered on a network, in the order it happened.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Capture {
+    pub packets: Vec<Captured>,
+}
+
+impl Capture {
+    /// Constructs an empty capture.
+    pub fn new() -> Capture {
+        Capture::default()
+    }
+
+    /// Adds a sent packet to the capture.
+    pub fn record_sent(&mut self, tick: usize, packet: &Packet) {
+        self.packets.push(Captured { event: Event::Sent, tick, packet: *packet });
+    }
+
+    /// Adds a delivered packet to the capture.
+    pub fn record_delivered(&mut self, tick: usize, packet: &Packet) {
+        self.packets.push(Captured { event: Event::Delivered, tick, packet: *packet });
+    }
+
+    /// Returns the packets delivered to the given address.
+    pub fn delivered_to(&self, address: i64) -> impl Iterator<Item = &Captured> {
+        self.packets.iter().filter(move |captured| captured.event == Event::Delivered && captured.packet.dst == address)
+    }
+
+    /// Returns the packets sent by the given address.
+    pub fn sent_by(&self, address: i64) -> impl Iterator<Item = &Captured> {
+        self.packets.iter().filter(move |captured| captured.event == Event::Sent && captured.packet.src == address)
+    }
+
+    /// Writes the capture to the given file.
+    pub fn save<P: AsRef<Path>>(&self, filename: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Writes the capture in the capture file format.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "# event,tick,src,dst,x,y")?;
+
+        for Captured { event, tick, packet } in &self.packets {
+            writeln!(writer, "{},{},{},{},{},{}", event.name(), tick, packet.src, packet.dst, packet.x, packet.y)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a capture from the given file.
+    pub fn load<P: AsRef<Path>>(filename: P) -> Result<Capture, CaptureError> {
+        Capture::read(BufReader::new(File::open(filename)?))
+    }
+
+    /// Reads a capture in the capture file format.  Blank lines and lines starting with '#' are skipped.
+    pub fn read<R: BufRead>(reader: R) -> Result<Capture, CaptureError> {
+        let mut capture = Capture::new();
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let text = line.trim();
+
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+
+            let parse_error = || CaptureError::Parse { line: i + 1, text: text.to_string() };
+            let (event, values) = text.split_once(',').ok_or_else(parse_error)?;
+            let event = match event.trim() {
+                "sent" => Event::Sent,
+                "delivered" => Event::Delivered,
+                _ => return Err(parse_error()),
+            };
+
+            let values = values.split(',').map(|value| value.trim().parse::<i64>()).collect::<Result<Vec<i64>, _>>();
+            match values.as_deref() {
+                Ok([tick, src, dst, x, y]) if *tick >= 0 => capture.packets.push(Captured {
+                    event,
+                    tick: *tick as usize,
+                    packet: Packet { src: *src, dst: *dst, x: *x, y: *y },
+                }),
+                _ => return Err(parse_error()),
+            }
+        }
+
+        Ok(capture)
+    }
+}
+
+/// Records every packet the network sends and delivers in the capture.
+pub fn record<'h>(network: Network<'h>, capture: &'h RefCell<Capture>) -> Network<'h> {
+    network
+        .on_send(move |tick, packet| capture.borrow_mut().record_sent(tick, packet))
+        .on_receive(move |tick, packet| capture.borrow_mut().record_delivered(tick, packet))
+}
+
+/// Boots a node at the given address and feeds it the packets delivered to that address in the
+/// capture.  Each tick delivers the packets delivered to the node on that tick, then gives the node
+/// a turn.  Returns the packets the node sent, and the ticks it sent them on.
+pub fn replay(program: &Computer, address: i64, capture: &Capture) -> Result<Vec<Captured>, (usize, IntcodeError)> {
+    let mut node = program.clone();
+    node.input(address);
+
+    let incoming: Vec<&Captured> = capture.delivered_to(address).collect();
+    let last_tick = capture.packets.iter().map(|captured| captured.tick).max().unwrap_or(0);
+
+    let mut next = 0;
+    let mut partial = Frames::new();
+    let mut sent = Vec::new();
+
+    for tick in 0..=last_tick {
+        while next < incoming.len() && incoming[next].tick <= tick {
+            node.input(incoming[next].packet.x);
+            node.input(incoming[next].packet.y);
+            next += 1;
+        }
+
+        let (output, _) = take_turn(&mut node, None)?;
+        sent.extend(complete_packets(address, &mut partial, output).into_iter().map(|packet| Captured { event: Event::Sent, tick, packet }));
+    }
+
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fault::{FaultInjector, Faults};
+    use crate::network::{Action, Nat};
+
+    /// Remembers the last packet, and wakes node 0 with it when the network is idle.  Stops the
+    /// third time the network goes idle.
+    struct Restart {
+        last: Option<Packet>,
+        idles: usize,
+    }
+
+    impl Nat for Restart {
+        fn receive(&mut self, packet: Packet) -> Action {
+            self.last = Some(packet);
+            Action::Continue
+        }
+
+        fn idle(&mut self) -> Action {
+            self.idles += 1;
+            match self.last {
+                Some(packet) if self.idles < 3 => Action::Send(Packet { src: 255, dst: 0, ..packet }),
+                _ => Action::Stop(self.idles as i64),
+            }
+        }
+    }
+
+    fn capture_puzzle() -> (Computer, Capture) {
+        let program = Computer::load("input.txt").unwrap();
+        let capture = RefCell::new(Capture::new());
+
+        let network = Network::new(&program, 50).with_nat(255, Restart { last: None, idles: 0 });
+        let mut network = record(network, &capture);
+
+        assert_eq!(Ok(3), network.run());
+        let stats = network.stats().clone();
+        drop(network);
+        let capture = capture.into_inner();
+
+        // Every packet the network sent is in the capture and was delivered once, and the NAT delivered twice.
+        let sent = capture.packets.iter().filter(|captured| captured.event == Event::Sent).count();
+        assert_eq!(sent, stats.sent.iter().sum::<usize>() + stats.nat_deliveries.len());
+        assert_eq!(2 * sent, capture.packets.len());
+        assert_eq!(2, stats.nat_deliveries.len());
+        assert_eq!(capture.sent_by(255).map(|captured| (captured.tick, captured.packet)).collect::<Vec<_>>(), stats.nat_deliveries);
+
+        (program, capture)
+    }
+
+    #[test]
+    fn write_and_read() {
+        let (_, capture) = capture_puzzle();
+
+        let mut written = Vec::new();
+        capture.write(&mut written).unwrap();
+        assert!(written.starts_with(b"# event,tick,src,dst,x,y\nsent,0,"));
+
+        assert_eq!(capture, Capture::read(&written[..]).unwrap());
+
+        match Capture::read("# event,tick,src,dst,x,y\nsent,1,2,3\n".as_bytes()) {
+            Err(CaptureError::Parse { line: 2, text }) => assert_eq!("sent,1,2,3", text),
+            result => panic!("Expected a parse error, got {:?}", result),
+        }
+
+        match Capture::read("received,1,2,3,4,5\n".as_bytes()) {
+            Err(CaptureError::Parse { line: 1, .. }) => {},
+            result => panic!("Expected a parse error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn replay_node() {
+        let (program, capture) = capture_puzzle();
+
+        // The replayed node sends the same packets it sent in the network, on the same ticks.
+        for address in 0..50 {
+            let replayed: Vec<(usize, Packet)> = replay(&program, address, &capture).unwrap().into_iter().map(|c| (c.tick, c.packet)).collect();
+            let captured: Vec<(usize, Packet)> = capture.sent_by(address).map(|c| (c.tick, c.packet)).collect();
+
+            assert_eq!(captured, replayed, "node {}", address);
+        }
+
+        assert!(capture.sent_by(0).count() > 1);
+    }
+
+    #[test]
+    fn replay_faulty_node() {
+        let program = Computer::load("input.txt").unwrap();
+        let capture = RefCell::new(Capture::new());
+
+        let faults = FaultInjector::new(3, Faults { drop: 0.1, duplicate: 0.1, delay: 0.1, reorder: 0.1 });
+        let network = Network::new(&program, 50)
+            .with_nat(255, Restart { last: None, idles: 0 })
+            .with_faults(faults)
+            .with_tick_limit(1000);
+        let mut network = record(network, &capture);
+
+        assert!(network.run().is_ok());
+        let counts = network.faults().unwrap().counts();
+        drop(network);
+        let capture = capture.into_inner();
+        assert!(counts.dropped > 0 && counts.duplicated > 0 && counts.delayed > 0 && counts.reordered > 0, "{:?}", counts);
+
+        // Replays see the packets that made it through the faults, so they send what the live nodes sent.
+        for address in 0..50 {
+            let replayed: Vec<(usize, Packet)> = replay(&program, address, &capture).unwrap().into_iter().map(|c| (c.tick, c.packet)).collect();
+            let captured: Vec<(usize, Packet)> = capture.sent_by(address).map(|c| (c.tick, c.packet)).collect();
+
+            assert_eq!(captured, replayed, "node {}", address);
+        }
+    }
+
+    #[test]
+    fn dropped_nat_packets() {
+        let program = Computer::load("input.txt").unwrap();
+        let capture = RefCell::new(Capture::new());
+
+        // Every packet the NAT sends is dropped, so the network goes idle until the NAT gives up.
+        let faults = FaultInjector::new(0, Faults::default()).with_link(255, 0, Faults { drop: 1.0, ..Faults::default() });
+        let network = Network::new(&program, 50).with_nat(255, Restart { last: None, idles: 0 }).with_faults(faults);
+        let mut network = record(network, &capture);
+
+        assert_eq!(Ok(3), network.run());
+        assert!(network.stats().nat_deliveries.is_empty());
+        drop(network);
+
+        let capture = capture.into_inner();
+        assert_eq!(2, capture.sent_by(255).count());
+        assert_eq!(0, capture.delivered_to(0).filter(|captured| captured.packet.src == 255).count());
+    }
+}
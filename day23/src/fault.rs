@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::network::Packet;
+
+// Fault injection sits between a node's output and the destination's input, and makes the
+// network unreliable.  Each packet can be dropped, duplicated, delayed by a few ticks, or swapped
+// with the next packet on the same link.  The probabilities can be different for each link, and
+// the faults come from a seeded generator so a failing run can be repeated:
+//
+//   let faults = FaultInjector::new(42, Faults { drop: 0.01, ..Faults::default() })
+//       .with_link(255, 0, Faults::default());
+//   let result = Network::new(&nic, 50).with_faults(faults).run();
+
+/// Most ticks a delayed packet is delivered late by.
+const MAX_DELAY: usize = 3;
+
+/// Probability of each fault happening to a packet.  The injector clamps probabilities to 0 to 1,
+/// and treats NaN as 0.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Faults {
+    /// The packet is never delivered.
+    pub drop: f64,
+    /// The packet is delivered twice.
+    pub duplicate: f64,
+    /// The packet is delivered 1 to MAX_DELAY ticks late.
+    pub delay: f64,
+    /// The packet is delivered after the next packet on the same link, or at the end of the tick.
+    pub reorder: f64,
+}
+
+impl Faults {
+    /// Returns the faults with every probability between 0 and 1.
+    fn clamped(self) -> Faults {
+        let clamp = |p: f64| if p.is_nan() { 0.0 } else { p.clamp(0.0, 1.0) };
+        Faults {
+            drop: clamp(self.drop),
+            duplicate: clamp(self.duplicate),
+            delay: clamp(self.delay),
+            reorder: clamp(self.reorder),
+        }
+    }
+}
+
+/// Number of packets each fault happened to.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct FaultCounts {
+    pub dropped: usize,
+    pub duplicated: usize,
+    pub delayed: usize,
+    pub reordered: usize,
+}
+
+/// Decides which faults happen to each packet, and holds the packets that are delivered late.
+#[derive(Debug, Clone)]
+pub struct FaultInjector {
+    rng: StdRng,
+    default: Faults,
+    links: HashMap<(i64, i64), Faults>,
+    /// Most ticks a packet is delayed by - MAX_DELAY, or 1 in tests that need a fixed delay.
+    max_delay: usize,
+    /// Delayed packets, and the tick they're delivered on.
+    delayed: Vec<(usize, Packet)>,
+    /// Reordered packets waiting for the next packet on their link.
+    held: HashMap<(i64, i64), Packet>,
+    counts: FaultCounts,
+}
+
+impl FaultInjector {
+    /// Constructs an injector that applies the faults to every link, with the given random seed.
+    pub fn new(seed: u64, faults: Faults) -> FaultInjector {
+        FaultInjector {
+            rng: StdRng::seed_from_u64(seed),
+            default: faults.clamped(),
+            links: HashMap::new(),
+            max_delay: MAX_DELAY,
+            delayed: Vec::new(),
+            held: HashMap::new(),
+            counts: FaultCounts::default(),
+        }
+    }
+
+    /// Applies different faults to packets sent from src to dst.
+    pub fn with_link(mut self, src: i64, dst: i64, faults: Faults) -> Self {
+        self.links.insert((src, dst), faults.clamped());
+        self
+    }
+
+    /// Returns the number of packets each fault has happened to.
+    pub fn counts(&self) -> FaultCounts {
+        self.counts
+    }
+
+    /// Returns true if there are packets that haven't been delivered yet.
+    pub(crate) fn in_flight(&self) -> bool {
+        !self.delayed.is_empty() || !self.held.is_empty()
+    }
+
+    /// Decides what happens to a packet sent on the given tick.  Returns the packets to deliver now.
+    pub(crate) fn inject(&mut self, tick: usize, packet: Packet) -> Vec<Packet> {
+        let link = (packet.src, packet.dst);
+        let faults = self.links.get(&link).copied().unwrap_or(self.default);
+
+        if self.rng.gen_bool(faults.drop) {
+            self.counts.dropped += 1;
+            return Vec::new();
+        }
+
+        let copies = if self.rng.gen_bool(faults.duplicate) {
+            self.counts.duplicated += 1;
+            2
+        } else {
+            1
+        };
+
+        let mut deliver = Vec::new();
+        for _ in 0..copies {
+            if self.rng.gen_bool(faults.delay) {
+                self.counts.delayed += 1;
+                let due = tick + self.rng.gen_range(1..=self.max_delay);
+                self.delayed.push((due, packet));
+            } else if !self.held.contains_key(&link) && self.rng.gen_bool(faults.reorder) {
+                self.counts.reordered += 1;
+                self.held.insert(link, packet);
+            } else {
+                deliver.push(packet);
+                deliver.extend(self.held.remove(&link));
+            }
+        }
+
+        deliver
+    }
+
+    /// Returns the packets to deliver at the end of a tick: delayed packets that are due, and
+    /// reordered packets that no other packet overtook.
+    pub(crate) fn release(&mut self, tick: usize) -> Vec<Packet> {
+        let mut due = Vec::new();
+        self.delayed.retain(|&(at, packet)| {
+            if at <= tick {
+                due.push(packet);
+            }
+
+            at > tick
+        });
+
+        let mut held: Vec<((i64, i64), Packet)> = self.held.drain().collect();
+        held.sort_by_key(|&(link, _)| link);
+        due.extend(held.into_iter().map(|(_, packet)| packet));
+
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{Action, Nat, Network, NetworkError};
+    use intcode::Computer;
+
+    fn packet(src: i64, dst: i64, x: i64) -> Packet {
+        Packet { src, dst, x, y: 0 }
+    }
+
+    #[test]
+    fn faults() {
+        let always = |faults: Faults| FaultInjector::new(1, faults);
+
+        let mut drop = always(Faults { drop: 1.0, ..Faults::default() });
+        assert_eq!(Vec::<Packet>::new(), drop.inject(0, packet(0, 1, 5)));
+
+        let mut duplicate = always(Faults { duplicate: 1.0, ..Faults::default() });
+        assert_eq!(vec![packet(0, 1, 5), packet(0, 1, 5)], duplicate.inject(0, packet(0, 1, 5)));
+
+        let mut delay = always(Faults { delay: 1.0, ..Faults::default() });
+        delay.max_delay = 1;
+        assert!(delay.inject(0, packet(0, 1, 5)).is_empty());
+        assert!(delay.in_flight());
+        assert_eq!(vec![packet(0, 1, 5)], delay.release(1));
+        assert!(!delay.in_flight());
+
+        // The first packet is held until the second overtakes it.  The third is held until the end of the tick.
+        let mut reorder = always(Faults { reorder: 1.0, ..Faults::default() });
+        assert!(reorder.inject(0, packet(0, 1, 1)).is_empty());
+        assert_eq!(vec![packet(0, 1, 2), packet(0, 1, 1)], reorder.inject(0, packet(0, 1, 2)));
+        assert!(reorder.inject(0, packet(0, 1, 3)).is_empty());
+        assert_eq!(vec![packet(0, 1, 3)], reorder.release(0));
+
+        assert_eq!(FaultCounts { dropped: 0, duplicated: 0, delayed: 0, reordered: 2 }, reorder.counts());
+    }
+
+    #[test]
+    fn out_of_range() {
+        let faults = Faults { drop: f64::NAN, duplicate: 1.5, delay: -0.5, reorder: f64::NEG_INFINITY };
+        let mut faults = FaultInjector::new(1, faults).with_link(0, 2, Faults { drop: 7.0, ..Faults::default() });
+
+        assert_eq!(vec![packet(0, 1, 5), packet(0, 1, 5)], faults.inject(0, packet(0, 1, 5)));
+        assert!(faults.inject(0, packet(0, 2, 5)).is_empty());
+        assert_eq!(FaultCounts { dropped: 1, duplicated: 1, delayed: 0, reordered: 0 }, faults.counts());
+    }
+
+    #[test]
+    fn per_link() {
+        let mut faults = FaultInjector::new(1, Faults { drop: 1.0, ..Faults::default() })
+            .with_link(0, 1, Faults::default());
+
+        assert_eq!(vec![packet(0, 1, 5)], faults.inject(0, packet(0, 1, 5)));
+        assert!(faults.inject(0, packet(1, 0, 5)).is_empty());
+    }
+
+    /// Two nodes count to 10 by passing the count back and forth, and send 10 to address 2.  Each
+    /// node ignores counts lower than its own, and sends its count again if it doesn't get a packet
+    /// for 50 reads, so the count gets through lost, repeated and out of order packets.
+    fn counter() -> Computer {
+        Computer::new(vec![
+            // 0: other = 1 - address, count = 1 on node 0 and 0 on node 1.  Node 0 sends first.
+            3, 200, 1002, 200, -1, 201, 1001, 201, 1, 201, 1008, 200, 0, 202, 1006, 200, 20, 1105, 1, 26,
+            // 20: send (other, count, 0)
+            4, 201, 4, 202, 104, 0,
+            // 26: 50 reads until the count is sent again
+            1101, 50, 0, 203,
+            // 30: read X - on -1, count down and send the count again if it gets to 0
+            3, 204, 1008, 204, -1, 206, 1006, 206, 49, 1001, 203, -1, 203, 1005, 203, 30, 1105, 1, 20,
+            // 49: read Y, send (2, X, 0) if X is 10
+            3, 205, 1007, 204, 10, 206, 1005, 206, 67, 104, 2, 4, 204, 104, 0, 1105, 1, 30,
+            // 67: ignore X if it's lower than the count, otherwise send X + 1
+            7, 204, 202, 206, 1005, 206, 30, 1001, 204, 1, 202, 1105, 1, 20,
+        ])
+    }
+
+    /// Forwards each packet to the next address, without retrying (like the relay in network.rs).
+    fn relay() -> Computer {
+        Computer::new(vec![
+            3, 100, 1001, 100, 1, 101, 4, 101, 4, 100, 104, 0,
+            3, 102, 1008, 102, -1, 104, 1005, 104, 12, 3, 103, 1001, 103, 1, 103, 4, 101, 4, 102, 4, 103,
+            1105, 1, 12,
+        ])
+    }
+
+    /// Stops with the first X it receives.
+    struct Done;
+
+    impl Nat for Done {
+        fn receive(&mut self, packet: Packet) -> Action {
+            Action::Stop(packet.x)
+        }
+    }
+
+    #[test]
+    fn retrying_program_converges() {
+        let counter = counter();
+        let faults = Faults { drop: 0.3, duplicate: 0.2, delay: 0.2, reorder: 0.2 };
+
+        for seed in 0..10 {
+            let mut network = Network::new(&counter, 2)
+                .with_faults(FaultInjector::new(seed, faults))
+                .with_nat(2, Done)
+                .with_idle_polls(100)
+                .with_tick_limit(10_000);
+
+            assert_eq!(Ok(10), network.run(), "seed {}", seed);
+            assert!(network.faults().unwrap().counts().dropped > 0, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn relay_deadlocks() {
+        let relay = relay();
+
+        // Without drops, every packet eventually gets through.
+        let faults = Faults { duplicate: 0.3, delay: 0.3, reorder: 0.3, drop: 0.0 };
+        for seed in 0..10 {
+            let mut network = Network::new(&relay, 3).with_faults(FaultInjector::new(seed, faults)).with_nat(3, Done);
+            assert!(network.run().is_ok(), "seed {}", seed);
+        }
+
+        let mut network = Network::new(&relay, 3)
+            .with_faults(FaultInjector::new(0, Faults { drop: 1.0, ..Faults::default() }))
+            .with_nat(3, Done);
+
+        assert!(matches!(network.run(), Err(NetworkError::Deadlocked { .. })));
+        assert_eq!(3, network.faults().unwrap().counts().dropped);
+    }
+}
@@ -1,8 +1,16 @@
-use crate::computer::Computer;
-use crate::computer::ProgramState::WaitingForInput;
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::env;
+use std::error::Error;
 
-mod computer;
+use intcode::Computer;
+
+use crate::capture::{record, replay, Capture, Captured, Event};
+use crate::fault::{FaultInjector, Faults};
+use crate::network::{Action, Nat, Network, NetworkError, Packet, Schedule, Stats};
+
+mod capture;
+mod fault;
+mod network;
 
 // Boot 50 computers, provide network address as input (0 to 49)
 // Packets have two values named X and Y, and are queued by the recipient in the order they're received.
@@ -11,118 +19,152 @@ mod computer;
 // then the Y value.  Packet is removed from the queue once it's read.
 // Input and output instructions never block.
 
-/// Returns the Y value of the first packet sent to address 255.
-fn part1() -> i64 {
-    let computer = Computer::load("input.txt");
-
-    let mut networked_computers: Vec<Computer> = (0..50).map(|i| {
-        let mut networked_computer = computer.clone();
-        networked_computer.input(i);
-        networked_computer
-    }).collect();
-
-    loop {
-        for i in 0..networked_computers.len() {
-            let mut comp = &mut networked_computers[i];
-            if comp.state == WaitingForInput && comp.input.is_empty() {
-                comp.input(-1);
-            }
-
-            comp.run();
-            let output = comp.dump_output();
-
-            if !output.is_empty() && output.len() % 3 != 0 {
-                panic!("Computer {} produced an incomplete packet {:?}", i, output);
-            }
-
-            for window in output.chunks_exact(3) {
-                let (address, x, y) = (window[0] as usize, window[1], window[2]);
-
-                // If this is the first packet sent to 255, the Y value is the answer.
-                if window[0] == 255 {
-                    return y;
-                }
-
-                // Otherwise send the X and Y values in the packet to the destination.
-                networked_computers[address].input(x);
-                networked_computers[address].input(y);
-            }
+/// NAT for part 1, which stops the network with the Y value of the first packet it receives.
+struct FirstPacket;
+
+impl Nat for FirstPacket {
+    fn receive(&mut self, packet: Packet) -> Action {
+        Action::Stop(packet.y)
+    }
+}
+
+/// NAT for part 2, which sends the last packet it received to address 0 when the network is idle,
+/// and stops the network once it's about to deliver the same Y value twice in a row.
+#[derive(Default)]
+struct IdleRestart {
+    last_received: Option<Packet>,
+    last_delivered_y: Option<i64>,
+}
+
+impl Nat for IdleRestart {
+    fn receive(&mut self, packet: Packet) -> Action {
+        self.last_received = Some(packet);
+        Action::Continue
+    }
+
+    fn idle(&mut self) -> Action {
+        let packet = match self.last_received {
+            Some(packet) => packet,
+            None => return Action::Continue,
+        };
+
+        if self.last_delivered_y == Some(packet.y) {
+            return Action::Stop(packet.y);
         }
+
+        self.last_delivered_y = Some(packet.y);
+        Action::Send(Packet { src: 255, dst: 0, x: packet.x, y: packet.y })
     }
 }
 
+/// Returns the Y value of the first packet sent to address 255.
+fn part1(computer: &Computer) -> Result<i64, NetworkError> {
+    Network::new(computer, 50).with_nat(255, FirstPacket).run()
+}
+
 /// Returns the first Y value delivered by the NAT to the computer at address 0 twice in a row.
 /// Packets sent to address 255 are handled by a 'NAT', which sends the last packet it received to
 /// address 0 if the network is idle (e.g. if all of the computers have an empty packet queue
 /// and are continuously trying to receive packets without sending).
-fn part2() -> i64 {
-    let computer = Computer::load("input.txt");
-
-    let mut networked_computers: Vec<Computer> = (0..50).map(|i| {
-        let mut networked_computer = computer.clone();
-        networked_computer.input(i);
-        networked_computer
-    }).collect();
-
-    let (mut nat_x, mut nat_y) = (None, None);
-    let mut last_delivered_y = None;
-
-    loop {
-        // Network is idle if all computers have empty input buffers and aren't producing output.
-        let mut idle = true;
-
-        for i in 0..networked_computers.len() {
-            let mut comp = &mut networked_computers[i];
-            if comp.state == WaitingForInput && comp.input.is_empty() {
-                comp.input(-1);
-            } else {
-                idle = false;
-            }
-
-            comp.run();
-            let output = comp.dump_output();
-
-            if !output.is_empty() && output.len() % 3 != 0 {
-                panic!("Computer {} produced an incomplete packet {:?}", i, output);
-            }
-
-            if !output.is_empty() {
-                idle = false;
-            }
-
-            for window in output.chunks_exact(3) {
-                let (address, x, y) = (window[0] as usize, window[1], window[2]);
-
-                // Packets sent to 255 are sent to the NAT, not a computer.
-                if address == 255 {
-                    nat_x = Some(x);
-                    nat_y = Some(y);
-                } else {
-                    // Otherwise send the X and Y values in the packet to the destination.
-                    networked_computers[address].input(x);
-                    networked_computers[address].input(y);
-                }
-
-            }
-        }
+/// Also returns the network's stats and the number of packets the NAT received, and records every
+/// packet in the capture.
+fn part2(computer: &Computer, schedule: Schedule, capture: &RefCell<Capture>) -> Result<(i64, Stats, usize), NetworkError> {
+    let mut nat_received = 0;
+
+    let network = Network::new(computer, 50)
+        .with_schedule(schedule)
+        .with_nat(255, IdleRestart::default())
+        .on_receive(|_, packet| if packet.dst == 255 { nat_received += 1 });
+    let mut network = record(network, capture);
+
+    let y = network.run()?;
+    let stats = network.stats().clone();
+    drop(network);
+
+    Ok((y, stats, nat_received))
+}
 
-        // If all of the computers are idle, the NAT sends the last packet it received to 0.
-        if idle {
-            // Answer is the first Y value delivered by the NAT twice in a row.
-            if last_delivered_y != None && nat_y == last_delivered_y {
-                return nat_y.unwrap();
-            } else {
-                last_delivered_y = nat_y;
-            }
+/// Replays the packets sent to the node at address in the capture file, and prints the packets
+/// it sends that don't match the capture.
+fn replay_node(computer: &Computer, filename: &str, address: i64) -> Result<(), Box<dyn Error>> {
+    let capture = Capture::load(filename)?;
+    let replayed = replay(computer, address, &capture).map_err(|(pc, e)| format!("{} at pc {}", e, pc))?;
+    let captured: Vec<&Captured> = capture.sent_by(address).collect();
+
+    println!("Node {} sent {} packets in the capture and {} in the replay", address, captured.len(), replayed.len());
+    for i in 0..captured.len().max(replayed.len()) {
+        let (expected, actual) = (captured.get(i).map(|c| c.packet), replayed.get(i).map(|c| c.packet));
+        if expected != actual {
+            println!("  packet {}: captured {:?}, replayed {:?}", i, expected, actual);
+        }
+    }
 
-            networked_computers[0].input(nat_x.unwrap());
-            networked_computers[0].input(nat_y.unwrap());
+    Ok(())
+}
 
+/// Runs part 2 on networks that drop, duplicate, delay and reorder packets between nodes, and prints
+/// whether each one still converges on the answer and how many packets the NAT had to deliver.
+/// The NAT's own deliveries to address 0 are reliable.
+fn faulty_networks(computer: &Computer, expected: i64) {
+    let profiles = [
+        ("drop 1%", Faults { drop: 0.01, ..Faults::default() }),
+        ("duplicate 10%", Faults { duplicate: 0.1, ..Faults::default() }),
+        ("delay 10%", Faults { delay: 0.1, ..Faults::default() }),
+        ("reorder 10%", Faults { reorder: 0.1, ..Faults::default() }),
+        ("all 5%", Faults { drop: 0.05, duplicate: 0.05, delay: 0.05, reorder: 0.05 }),
+    ];
+
+    for (name, faults) in profiles.iter() {
+        for seed in 0..3 {
+            let mut network = Network::new(computer, 50)
+                .with_nat(255, IdleRestart::default())
+                .with_faults(FaultInjector::new(seed, *faults).with_link(255, 0, Faults::default()))
+                .with_tick_limit(10_000);
+
+            let outcome = match network.run() {
+                Ok(y) if y == expected => format!("converged on {}", y),
+                Ok(y) => format!("converged on the wrong answer {}", y),
+                Err(e) => e.to_string(),
+            };
+
+            println!("{:>13} seed {}: {} after {} NAT deliveries - {:?}",
+                     name, seed, outcome, network.stats().nat_deliveries.len(), network.faults().unwrap().counts());
         }
     }
 }
 
-fn main() {
-    println!("Part 1: {}", part1());
-    println!("Part 2: {}", part2());
+// `cargo run -- stats` prints part 2's network stats, `cargo run -- capture <file>` writes part 2's
+// packets to a capture file, `cargo run -- replay <file> <address>` replays a capture to one node,
+// and `cargo run -- faults` runs part 2 on unreliable networks.
+fn main() -> Result<(), Box<dyn Error>> {
+    let computer = Computer::load("input.txt")?;
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let [command, filename, address] = args.as_slice() {
+        if command == "replay" {
+            return replay_node(&computer, filename, address.parse()?);
+        }
+    }
+
+    println!("Part 1: {}", part1(&computer)?);
+
+    let capture = RefCell::new(Capture::new());
+    let (y, stats, nat_received) = part2(&computer, Schedule::RoundRobin, &capture)?;
+    let capture = capture.into_inner();
+    println!("Part 2: {} ({} packets over {} ticks, NAT received {} and delivered {})",
+             y, capture.packets.iter().filter(|captured| captured.event == Event::Sent).count(), stats.queue_depths.len(), nat_received, stats.nat_deliveries.len());
+
+    match args.as_slice() {
+        [command] if command == "stats" => print!("{}", stats),
+        [command, filename] if command == "capture" => capture.save(filename)?,
+        [command] if command == "faults" => faulty_networks(&computer, y),
+        _ => {},
+    }
+
+    // The answer shouldn't depend on the order the nodes run in.
+    for schedule in [Schedule::Random { seed: 2019 }, Schedule::Steps(100)] {
+        println!("Part 2 ({:?}): {}", schedule, part2(&computer, schedule, &RefCell::new(Capture::new()))?.0);
+    }
+
+    Ok(())
 }
@@ -0,0 +1,573 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::iter;
+
+use intcode::frame::{Frame, Frames};
+use intcode::{Computer, IntcodeError, ProgramState};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::fault::FaultInjector;
+
+// A network boots a copy of the NIC program for each address, passing the address as its first
+// input.  Nodes send packets by outputting the destination address followed by X and Y, and
+// receive them as X then Y inputs - a node that reads from an empty queue gets -1.
+//
+// Addresses that aren't nodes can be handled by a Nat, which sees every packet sent to its
+// address and gets a chance to send packets whenever the network goes idle.  Either can stop the
+// network with an answer:
+//
+//   let y = Network::new(&nic, 50).with_nat(255, FirstY).run()?;
+
+/// Default number of -1 inputs in a row that a node has to read without sending before it counts
+/// as idle.  A node that reads -1 once might just be checking the queue between packets.
+const IDLE_POLLS: usize = 2;
+
+/// A packet of X and Y values, and the address that sent it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Packet {
+    pub src: i64,
+    pub dst: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+/// Three values a node outputs to send a packet: the destination address, then X and Y.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct Outgoing {
+    dst: i64,
+    x: i64,
+    y: i64,
+}
+
+impl Frame for Outgoing {
+    const ARITY: usize = 3;
+
+    fn decode(values: &[i64]) -> Outgoing {
+        Outgoing { dst: values[0], x: values[1], y: values[2] }
+    }
+}
+
+/// What the network does after a Nat handles an event.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Action {
+    /// Keep running.
+    Continue,
+    /// Send the packet, then keep running.
+    Send(Packet),
+    /// Stop the network with the given answer.
+    Stop(i64),
+}
+
+/// Handler for packets sent to a special address.
+pub trait Nat {
+    /// Called with each packet sent to the Nat's address.
+    fn receive(&mut self, packet: Packet) -> Action;
+
+    /// Called when every node is idle.  If no Nat sends a packet, the network is deadlocked.
+    fn idle(&mut self) -> Action {
+        Action::Continue
+    }
+}
+
+/// Order the network runs its nodes in.  Each tick gives every node that hasn't halted one turn.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Schedule {
+    /// Nodes take turns in address order, and run until they block waiting for input.
+    RoundRobin,
+    /// Nodes take turns in a random order each tick, and run until they block waiting for input.
+    Random { seed: u64 },
+    /// Nodes take turns in address order, and run at most the given number of instructions.
+    Steps(usize),
+}
+
+/// Reason a network couldn't keep running.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum NetworkError {
+    /// A node's program couldn't be executed.
+    Node { address: usize, pc: usize, error: IntcodeError },
+    /// A packet was sent to an address without a node or a Nat.
+    UnknownAddress(Packet),
+    /// Every node is idle, and no Nat sent a packet to wake them up.
+    Deadlocked { tick: usize },
+    /// The network ran for the tick limit without stopping.
+    TickLimit { tick: usize },
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::Node { address, pc, error } => write!(f, "node {}: {} at pc {}", address, error, pc),
+            NetworkError::UnknownAddress(packet) => write!(f, "{} sent a packet to unknown address {}", packet.src, packet.dst),
+            NetworkError::Deadlocked { tick } => write!(f, "network deadlocked at tick {}", tick),
+            NetworkError::TickLimit { tick } => write!(f, "network didn't stop after {} ticks", tick),
+        }
+    }
+}
+
+impl Error for NetworkError {}
+
+/// Callback for packets sent or received, and a tick.
+type Hook<'h> = Box<dyn FnMut(usize, &Packet) + 'h>;
+
+/// A network of NIC programs, and the Nats that handle special addresses.
+pub struct Network<'h> {
+    nodes: Vec<Computer>,
+    nats: HashMap<i64, Box<dyn Nat + 'h>>,
+    schedule: Schedule,
+    rng: StdRng,
+    faults: Option<FaultInjector>,
+    tick_limit: Option<usize>,
+    idle_polls: usize,
+    on_send: Vec<Hook<'h>>,
+    on_receive: Vec<Hook<'h>>,
+
+    tick: usize,
+    /// Tick of each node's next turn.
+    next_turn: Vec<usize>,
+    /// Values output by each node that don't make up a full packet yet.
+    partial: Vec<Frames<Outgoing>>,
+    /// Number of -1 inputs each node has read in a row without sending or receiving a packet.
+    empty_polls: Vec<usize>,
+    stats: Stats,
+}
+
+/// What a network has done so far.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Stats {
+    /// Number of packets each node sent.
+    pub sent: Vec<usize>,
+    /// Number of packets delivered to each node's queue.
+    pub received: Vec<usize>,
+    /// Number of packets waiting in each node's queue at the end of each tick.
+    pub queue_depths: Vec<Vec<usize>>,
+    /// Packets sent by Nats that were delivered, and the tick they were delivered on.
+    pub nat_deliveries: Vec<(usize, Packet)>,
+}
+
+impl Stats {
+    fn new(nodes: usize) -> Stats {
+        Stats { sent: vec![0; nodes], received: vec![0; nodes], ..Stats::default() }
+    }
+
+    /// Returns the deepest each node's queue got at the end of a tick.
+    pub fn max_queue_depths(&self) -> Vec<usize> {
+        (0..self.sent.len())
+            .map(|node| self.queue_depths.iter().map(|depths| depths[node]).max().unwrap_or(0))
+            .collect()
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ticks, {} NAT deliveries", self.queue_depths.len(), self.nat_deliveries.len())?;
+        writeln!(f, "node  sent  received  max queue")?;
+
+        for (node, max_depth) in self.max_queue_depths().into_iter().enumerate() {
+            writeln!(f, "{:>4}  {:>4}  {:>8}  {:>9}", node, self.sent[node], self.received[node], max_depth)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'h> Network<'h> {
+    /// Constructs a network of nodes running the program, at addresses 0 to nodes - 1.
+    pub fn new(program: &Computer, nodes: usize) -> Network<'h> {
+        let count = nodes;
+        let nodes = (0..count)
+            .map(|address| {
+                let mut node = program.clone();
+                node.input(address as i64);
+                node
+            })
+            .collect();
+
+        Network {
+            nodes,
+            nats: HashMap::new(),
+            schedule: Schedule::RoundRobin,
+            rng: StdRng::seed_from_u64(0),
+            faults: None,
+            tick_limit: None,
+            idle_polls: IDLE_POLLS,
+            on_send: Vec::new(),
+            on_receive: Vec::new(),
+            tick: 0,
+            next_turn: vec![0; count],
+            partial: vec![Frames::new(); count],
+            empty_polls: vec![0; count],
+            stats: Stats::new(count),
+        }
+    }
+
+    /// Handles packets sent to the address with the Nat.
+    pub fn with_nat<N: Nat + 'h>(mut self, address: i64, nat: N) -> Self {
+        self.nats.insert(address, Box::new(nat));
+        self
+    }
+
+    /// Runs nodes in the given order.
+    pub fn with_schedule(mut self, schedule: Schedule) -> Self {
+        if let Schedule::Random { seed } = schedule {
+            self.rng = StdRng::seed_from_u64(seed);
+        }
+
+        self.schedule = schedule;
+        self
+    }
+
+    /// Passes every packet through the fault injector before it's delivered.
+    pub fn with_faults(mut self, faults: FaultInjector) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// Stops the network with an error if it's still running after the given number of ticks.
+    pub fn with_tick_limit(mut self, ticks: usize) -> Self {
+        self.tick_limit = Some(ticks);
+        self
+    }
+
+    /// Test helper that sets the number of -1 inputs in a row a node has to read before it counts
+    /// as idle, so test programs that resend packets after a timeout aren't stopped as idle.
+    #[cfg(test)]
+    pub(crate) fn with_idle_polls(mut self, polls: usize) -> Self {
+        self.idle_polls = polls;
+        self
+    }
+
+    /// Calls the hook with every packet a node or Nat sends, before it's delivered.
+    pub fn on_send<F: FnMut(usize, &Packet) + 'h>(mut self, hook: F) -> Self {
+        self.on_send.push(Box::new(hook));
+        self
+    }
+
+    /// Calls the hook with every packet delivered to a node's queue or a Nat, and the first tick the
+    /// receiver can read it on.  A packet delivered to a node after the node's turn is read on the
+    /// next tick.
+    pub fn on_receive<F: FnMut(usize, &Packet) + 'h>(mut self, hook: F) -> Self {
+        self.on_receive.push(Box::new(hook));
+        self
+    }
+
+    /// Returns what the network has done so far.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Returns the fault injector, if the network has one.
+    pub fn faults(&self) -> Option<&FaultInjector> {
+        self.faults.as_ref()
+    }
+
+    /// Runs the network until a Nat stops it, returning the Nat's answer.
+    pub fn run(&mut self) -> Result<i64, NetworkError> {
+        loop {
+            if let Some(answer) = self.run_tick()? {
+                return Ok(answer);
+            }
+
+            if self.tick_limit.is_some_and(|limit| self.tick >= limit) {
+                return Err(NetworkError::TickLimit { tick: self.tick });
+            }
+        }
+    }
+
+    /// Gives every node a turn, then wakes the Nats if the network is idle.  Returns the answer
+    /// if a Nat stopped the network.
+    fn run_tick(&mut self) -> Result<Option<i64>, NetworkError> {
+        let mut order: Vec<usize> = (0..self.nodes.len()).collect();
+        if let Schedule::Random { .. } = self.schedule {
+            order.shuffle(&mut self.rng);
+        }
+
+        for address in order {
+            for packet in self.run_node(address)? {
+                if let Some(answer) = self.send(packet)? {
+                    return Ok(Some(answer));
+                }
+            }
+        }
+
+        self.tick += 1;
+
+        // Packets the fault injector held back during the tick arrive before the next one.
+        let released = match &mut self.faults {
+            Some(faults) => faults.release(self.tick),
+            None => Vec::new(),
+        };
+
+        for packet in released {
+            if let Some(answer) = self.deliver(packet)? {
+                return Ok(Some(answer));
+            }
+        }
+
+        self.stats.queue_depths.push(self.nodes.iter().map(|node| node.input_len().div_ceil(2)).collect());
+
+        if !self.is_idle() {
+            return Ok(None);
+        }
+
+        let mut woken = Vec::new();
+        let mut addresses: Vec<i64> = self.nats.keys().copied().collect();
+        addresses.sort_unstable();
+
+        for address in addresses {
+            match self.nats.get_mut(&address).unwrap().idle() {
+                Action::Continue => {},
+                Action::Send(packet) => woken.push(packet),
+                Action::Stop(answer) => return Ok(Some(answer)),
+            }
+        }
+
+        if woken.is_empty() {
+            return Err(NetworkError::Deadlocked { tick: self.tick });
+        }
+
+        for packet in woken {
+            if let Some(answer) = self.send(packet)? {
+                return Ok(Some(answer));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Runs the node for one turn, returning the packets it sent.
+    fn run_node(&mut self, address: usize) -> Result<Vec<Packet>, NetworkError> {
+        let limit = match self.schedule {
+            Schedule::Steps(steps) => Some(steps),
+            _ => None,
+        };
+
+        let (output, polls) = take_turn(&mut self.nodes[address], limit)
+            .map_err(|(pc, error)| NetworkError::Node { address, pc, error })?;
+        self.next_turn[address] = self.tick + 1;
+        self.empty_polls[address] += polls;
+
+        let packets = complete_packets(address as i64, &mut self.partial[address], output);
+        self.stats.sent[address] += packets.len();
+        if !packets.is_empty() {
+            self.empty_polls[address] = 0;
+        }
+
+        Ok(packets)
+    }
+
+    /// Sends the packet through the fault injector, and delivers the packets that come out of it.
+    /// Returns the answer if a Nat stopped the network.
+    fn send(&mut self, packet: Packet) -> Result<Option<i64>, NetworkError> {
+        let tick = self.tick;
+        self.on_send.iter_mut().for_each(|hook| hook(tick, &packet));
+
+        let packets = match &mut self.faults {
+            Some(faults) => faults.inject(tick, packet),
+            None => vec![packet],
+        };
+
+        for packet in packets {
+            if let Some(answer) = self.deliver(packet)? {
+                return Ok(Some(answer));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Delivers the packet to a node's queue or a Nat.  Returns the answer if a Nat stopped the network.
+    fn deliver(&mut self, packet: Packet) -> Result<Option<i64>, NetworkError> {
+        if self.nats.contains_key(&packet.dst) {
+            self.received(self.tick, &packet);
+
+            return match self.nats.get_mut(&packet.dst).unwrap().receive(packet) {
+                Action::Continue => Ok(None),
+                Action::Send(reply) => self.send(reply),
+                Action::Stop(answer) => Ok(Some(answer)),
+            };
+        }
+
+        let node = match usize::try_from(packet.dst).ok().filter(|&dst| dst < self.nodes.len()) {
+            Some(dst) => dst,
+            None => return Err(NetworkError::UnknownAddress(packet)),
+        };
+
+        self.received(self.next_turn[node], &packet);
+        self.nodes[node].input(packet.x);
+        self.nodes[node].input(packet.y);
+        self.empty_polls[node] = 0;
+        self.stats.received[node] += 1;
+
+        Ok(None)
+    }
+
+    /// Calls the receive hooks with a packet that's been delivered, and the tick it can be read on,
+    /// and counts it if a Nat sent it.
+    fn received(&mut self, readable: usize, packet: &Packet) {
+        if self.nats.contains_key(&packet.src) {
+            self.stats.nat_deliveries.push((self.tick, *packet));
+        }
+
+        self.on_receive.iter_mut().for_each(|hook| hook(readable, packet));
+    }
+
+    /// Returns true if no packets are held by the fault injector, and every node has halted or has
+    /// an empty queue and has read -1 enough times in a row that it's waiting for a packet.
+    fn is_idle(&self) -> bool {
+        !self.faults.as_ref().is_some_and(FaultInjector::in_flight) && self.nodes.iter().zip(&self.empty_polls).all(|(node, &polls)| {
+            node.state() == &ProgramState::Done || (!node.has_input() && polls >= self.idle_polls)
+        })
+    }
+}
+
+/// Runs the node for one turn.  A node blocked on an empty queue reads -1, then runs until it
+/// blocks again, or until it runs out of steps if there's a limit.  Returns the values the node
+/// output and the number of times it read -1, or the pc and error if the node's program failed.
+pub(crate) fn take_turn(node: &mut Computer, limit: Option<usize>) -> Result<(Vec<i64>, usize), (usize, IntcodeError)> {
+    let mut polls = 0;
+
+    let limit = match limit {
+        Some(limit) => limit,
+        None => {
+            let poll = iter::from_fn(|| (polls == 0).then(|| {
+                polls += 1;
+                -1
+            }));
+
+            let output = node.outputs(poll).collect::<Result<_, _>>().map_err(|error| (node.pc(), error))?;
+            return Ok((output, polls));
+        }
+    };
+
+    let mut steps = 0;
+    while node.state() != &ProgramState::Done && steps < limit {
+        if node.state() == &ProgramState::WaitingForInput && !node.has_input() {
+            node.input(-1);
+            polls += 1;
+        }
+
+        let pc = node.pc();
+        node.step().map_err(|error| (pc, error))?;
+        steps += 1;
+    }
+
+    Ok((node.dump_output(), polls))
+}
+
+/// Adds the node's output to its partial packet, and returns the packets that are complete.
+pub(crate) fn complete_packets(src: i64, partial: &mut Frames<Outgoing>, output: Vec<i64>) -> Vec<Packet> {
+    partial.extend(output).into_iter()
+        .map(|outgoing| Packet { src, dst: outgoing.dst, x: outgoing.x, y: outgoing.y })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends (address + 1, address, 0) and then forwards every packet it receives to the next
+    /// address with Y + 1.  The last node's packets go to 100.
+    fn relay() -> Computer {
+        Computer::new(vec![
+            // 0: read address, send (address + 1, address, 0)
+            3, 100, 1001, 100, 1, 101, 4, 101, 4, 100, 104, 0,
+            // 12: read X until it isn't -1, read Y, send (address + 1, X, Y + 1)
+            3, 102, 1008, 102, -1, 104, 1005, 104, 12, 3, 103, 1001, 103, 1, 103, 4, 101, 4, 102, 4, 103,
+            1105, 1, 12,
+        ])
+    }
+
+    /// Stops with the Y value of the first packet it receives.
+    struct FirstY;
+
+    impl Nat for FirstY {
+        fn receive(&mut self, packet: Packet) -> Action {
+            Action::Stop(packet.y)
+        }
+    }
+
+    #[test]
+    fn round_robin() {
+        let mut sent = Vec::new();
+        let program = relay();
+        let result = Network::new(&program, 3)
+            .with_nat(3, FirstY)
+            .on_send(|tick, packet| sent.push((tick, *packet)))
+            .run();
+
+        assert_eq!(Ok(0), result);
+        assert_eq!((0, Packet { src: 0, dst: 1, x: 0, y: 0 }), sent[0]);
+        assert_eq!((0, Packet { src: 1, dst: 2, x: 0, y: 1 }), sent[2]);
+        assert_eq!((0, Packet { src: 2, dst: 3, x: 2, y: 0 }), *sent.last().unwrap());
+    }
+
+    #[test]
+    fn schedules() {
+        let program = relay();
+
+        // Node 2's own packet is the first one to get to the Nat whatever order the nodes run in.
+        for schedule in [Schedule::RoundRobin, Schedule::Random { seed: 7 }, Schedule::Steps(1)] {
+            let mut received = Vec::new();
+            let result = Network::new(&program, 3)
+                .with_schedule(schedule)
+                .with_nat(3, FirstY)
+                .on_receive(|_, packet| received.push(*packet))
+                .run();
+
+            assert_eq!(Ok(0), result, "{:?}", schedule);
+            assert!(received.iter().any(|packet| packet.dst == 3), "{:?}", schedule);
+        }
+    }
+
+    /// Counts the packets it receives, and wakes node 0 with a packet the first time the network
+    /// goes idle.
+    struct Wake {
+        received: usize,
+        woken: bool,
+    }
+
+    impl Nat for Wake {
+        fn receive(&mut self, _: Packet) -> Action {
+            self.received += 1;
+            Action::Continue
+        }
+
+        fn idle(&mut self) -> Action {
+            if self.woken {
+                Action::Stop(self.received as i64)
+            } else {
+                self.woken = true;
+                Action::Send(Packet { src: 3, dst: 0, x: 10, y: 10 })
+            }
+        }
+    }
+
+    #[test]
+    fn idle() {
+        let program = relay();
+        let result = Network::new(&program, 3).with_nat(3, Wake { received: 0, woken: false }).run();
+
+        // Node 2's own packet, nodes 0 and 1's packets relayed by node 2, and the wake up packet.
+        assert_eq!(Ok(4), result);
+    }
+
+    #[test]
+    fn errors() {
+        let program = relay();
+        assert_eq!(Err(NetworkError::UnknownAddress(Packet { src: 2, dst: 3, x: 2, y: 0 })), Network::new(&program, 3).run());
+
+        // A Nat that never wakes the network can't stop it.
+        struct Ignore;
+        impl Nat for Ignore {
+            fn receive(&mut self, _: Packet) -> Action {
+                Action::Continue
+            }
+        }
+
+        assert_eq!(Err(NetworkError::Deadlocked { tick: 3 }), Network::new(&program, 3).with_nat(3, Ignore).run());
+    }
+}
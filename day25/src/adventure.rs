@@ -0,0 +1,270 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::FromStr;
+
+use intcode::{Computer, IntcodeError};
+
+// The droid's text protocol.  The droid describes rooms and the results of commands in text, and
+// asks for the next command with "Command?".  Output is parsed into messages, and commands are
+// written the way the droid reads them:
+//
+//   == Observatory ==
+//   There are a few telescopes; they're all bolted down, though.
+//
+//   Doors here lead:
+//   - north
+//   - south
+//
+//   Items here:
+//   - dark matter
+//
+//   Command?
+//
+// parses as a Message::Room, and Command::Take("dark matter") is sent as "take dark matter".
+
+/// Direction of a door.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "north" => Ok(Direction::North),
+            "south" => Ok(Direction::South),
+            "east" => Ok(Direction::East),
+            "west" => Ok(Direction::West),
+            _ => Err(format!("'{}' isn't a direction", s)),
+        }
+    }
+}
+
+/// A room the droid is in.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Room {
+    pub name: String,
+    pub description: String,
+    pub doors: Vec<Direction>,
+    pub items: Vec<String>,
+}
+
+/// Why the pressure-sensitive floor ejected the droid.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Ejection {
+    /// Droids on the ship are lighter than the droid, so it's carrying too much.
+    Lighter,
+    /// Droids on the ship are heavier than the droid, so it's carrying too little.
+    Heavier,
+}
+
+/// Something the droid said.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Message {
+    /// The droid entered a room.
+    Room(Room),
+    /// Items the droid is carrying.
+    Inventory(Vec<String>),
+    Taken(String),
+    Dropped(String),
+    /// The pressure-sensitive floor didn't accept the droid's weight, and sent it back.
+    Ejected(Ejection),
+    /// The pressure-sensitive floor accepted the droid, and Santa gave it the airlock code.
+    Accepted { code: String },
+    /// There isn't a door in that direction.
+    NoDoor,
+    /// The item isn't in the room.
+    NotHere,
+    /// The droid isn't carrying the item.
+    NotCarrying,
+    Unrecognized,
+    /// Anything else, like the droid's demise.
+    Other(String),
+}
+
+/// Parses the droid's output into messages.
+pub fn parse(output: &str) -> Vec<Message> {
+    let mut messages = Vec::new();
+    let mut lines = output.lines().map(str::trim).filter(|line| !line.is_empty() && *line != "Command?").peekable();
+
+    while let Some(line) = lines.next() {
+        let message = if let Some(name) = line.strip_prefix("== ").and_then(|line| line.strip_suffix(" ==")) {
+            let mut room = Room { name: name.to_string(), description: String::new(), doors: Vec::new(), items: Vec::new() };
+            if let Some(description) = lines.next_if(|&line| line != "Doors here lead:" && line != "Items here:") {
+                room.description = description.to_string();
+            }
+
+            loop {
+                if lines.next_if_eq(&"Doors here lead:").is_some() {
+                    room.doors = list(&mut lines).iter().filter_map(|door| door.parse().ok()).collect();
+                } else if lines.next_if_eq(&"Items here:").is_some() {
+                    room.items = list(&mut lines);
+                } else {
+                    break;
+                }
+            }
+
+            Message::Room(room)
+        } else if line == "Items in your inventory:" {
+            Message::Inventory(list(&mut lines))
+        } else if line == "You aren't carrying any items." {
+            Message::Inventory(Vec::new())
+        } else if let Some(item) = line.strip_prefix("You take the ").and_then(|line| line.strip_suffix('.')) {
+            Message::Taken(item.to_string())
+        } else if let Some(item) = line.strip_prefix("You drop the ").and_then(|line| line.strip_suffix('.')) {
+            Message::Dropped(item.to_string())
+        } else if line.contains("Droids on this ship are lighter than the detected value") {
+            Message::Ejected(Ejection::Lighter)
+        } else if line.contains("Droids on this ship are heavier than the detected value") {
+            Message::Ejected(Ejection::Heavier)
+        } else if line.contains("Analysis complete! You may proceed.") {
+            // Santa radios the code on a later line.
+            let code = lines.by_ref()
+                .find_map(|line| line.split("by typing ").nth(1).and_then(|rest| rest.split(' ').next()))
+                .unwrap_or("");
+            Message::Accepted { code: code.to_string() }
+        } else {
+            match line {
+                "You can't go that way." => Message::NoDoor,
+                "You don't see that item here." => Message::NotHere,
+                "You don't have that item." => Message::NotCarrying,
+                "Unrecognized command." => Message::Unrecognized,
+                _ => Message::Other(line.to_string()),
+            }
+        };
+
+        messages.push(message);
+    }
+
+    messages
+}
+
+/// Takes the '- item' lines of a list that follows a heading.
+fn list<'a, I: Iterator<Item = &'a str>>(lines: &mut Peekable<I>) -> Vec<String> {
+    let mut items = Vec::new();
+    while let Some(item) = lines.next_if(|line| line.starts_with("- ")) {
+        items.push(item[2..].to_string());
+    }
+
+    items
+}
+
+/// A command the droid understands.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Command {
+    North,
+    South,
+    East,
+    West,
+    Take(String),
+    Drop(String),
+    Inv,
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::North => write!(f, "north"),
+            Command::South => write!(f, "south"),
+            Command::East => write!(f, "east"),
+            Command::West => write!(f, "west"),
+            Command::Take(item) => write!(f, "take {}", item),
+            Command::Drop(item) => write!(f, "drop {}", item),
+            Command::Inv => write!(f, "inv"),
+        }
+    }
+}
+
+/// The droid, running the adventure's program.
+#[derive(Debug, Clone)]
+pub struct Droid {
+    computer: Computer,
+}
+
+impl Droid {
+    /// Starts the adventure, returning the droid and what it said before the first command.
+    pub fn start(computer: Computer) -> Result<(Droid, Vec<Message>), IntcodeError> {
+        let mut droid = Droid { computer };
+        let messages = droid.run()?;
+
+        Ok((droid, messages))
+    }
+
+    /// Sends a command to the droid, returning what it said in response.
+    pub fn send(&mut self, command: &Command) -> Result<Vec<Message>, IntcodeError> {
+        self.computer.text_input(&format!("{}\n", command));
+        self.run()
+    }
+
+    /// Runs the program until it asks for a command or halts, and parses what it said.
+    fn run(&mut self) -> Result<Vec<Message>, IntcodeError> {
+        self.computer.try_run()?;
+
+        let output: String = self.computer.dump_output().into_iter().map(|c| c as u8 as char).collect();
+        Ok(parse(&output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rooms() {
+        let output = "\n\n\n== Observatory ==\nThere are a few telescopes; they're all bolted down, though.\n\n\
+            Doors here lead:\n- north\n- east\n- south\n\nItems here:\n- dark matter\n\nCommand?\n";
+
+        assert_eq!(vec![Message::Room(Room {
+            name: "Observatory".to_string(),
+            description: "There are a few telescopes; they're all bolted down, though.".to_string(),
+            doors: vec![Direction::North, Direction::East, Direction::South],
+            items: vec!["dark matter".to_string()],
+        })], parse(output));
+
+        let ejected = "\n\n\n== Pressure-Sensitive Floor ==\nAnalyzing...\n\nDoors here lead:\n- east\n\n\
+            A loud, robotic voice says \"Alert! Droids on this ship are heavier than the detected value!\" and you are ejected back to the checkpoint.\n\n\n\n\
+            == Security Checkpoint ==\nIn the next room, a pressure-sensitive floor will verify your identity.\n\n\
+            Doors here lead:\n- east\n- west\n\nCommand?\n";
+
+        let messages = parse(ejected);
+        assert_eq!(3, messages.len());
+        assert!(matches!(&messages[0], Message::Room(room) if room.name == "Pressure-Sensitive Floor" && room.doors == vec![Direction::East]));
+        assert_eq!(Message::Ejected(Ejection::Heavier), messages[1]);
+        assert!(matches!(&messages[2], Message::Room(room) if room.name == "Security Checkpoint" && room.items.is_empty()));
+
+        let accepted = "A loud, robotic voice says \"Analysis complete! You may proceed.\" and you enter the cockpit.\n\
+            Santa notices your small droid, looks puzzled for a moment, realizes what has happened, and radios your ship directly.\n\
+            \"Oh, hello! You should be able to get in by typing 1234 on the keypad at the main airlock.\"\n";
+        assert_eq!(vec![Message::Accepted { code: "1234".to_string() }], parse(accepted));
+    }
+
+    #[test]
+    fn responses() {
+        assert_eq!(vec![Message::Taken("dark matter".to_string())], parse("\nYou take the dark matter.\n\nCommand?\n"));
+        assert_eq!(vec![Message::Dropped("dark matter".to_string())], parse("\nYou drop the dark matter.\n\nCommand?\n"));
+        assert_eq!(vec![Message::Inventory(vec!["antenna".to_string(), "candy cane".to_string()])],
+                   parse("\nItems in your inventory:\n- antenna\n- candy cane\n\nCommand?\n"));
+        assert_eq!(vec![Message::Inventory(Vec::new())], parse("\nYou aren't carrying any items.\n\nCommand?\n"));
+        assert_eq!(vec![Message::NoDoor], parse("\nYou can't go that way.\n\nCommand?\n"));
+        assert_eq!(vec![Message::NotHere, Message::NotCarrying, Message::Unrecognized],
+                   parse("You don't see that item here.\nYou don't have that item.\nUnrecognized command.\n"));
+        assert_eq!(vec![Message::Other("You're launched into space! Bye!".to_string())], parse("You're launched into space! Bye!\n"));
+    }
+
+    #[test]
+    fn commands() {
+        let commands = [Command::North, Command::West, Command::Take("bowl of rice".to_string()), Command::Drop("jam".to_string()), Command::Inv];
+        let text: Vec<String> = commands.iter().map(Command::to_string).collect();
+        assert_eq!(vec!["north", "west", "take bowl of rice", "drop jam", "inv"], text);
+
+        let (mut droid, messages) = Droid::start(Computer::load("input.txt").unwrap()).unwrap();
+        assert!(matches!(&messages[..], [Message::Room(room)] if room.name == "Hull Breach"));
+        assert_eq!(vec![Message::Inventory(Vec::new())], droid.send(&Command::Inv).unwrap());
+        assert_eq!(vec![Message::NoDoor], droid.send(&Command::East).unwrap());
+    }
+}
@@ -1,8 +1,39 @@
-use crate::computer::Computer;
-
-mod computer;
+use std::env;
+use std::error::Error;
+use std::fs;
+
+use intcode::coverage::Coverage;
+use intcode::{Computer, ProgramState};
+
+/// Plays the adventure once for each file of commands, one command per line, and prints the
+/// coverage of the adventure's program across all of the plays.  Each play ends when the program
+/// halts or runs out of commands.
+fn explored(computer: &Computer, filenames: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut coverage = Coverage::new();
+
+    for filename in filenames {
+        let commands = fs::read_to_string(filename)?;
+        let mut computer = computer.clone();
+
+        coverage.run(&mut computer)?;
+        for command in commands.lines() {
+            if *computer.state() == ProgramState::Done {
+                break;
+            }
+
+            computer.text_input(command);
+            computer.text_input("\n");
+            coverage.run(&mut computer)?;
+        }
+    }
+
+    print!("{}", coverage.report(&computer.memory.to_program()));
+    Ok(())
+}
 
-fn main() {
+// `cargo run` plays the adventure interactively, and `cargo run -- coverage <file>...` plays the
+// commands in each file and prints which parts of the adventure's program they explored.
+fn main() -> Result<(), Box<dyn Error>> {
     // Commands:
     // Movement: north, south, east, or west.
     // Take Item: take <name of item>
@@ -10,7 +41,14 @@ fn main() {
     // List Inventory: inv
 
 
-    let mut computer = Computer::load("input.txt");
+    let mut computer = Computer::load("input.txt")?;
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let [command, filenames @ ..] = args.as_slice() {
+        if command == "coverage" {
+            return explored(&computer, filenames);
+        }
+    }
 
     computer.run_interactive();
 
@@ -60,4 +98,6 @@ fn main() {
 
     Answer: hypercube + antenna + dehydrated water + candy cane
      */
+
+    Ok(())
 }
@@ -1,85 +1,55 @@
-use itertools::Itertools;
-use rayon::prelude::*;
-
-use crate::computer::Computer;
+use intcode::Computer;
+use intcode::circuit::Circuit;
+use intcode::program::ProgramError;
+
+/// Five amplifiers in a series, where each passes its output to the next.
+const CHAIN: &str = "
+A -> B -> C -> D -> E
+input A 0
+output E
+";
+
+/// Five amplifiers in a feedback loop, where the last amplifier passes its output back to the first.
+const LOOP: &str = "
+A -> B -> C -> D -> E -> A
+input A 0
+output E
+";
+
+/// Builds an amplifier circuit that runs the program on every amplifier.
+fn amplifiers(description: &str, computer: &Computer) -> Circuit {
+    Circuit::parse(description, &[("amp", computer)]).unwrap()
+}
 
 /// Runs the program on a series of amplifiers, using the given phase settings and passing the output from one amp to the next.
-fn chain_output(computer: &Computer, phase_settings: Vec<i32>) -> i32 {
-    let amplifiers = (0..5).map(|i| {
-        let mut amp = computer.clone();
-        amp.input(phase_settings[i]);
-        amp
-    });
-
-    // Run the amplifiers once - input for the first amplifier is 0
-    let mut value = 0;
-    for mut amplifier in amplifiers {
-        amplifier.input(value);
-        value = amplifier.run().unwrap();
-    }
-
-    value
+#[cfg(test)]
+fn chain_output(computer: &Computer, phase_settings: Vec<i64>) -> i64 {
+    amplifiers(CHAIN, computer).run(&phase_settings).unwrap().last_output().unwrap()
 }
 
 /// Returns the maximum output that a five-phase series of amplifier programs can produce with
 /// permutations of 0-4 as phase settings.
-fn max_output(computer: &Computer) -> i32 {
-    let phase_settings = vec![0, 1, 2, 3, 4];
-
-    phase_settings.into_iter()
-        .permutations(5)
-        .par_bridge()
-        .map(|permutation| chain_output(computer, permutation))
-        .max()
-        .unwrap()
+fn max_output(computer: &Computer) -> i64 {
+    amplifiers(CHAIN, computer).best_phases(&[0, 1, 2, 3, 4]).unwrap().1
 }
 
 /// Runs the given program on a loop of amplifiers until they all halt and returns the final output from amplifier E.
-/// Input is the phase setting for each amplifier, then the output from the previous amp in the chain.Iterator
+/// Input is the phase setting for each amplifier, then the output from the previous amp in the chain.
 /// The first amplifier's initial chained input is 0.
-fn looped_output(computer: &Computer, phase_settings: Vec<i32>) -> i32 {
-    let mut amplifiers: Vec<Computer> = (0..5).map(|i| {
-        let mut amp = computer.clone();
-        amp.input(phase_settings[i]);
-        amp
-    }).collect();
-
-    // Run the amplifiers in a continuous loop until they halt.
-    let mut i = 0;
-    let mut value = 0;
-
-    loop {
-        amplifiers[i].input(value);
-
-        if !amplifiers[i].is_runnable() {
-            break;
-        }
-
-        value = amplifiers[i].run().unwrap();
-        i = (i + 1) % 5;
-    }
-
-    // Output comes from the last amplifier.
-    amplifiers[4].last_output().unwrap()
+#[cfg(test)]
+fn looped_output(computer: &Computer, phase_settings: Vec<i64>) -> i64 {
+    amplifiers(LOOP, computer).run(&phase_settings).unwrap().last_output().unwrap()
 }
 
 /// Given a program, returns the maximum output that a looped chain of amplifiers can produce.
-fn max_looped_output(computer: &Computer) -> i32 {
-    let phase_settings = vec![5, 6, 7, 8, 9];
-
-    phase_settings.into_iter()
-        .permutations(5)
-        .par_bridge()
-        .map(|permutation| looped_output(computer, permutation))
-        .max()
-        .unwrap()
+fn max_looped_output(computer: &Computer) -> i64 {
+    amplifiers(LOOP, computer).best_phases(&[5, 6, 7, 8, 9]).unwrap().1
 }
 
 #[cfg(test)]
 mod test;
-mod computer;
 
-fn main() -> std::io::Result<()> {
+fn main() -> Result<(), ProgramError> {
     let computer = Computer::load("input.txt")?;
 
     // Part 1: passing 0-4, then the output from the previous phase, what's the maximum output for a 5-amp series?
@@ -0,0 +1,17 @@
+// Generates the C header from the extern "C" API into OUT_DIR whenever the library builds.  The
+// header_is_current test fails if it doesn't match the committed include/intcode.h.
+
+use std::env;
+use std::path::Path;
+
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    cbindgen::generate(&crate_dir)
+        .expect("Unable to generate the C header")
+        .write_to_file(Path::new(&out_dir).join("intcode.h"));
+
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
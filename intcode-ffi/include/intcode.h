@@ -0,0 +1,136 @@
+#ifndef INTCODE_H
+#define INTCODE_H
+
+/* Generated by cbindgen from intcode-ffi/src/lib.rs - don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Version of this API.  Changes whenever a function's signature or behavior changes.
+#define INTCODE_API_VERSION 1
+
+// Budget that lets intcode_run run until the program halts or needs input.
+#define INTCODE_UNLIMITED UINT64_MAX
+
+// Why intcode_run returned.
+typedef enum IntcodeStatus {
+  // The program halted.
+  INTCODE_STATUS_HALTED = 0,
+  // The program needs input - push some and run it again.
+  INTCODE_STATUS_WAITING_FOR_INPUT = 1,
+  // The program ran for the whole budget and can keep running.
+  INTCODE_STATUS_BUDGET_EXHAUSTED = 2,
+  // The program can't be executed.  intcode_error describes the problem, and the computer stays
+  // at the instruction that failed.
+  INTCODE_STATUS_ERROR = 3,
+  // The computer handle was NULL.
+  INTCODE_STATUS_INVALID_HANDLE = 4,
+} IntcodeStatus;
+
+// Intcode computer, along with the message for the last error it ran into.
+typedef struct IntcodeComputer IntcodeComputer;
+
+// Copy of a computer's complete state, including memory, registers, input and output.
+typedef struct IntcodeSnapshot IntcodeSnapshot;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns the version of the API the library implements.  Compare it to INTCODE_API_VERSION from
+// the header to check that the library matches.
+uint32_t intcode_api_version(void);
+
+// Constructs a computer that runs a copy of the given program of len values.  The program can be
+// NULL if len is 0.  Free the computer with intcode_free.
+//
+// # Safety
+// program must point to len readable values.
+struct IntcodeComputer *intcode_new(const int64_t *program, uintptr_t len);
+
+// Frees a computer.  Does nothing if the computer is NULL.
+//
+// # Safety
+// computer must be NULL or a computer from intcode_new that hasn't been freed.
+void intcode_free(struct IntcodeComputer *computer);
+
+// Adds a value to the end of the computer's input queue.
+//
+// # Safety
+// computer must be NULL or a live computer.
+void intcode_push_input(struct IntcodeComputer *computer, int64_t value);
+
+// Runs at most budget instructions, stopping early if the program halts, needs input, or fails.
+// Pass INTCODE_UNLIMITED to run until the program halts or needs input.  If steps isn't NULL, it's
+// set to the number of instructions that ran.
+//
+// # Safety
+// computer must be NULL or a live computer, and steps must be NULL or writable.
+enum IntcodeStatus intcode_run(struct IntcodeComputer *computer, uint64_t budget, uint64_t *steps);
+
+// Returns a description of the error from the last call to intcode_run, or NULL if it didn't
+// fail.  The string belongs to the computer, and is valid until the next intcode_run or
+// intcode_restore call.
+//
+// # Safety
+// computer must be NULL or a live computer.
+const char *intcode_error(const struct IntcodeComputer *computer);
+
+// Removes the oldest value the program output and stores it in value, returning whether there
+// was one.  If value is NULL, returns false and leaves the output alone.
+//
+// # Safety
+// computer must be NULL or a live computer, and value must be NULL or writable.
+bool intcode_pop_output(struct IntcodeComputer *computer, int64_t *value);
+
+// Returns the number of values the program has output that haven't been popped.
+//
+// # Safety
+// computer must be NULL or a live computer.
+uintptr_t intcode_output_len(const struct IntcodeComputer *computer);
+
+// Returns the address of the next instruction the program will run.
+//
+// # Safety
+// computer must be NULL or a live computer.
+uintptr_t intcode_pc(const struct IntcodeComputer *computer);
+
+// Returns the value at the given address.  Addresses that have never been set hold 0.
+//
+// # Safety
+// computer must be NULL or a live computer.
+int64_t intcode_get_memory(const struct IntcodeComputer *computer, uintptr_t addr);
+
+// Sets the value at the given address, returning whether it was stored.
+//
+// # Safety
+// computer must be NULL or a live computer.
+bool intcode_set_memory(struct IntcodeComputer *computer, uintptr_t addr, int64_t value);
+
+// Copies the computer's state into a snapshot, or returns NULL if the computer is NULL.  Free
+// the snapshot with intcode_snapshot_free.
+//
+// # Safety
+// computer must be NULL or a live computer.
+struct IntcodeSnapshot *intcode_snapshot(const struct IntcodeComputer *computer);
+
+// Returns the computer to the state in the snapshot.  The snapshot can be restored any number of
+// times, and into any computer.
+//
+// # Safety
+// computer and snapshot must each be NULL or live.
+void intcode_restore(struct IntcodeComputer *computer, const struct IntcodeSnapshot *snapshot);
+
+// Frees a snapshot.  Does nothing if the snapshot is NULL.
+//
+// # Safety
+// snapshot must be NULL or a snapshot from intcode_snapshot that hasn't been freed.
+void intcode_snapshot_free(struct IntcodeSnapshot *snapshot);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* INTCODE_H */
@@ -0,0 +1,388 @@
+// C API for the intcode computer, so test rigs written in C or C++ can drive it.  The build
+// produces libintcode_ffi.so, and include/intcode.h declares the functions in this file.
+//
+//   int64_t program[] = {3, 0, 4, 0, 99};
+//   IntcodeComputer *computer = intcode_new(program, 5);
+//   intcode_push_input(computer, 7);
+//   if (intcode_run(computer, INTCODE_UNLIMITED, NULL) == INTCODE_STATUS_HALTED) {
+//       int64_t value;
+//       while (intcode_pop_output(computer, &value)) { printf("%lld\n", value); }
+//   }
+//   intcode_free(computer);
+//
+// Computers and snapshots are opaque handles owned by the caller, and must be freed with
+// intcode_free and intcode_snapshot_free.  Functions accept NULL handles and do nothing with them,
+// but passing a freed handle or a handle from another library is undefined behavior.  The API
+// version goes up whenever a function's signature or behavior changes.
+//
+// The committed header is generated by cbindgen.  After changing the API, regenerate it with the
+// cbindgen CLI - the tests fail until it matches the API:
+//
+//   cbindgen --config cbindgen.toml --output include/intcode.h
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::ptr;
+use std::slice;
+
+use intcode::{Computer, ProgramState};
+
+/// Version of this API.  Changes whenever a function's signature or behavior changes.
+pub const INTCODE_API_VERSION: u32 = 1;
+
+/// Budget that lets intcode_run run until the program halts or needs input.
+pub const INTCODE_UNLIMITED: u64 = u64::MAX;
+
+/// Why intcode_run returned.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IntcodeStatus {
+    /// The program halted.
+    Halted = 0,
+    /// The program needs input - push some and run it again.
+    WaitingForInput = 1,
+    /// The program ran for the whole budget and can keep running.
+    BudgetExhausted = 2,
+    /// The program can't be executed.  intcode_error describes the problem, and the computer stays
+    /// at the instruction that failed.
+    Error = 3,
+    /// The computer handle was NULL.
+    InvalidHandle = 4,
+}
+
+/// Intcode computer, along with the message for the last error it ran into.
+pub struct IntcodeComputer {
+    computer: Computer,
+    error: Option<CString>,
+}
+
+/// Copy of a computer's complete state, including memory, registers, input and output.
+pub struct IntcodeSnapshot {
+    computer: Computer,
+}
+
+/// Returns the version of the API the library implements.  Compare it to INTCODE_API_VERSION from
+/// the header to check that the library matches.
+#[no_mangle]
+pub extern "C" fn intcode_api_version() -> u32 {
+    INTCODE_API_VERSION
+}
+
+/// Constructs a computer that runs a copy of the given program of len values.  The program can be
+/// NULL if len is 0.  Free the computer with intcode_free.
+///
+/// # Safety
+/// program must point to len readable values.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_new(program: *const i64, len: usize) -> *mut IntcodeComputer {
+    let program = if len == 0 { Vec::new() } else { slice::from_raw_parts(program, len).to_vec() };
+
+    Box::into_raw(Box::new(IntcodeComputer { computer: Computer::new(program), error: None }))
+}
+
+/// Frees a computer.  Does nothing if the computer is NULL.
+///
+/// # Safety
+/// computer must be NULL or a computer from intcode_new that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_free(computer: *mut IntcodeComputer) {
+    if !computer.is_null() {
+        drop(Box::from_raw(computer));
+    }
+}
+
+/// Adds a value to the end of the computer's input queue.
+///
+/// # Safety
+/// computer must be NULL or a live computer.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_push_input(computer: *mut IntcodeComputer, value: i64) {
+    if let Some(computer) = computer.as_mut() {
+        computer.computer.input(value);
+    }
+}
+
+/// Runs at most budget instructions, stopping early if the program halts, needs input, or fails.
+/// Pass INTCODE_UNLIMITED to run until the program halts or needs input.  If steps isn't NULL, it's
+/// set to the number of instructions that ran.
+///
+/// # Safety
+/// computer must be NULL or a live computer, and steps must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_run(computer: *mut IntcodeComputer, budget: u64, steps: *mut u64) -> IntcodeStatus {
+    let computer = match computer.as_mut() {
+        Some(computer) => computer,
+        None => return IntcodeStatus::InvalidHandle,
+    };
+
+    let (status, ran) = computer.run(budget);
+    if let Some(steps) = steps.as_mut() {
+        *steps = ran;
+    }
+
+    status
+}
+
+impl IntcodeComputer {
+    /// Runs at most budget instructions, returning why the computer stopped and how many
+    /// instructions ran.
+    fn run(&mut self, budget: u64) -> (IntcodeStatus, u64) {
+        self.error = None;
+
+        let mut ran = 0;
+        while ran < budget && self.computer.is_runnable() {
+            // Panics can't unwind into C, so they're reported as errors too.
+            let message = match panic::catch_unwind(AssertUnwindSafe(|| self.computer.step())) {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(e.to_string()),
+                Err(payload) => {
+                    let panic = payload.downcast_ref::<&str>().map(|message| message.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_default();
+                    Some(format!("Panicked: {}", panic))
+                }
+            };
+
+            if let Some(message) = message {
+                let message = format!("{} at pc {}", message, self.computer.pc());
+                self.error = Some(CString::new(message).unwrap_or_default());
+                return (IntcodeStatus::Error, ran);
+            }
+
+            ran += 1;
+        }
+
+        let status = match self.computer.state() {
+            ProgramState::Done => IntcodeStatus::Halted,
+            ProgramState::WaitingForInput => IntcodeStatus::WaitingForInput,
+            ProgramState::Runnable => IntcodeStatus::BudgetExhausted,
+        };
+
+        (status, ran)
+    }
+}
+
+/// Returns a description of the error from the last call to intcode_run, or NULL if it didn't
+/// fail.  The string belongs to the computer, and is valid until the next intcode_run or
+/// intcode_restore call.
+///
+/// # Safety
+/// computer must be NULL or a live computer.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_error(computer: *const IntcodeComputer) -> *const c_char {
+    match computer.as_ref().and_then(|computer| computer.error.as_ref()) {
+        Some(error) => error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Removes the oldest value the program output and stores it in value, returning whether there
+/// was one.  If value is NULL, returns false and leaves the output alone.
+///
+/// # Safety
+/// computer must be NULL or a live computer, and value must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_pop_output(computer: *mut IntcodeComputer, value: *mut i64) -> bool {
+    let (computer, value) = match (computer.as_mut(), value.as_mut()) {
+        (Some(computer), Some(value)) => (computer, value),
+        _ => return false,
+    };
+
+    match computer.computer.output.pop_front() {
+        Some(output) => {
+            *value = output;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns the number of values the program has output that haven't been popped.
+///
+/// # Safety
+/// computer must be NULL or a live computer.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_output_len(computer: *const IntcodeComputer) -> usize {
+    computer.as_ref().map_or(0, |computer| computer.computer.output.len())
+}
+
+/// Returns the address of the next instruction the program will run.
+///
+/// # Safety
+/// computer must be NULL or a live computer.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_pc(computer: *const IntcodeComputer) -> usize {
+    computer.as_ref().map_or(0, |computer| computer.computer.pc())
+}
+
+/// Returns the value at the given address.  Addresses that have never been set hold 0.
+///
+/// # Safety
+/// computer must be NULL or a live computer.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_get_memory(computer: *const IntcodeComputer, addr: usize) -> i64 {
+    computer.as_ref().map_or(0, |computer| computer.computer.memory.get(addr))
+}
+
+/// Sets the value at the given address, returning whether it was stored.
+///
+/// # Safety
+/// computer must be NULL or a live computer.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_set_memory(computer: *mut IntcodeComputer, addr: usize, value: i64) -> bool {
+    computer.as_mut().is_some_and(|computer| computer.computer.memory.set(addr, value).is_ok())
+}
+
+/// Copies the computer's state into a snapshot, or returns NULL if the computer is NULL.  Free
+/// the snapshot with intcode_snapshot_free.
+///
+/// # Safety
+/// computer must be NULL or a live computer.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_snapshot(computer: *const IntcodeComputer) -> *mut IntcodeSnapshot {
+    match computer.as_ref() {
+        Some(computer) => Box::into_raw(Box::new(IntcodeSnapshot { computer: computer.computer.clone() })),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Returns the computer to the state in the snapshot.  The snapshot can be restored any number of
+/// times, and into any computer.
+///
+/// # Safety
+/// computer and snapshot must each be NULL or live.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_restore(computer: *mut IntcodeComputer, snapshot: *const IntcodeSnapshot) {
+    if let (Some(computer), Some(snapshot)) = (computer.as_mut(), snapshot.as_ref()) {
+        computer.computer = snapshot.computer.clone();
+        computer.error = None;
+    }
+}
+
+/// Frees a snapshot.  Does nothing if the snapshot is NULL.
+///
+/// # Safety
+/// snapshot must be NULL or a snapshot from intcode_snapshot that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_snapshot_free(snapshot: *mut IntcodeSnapshot) {
+    if !snapshot.is_null() {
+        drop(Box::from_raw(snapshot));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    // Reads values and outputs each one doubled, halting when it reads 0.
+    const DOUBLER: [i64; 15] = [3, 20, 1006, 20, 14, 102, 2, 20, 21, 4, 21, 1105, 1, 0, 99];
+
+    fn outputs(computer: *mut IntcodeComputer) -> Vec<i64> {
+        let mut outputs = Vec::new();
+        let mut value = 0;
+        while unsafe { intcode_pop_output(computer, &mut value) } {
+            outputs.push(value);
+        }
+
+        outputs
+    }
+
+    #[test]
+    fn run_with_budget() {
+        unsafe {
+            let computer = intcode_new(DOUBLER.as_ptr(), DOUBLER.len());
+            let mut steps = 0;
+
+            assert_eq!(IntcodeStatus::WaitingForInput, intcode_run(computer, INTCODE_UNLIMITED, &mut steps));
+            // Finding that there's no input takes a step.
+            assert_eq!(1, steps);
+
+            intcode_push_input(computer, 3);
+            intcode_push_input(computer, 4);
+            assert_eq!(IntcodeStatus::BudgetExhausted, intcode_run(computer, 4, &mut steps));
+            assert_eq!(4, steps);
+            assert_eq!(vec![6], outputs(computer));
+
+            // Popping into NULL leaves the output to pop later.
+            assert_eq!(IntcodeStatus::WaitingForInput, intcode_run(computer, INTCODE_UNLIMITED, ptr::null_mut()));
+            assert!(!intcode_pop_output(computer, ptr::null_mut()));
+            assert_eq!(1, intcode_output_len(computer));
+            assert_eq!(vec![8], outputs(computer));
+            assert_eq!(0, intcode_output_len(computer));
+
+            intcode_push_input(computer, 0);
+            assert_eq!(IntcodeStatus::Halted, intcode_run(computer, INTCODE_UNLIMITED, ptr::null_mut()));
+            assert_eq!(14, intcode_pc(computer));
+            assert!(intcode_error(computer).is_null());
+
+            intcode_free(computer);
+        }
+    }
+
+    #[test]
+    fn memory_and_snapshots() {
+        unsafe {
+            let computer = intcode_new(DOUBLER.as_ptr(), DOUBLER.len());
+            intcode_push_input(computer, 5);
+            intcode_run(computer, 2, ptr::null_mut());
+            assert_eq!(5, intcode_get_memory(computer, 20));
+
+            let snapshot = intcode_snapshot(computer);
+
+            // Triple instead of doubling.
+            assert!(intcode_set_memory(computer, 6, 3));
+            intcode_run(computer, INTCODE_UNLIMITED, ptr::null_mut());
+            assert_eq!(vec![15], outputs(computer));
+
+            intcode_restore(computer, snapshot);
+            assert_eq!(2, intcode_get_memory(computer, 6));
+            intcode_run(computer, INTCODE_UNLIMITED, ptr::null_mut());
+            assert_eq!(vec![10], outputs(computer));
+
+            intcode_snapshot_free(snapshot);
+            intcode_free(computer);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        unsafe {
+            let program = [1, 0, 0, 0, 42];
+            let computer = intcode_new(program.as_ptr(), program.len());
+
+            assert_eq!(IntcodeStatus::Error, intcode_run(computer, INTCODE_UNLIMITED, ptr::null_mut()));
+            assert_eq!("Unknown opcode 42 at pc 4", CStr::from_ptr(intcode_error(computer)).to_str().unwrap());
+            assert_eq!(4, intcode_pc(computer));
+            intcode_free(computer);
+
+            assert_eq!(IntcodeStatus::InvalidHandle, intcode_run(ptr::null_mut(), 1, ptr::null_mut()));
+            assert!(intcode_snapshot(ptr::null()).is_null());
+            intcode_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn header_is_current() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/intcode.h"));
+        let committed = include_str!("../include/intcode.h");
+
+        assert!(generated == committed, "include/intcode.h is out of date - regenerate it with cbindgen");
+    }
+
+    // Overflow only panics in debug builds.
+    #[cfg(debug_assertions)]
+    #[test]
+    fn panics() {
+        unsafe {
+            let program = [1102, i64::MAX, 2, 0, 99];
+            let computer = intcode_new(program.as_ptr(), program.len());
+
+            assert_eq!(IntcodeStatus::Error, intcode_run(computer, INTCODE_UNLIMITED, ptr::null_mut()));
+            assert_eq!("Panicked: attempt to multiply with overflow at pc 0", CStr::from_ptr(intcode_error(computer)).to_str().unwrap());
+            intcode_free(computer);
+        }
+    }
+}
@@ -0,0 +1,443 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+// Intcode assembly is written one instruction per line, in the same syntax coverage listings use.
+// '#' starts a comment, and 'name:' labels the address of the next instruction:
+//
+//   # Prints the numbers from n down to 1.
+//   export countdown
+//
+//   countdown:
+//       out [n]
+//       add [n], -1, [n]
+//       jnz [n], countdown
+//       ret
+//   n:  data 3
+//
+// Parameters are immediate (5, label, label+2), position ([100], [label], [label-1]) or relative
+// ([rb], [rb+1], [rb-2]).  Labels are addresses in the module, and are relocated when the module
+// is linked.  A label that isn't defined in the module is an import, resolved by the linker from
+// the modules that export it.  Numbers in position mode are absolute addresses, and aren't relocated.
+//
+// The instructions are add, mul, in, out, jnz, jz, lt, eq, arb and halt, plus a few that expand
+// into several instructions:
+//
+//   data 1, 2, label    values stored in the program as they are
+//   mov a, b            add a, 0, b
+//   jmp target          jz 0, target
+//   push a              stores a on top of the stack and moves the relative base past it
+//   pop a               moves the relative base back and loads the top of the stack into a
+//   call f, a, b -> x   calls f with arguments a and b, and stores its first result in x
+//   ret                 returns from a call
+//
+// Calls use the relative base as a stack pointer: [rb] is the first free cell.  A call stores the
+// return address in [rb] and the arguments in [rb+1], [rb+2]..., and the routine leaves its
+// results in the same cells as the arguments.  A routine that uses the stack has to move the
+// relative base back to where it was before it returns.  A call reads all of its arguments before
+// it stores any of them, and all of the results before it stores any, so 'call f, [rb+2], [rb+1]'
+// swaps them.  When a copy would read a relative cell that an earlier copy wrote, the call copies
+// every value through free cells past all of the relative cells it uses first.
+
+/// A value in an assembled module: a number, or the address of a label plus an offset.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Word {
+    Value(i64),
+    Address { symbol: String, offset: i64 },
+}
+
+/// A line in an assembly module is wrong.  Line is 1-indexed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+/// An assembled module, with addresses relative to the start of the module.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Module {
+    pub name: String,
+    pub words: Vec<Word>,
+    /// Address of each label in the module.
+    pub labels: HashMap<String, usize>,
+    /// Labels other modules can refer to.
+    pub exports: Vec<String>,
+}
+
+/// A parsed parameter: its mode and value.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Parameter {
+    mode: i64,
+    word: Word,
+}
+
+impl Parameter {
+    fn immediate(value: i64) -> Parameter {
+        Parameter { mode: 1, word: Word::Value(value) }
+    }
+
+    fn relative(offset: i64) -> Parameter {
+        Parameter { mode: 2, word: Word::Value(offset) }
+    }
+}
+
+impl Module {
+    /// Assembles a module from its source.
+    pub fn parse(name: &str, source: &str) -> Result<Module, ParseError> {
+        let mut module = Module { name: name.to_string(), words: Vec::new(), labels: HashMap::new(), exports: Vec::new() };
+        let mut returns = 0;
+        let mut exported = Vec::new();
+
+        for (i, line) in source.lines().enumerate() {
+            let error = |message: String| ParseError { line: i + 1, message };
+
+            let mut text = line.split('#').next().unwrap().trim();
+
+            // Labels, possibly followed by an instruction on the same line.
+            while let Some(colon) = text.find(':') {
+                let label = text[..colon].trim();
+                if !is_symbol(label) {
+                    break;
+                }
+
+                if module.labels.insert(label.to_string(), module.words.len()).is_some() {
+                    return Err(error(format!("label '{}' is defined twice", label)));
+                }
+                text = text[colon + 1..].trim();
+            }
+
+            if text.is_empty() {
+                continue;
+            }
+
+            let (mnemonic, rest) = match text.find(char::is_whitespace) {
+                Some(space) => (&text[..space], text[space..].trim()),
+                None => (text, ""),
+            };
+
+            if mnemonic == "export" {
+                for symbol in rest.split(',').map(str::trim) {
+                    if !is_symbol(symbol) {
+                        return Err(error(format!("can't export '{}'", symbol)));
+                    }
+                    exported.push((symbol.to_string(), i + 1));
+                }
+                continue;
+            }
+
+            if mnemonic == "data" {
+                for value in rest.split(',').map(str::trim) {
+                    module.words.push(parse_word(value).ok_or_else(|| error(format!("'{}' isn't a number or label", value)))?);
+                }
+                continue;
+            }
+
+            let (operands, results) = match rest.split_once("->") {
+                Some((operands, results)) if mnemonic == "call" => (operands, parse_parameters(results).map_err(error)?),
+                _ => (rest, Vec::new()),
+            };
+            let operands = parse_parameters(operands).map_err(error)?;
+
+            for (opcode, parameters) in expand(mnemonic, operands, results, &mut returns, &mut module).map_err(error)? {
+                module.instruction(opcode, parameters).map_err(error)?;
+            }
+        }
+
+        for (symbol, line) in exported {
+            if !module.labels.contains_key(&symbol) {
+                return Err(ParseError { line, message: format!("exported label '{}' isn't defined", symbol) });
+            }
+            module.exports.push(symbol);
+        }
+
+        Ok(module)
+    }
+
+    /// Returns the symbols the module refers to but doesn't define.
+    pub fn imports(&self) -> Vec<&str> {
+        let mut imports: Vec<&str> = self.words.iter()
+            .filter_map(|word| match word {
+                Word::Address { symbol, .. } if !self.labels.contains_key(symbol) => Some(symbol.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        imports.sort_unstable();
+        imports.dedup();
+        imports
+    }
+
+    /// Adds an instruction to the module.  Fails if the instruction writes to an immediate parameter.
+    fn instruction(&mut self, opcode: i64, parameters: Vec<Parameter>) -> Result<(), String> {
+        let writes = matches!(opcode, 1 | 2 | 3 | 7 | 8);
+        if writes && parameters.last().is_some_and(|parameter| parameter.mode == 1) {
+            return Err("can't write to an immediate parameter".to_string());
+        }
+
+        let modes = parameters.iter().rev().fold(0, |modes, parameter| modes * 10 + parameter.mode);
+        self.words.push(Word::Value(modes * 100 + opcode));
+        self.words.extend(parameters.into_iter().map(|parameter| parameter.word));
+
+        Ok(())
+    }
+}
+
+/// Returns the opcodes and parameters of the instructions a mnemonic assembles to.
+fn expand(mnemonic: &str, operands: Vec<Parameter>, results: Vec<Parameter>, returns: &mut usize, module: &mut Module)
+    -> Result<Vec<(i64, Vec<Parameter>)>, String> {
+    let count = |expected: usize| if operands.len() == expected {
+        Ok(())
+    } else {
+        Err(format!("{} takes {} parameters, but has {}", mnemonic, expected, operands.len()))
+    };
+
+    let zero = Parameter::immediate(0);
+
+    Ok(match mnemonic {
+        "add" | "mul" | "lt" | "eq" => {
+            count(3)?;
+            let opcode = match mnemonic {
+                "add" => 1,
+                "mul" => 2,
+                "lt" => 7,
+                _ => 8,
+            };
+            vec![(opcode, operands)]
+        }
+        "in" | "out" | "arb" => {
+            count(1)?;
+            let opcode = match mnemonic {
+                "in" => 3,
+                "out" => 4,
+                _ => 9,
+            };
+            vec![(opcode, operands)]
+        }
+        "jnz" | "jz" => {
+            count(2)?;
+            vec![(if mnemonic == "jnz" { 5 } else { 6 }, operands)]
+        }
+        "halt" => {
+            count(0)?;
+            vec![(99, operands)]
+        }
+        "mov" => {
+            count(2)?;
+            let mut operands = operands.into_iter();
+            vec![(1, vec![operands.next().unwrap(), zero, operands.next().unwrap()])]
+        }
+        "jmp" => {
+            count(1)?;
+            vec![(6, vec![zero, operands.into_iter().next().unwrap()])]
+        }
+        "push" => {
+            count(1)?;
+            vec![(1, vec![operands.into_iter().next().unwrap(), zero, Parameter::relative(0)]), (9, vec![Parameter::immediate(1)])]
+        }
+        "pop" => {
+            count(1)?;
+            vec![(9, vec![Parameter::immediate(-1)]), (1, vec![Parameter::relative(0), zero, operands.into_iter().next().unwrap()])]
+        }
+        "ret" => {
+            count(0)?;
+            vec![(6, vec![zero, Parameter::relative(0)])]
+        }
+        "call" => {
+            if operands.is_empty() {
+                return Err("call needs a routine to call".to_string());
+            }
+
+            // The return address is a label after the jump, which can't clash with labels in the source.
+            *returns += 1;
+            let label = format!(".return{}", *returns);
+            let mut operands = operands.into_iter();
+            let routine = operands.next().unwrap();
+
+            let mut instructions = copy_all(operands.enumerate().map(|(i, argument)| (argument, Parameter::relative(i as i64 + 1))).collect());
+
+            let return_address = Parameter { mode: 1, word: Word::Address { symbol: label.clone(), offset: 0 } };
+            instructions.push((1, vec![return_address, zero.clone(), Parameter::relative(0)]));
+            instructions.push((6, vec![zero.clone(), routine]));
+
+            // The label goes after the jump, which is 3 words.
+            let jump_end = module.words.len() + instructions.iter().map(|(_, parameters)| parameters.len() + 1).sum::<usize>();
+            module.labels.insert(label, jump_end);
+
+            instructions.extend(copy_all(results.into_iter().enumerate().map(|(i, result)| (Parameter::relative(i as i64 + 1), result)).collect()));
+            instructions
+        }
+        _ => return Err(format!("unknown instruction '{}'", mnemonic)),
+    })
+}
+
+/// Returns instructions that copy each source to its destination, as if the copies all happened at
+/// once.  If a source is a relative cell that an earlier copy writes, every source is copied to a
+/// free cell past the relative cells the copies use first, and then to its destination.
+fn copy_all(copies: Vec<(Parameter, Parameter)>) -> Vec<(i64, Vec<Parameter>)> {
+    let offset = |parameter: &Parameter| match parameter {
+        Parameter { mode: 2, word: Word::Value(offset) } => Some(*offset),
+        _ => None,
+    };
+    let copy = |source: Parameter, destination: Parameter| (1, vec![source, Parameter::immediate(0), destination]);
+
+    let clobbered = copies.iter().enumerate().any(|(i, (source, _))| {
+        offset(source).is_some_and(|source| copies[..i].iter().any(|(_, destination)| offset(destination) == Some(source)))
+    });
+
+    if !clobbered {
+        return copies.into_iter().map(|(source, destination)| copy(source, destination)).collect();
+    }
+
+    let free = copies.iter().flat_map(|(source, destination)| offset(source).into_iter().chain(offset(destination))).fold(0, i64::max) + 1;
+    let staged: Vec<Parameter> = (0..copies.len() as i64).map(|i| Parameter::relative(free + i)).collect();
+
+    let mut instructions: Vec<(i64, Vec<Parameter>)> = copies.iter().zip(&staged).map(|((source, _), stage)| copy(source.clone(), stage.clone())).collect();
+    instructions.extend(copies.into_iter().zip(staged).map(|((_, destination), stage)| copy(stage, destination)));
+    instructions
+}
+
+/// Parses a comma separated list of parameters.
+fn parse_parameters(text: &str) -> Result<Vec<Parameter>, String> {
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    text.split(',').map(|parameter| parse_parameter(parameter.trim())).collect()
+}
+
+/// Parses a parameter: an immediate value, [address] in position mode, or [rb+offset] in relative mode.
+fn parse_parameter(text: &str) -> Result<Parameter, String> {
+    let invalid = || format!("'{}' isn't a valid parameter", text);
+
+    let inner = match text.strip_prefix('[').and_then(|text| text.strip_suffix(']')) {
+        Some(inner) => inner.trim(),
+        None => return parse_word(text).map(|word| Parameter { mode: 1, word }).ok_or_else(invalid),
+    };
+
+    if let Some(offset) = inner.strip_prefix("rb") {
+        let offset = offset.replace(' ', "");
+        return match offset.as_str() {
+            "" => Ok(Parameter::relative(0)),
+            _ if offset.starts_with('+') => offset[1..].parse().map(Parameter::relative).map_err(|_| invalid()),
+            _ => offset.parse().map(Parameter::relative).map_err(|_| invalid()),
+        };
+    }
+
+    parse_word(inner).map(|word| Parameter { mode: 0, word }).ok_or_else(invalid)
+}
+
+/// Parses a number, or a label with an optional offset like 'label+2'.
+fn parse_word(text: &str) -> Option<Word> {
+    if let Ok(value) = text.parse() {
+        return Some(Word::Value(value));
+    }
+
+    let text = text.replace(' ', "");
+    let (symbol, offset) = match text.find(['+', '-']) {
+        Some(sign) => (&text[..sign], text[sign..].trim_start_matches('+').parse().ok()?),
+        None => (text.as_str(), 0),
+    };
+
+    if is_symbol(symbol) {
+        Some(Word::Address { symbol: symbol.to_string(), offset })
+    } else {
+        None
+    }
+}
+
+/// Returns whether the text is a valid label: letters, digits and underscores, not starting with a
+/// digit.  'rb' is reserved for relative mode.
+fn is_symbol(text: &str) -> bool {
+    text.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && text != "rb"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(symbol: &str, offset: i64) -> Word {
+        Word::Address { symbol: symbol.to_string(), offset }
+    }
+
+    #[test]
+    fn instructions() {
+        let module = Module::parse("test", "
+            # Comments and blank lines are skipped.
+            export start
+            start: in [n]
+                mul [n], 2, [rb-1]
+            loop:
+                jnz [n+1], loop
+                out external+3
+                halt
+            n: data 5, -1, n
+        ").unwrap();
+
+        let value = Word::Value;
+        assert_eq!(vec![
+            value(3), address("n", 0),
+            value(21002), address("n", 0), value(2), value(-1),
+            value(1005), address("n", 1), address("loop", 0),
+            value(104), address("external", 3),
+            value(99),
+            value(5), value(-1), address("n", 0),
+        ], module.words);
+
+        assert_eq!(Some(&6), module.labels.get("loop"));
+        assert_eq!(vec!["start".to_string()], module.exports);
+        assert_eq!(vec!["external"], module.imports());
+    }
+
+    #[test]
+    fn pseudo_instructions() {
+        let module = Module::parse("test", "
+            mov 7, [x]
+            push [x]
+            pop [rb+3]
+            call f, [x], 2 -> [x]
+            jmp 0
+            ret
+            x: data 0
+        ").unwrap();
+
+        let value = Word::Value;
+        assert_eq!(vec![
+            value(1101), value(7), value(0), address("x", 0),
+            value(21001), address("x", 0), value(0), value(0), value(109), value(1),
+            value(109), value(-1), value(21201), value(0), value(0), value(3),
+            // call: arguments, return address, jump, then the result.
+            value(21001), address("x", 0), value(0), value(1),
+            value(21101), value(2), value(0), value(2),
+            value(21101), address(".return1", 0), value(0), value(0),
+            value(1106), value(0), address("f", 0),
+            value(1201), value(1), value(0), address("x", 0),
+            value(1106), value(0), value(0),
+            value(2106), value(0), value(0),
+            value(0),
+        ], module.words);
+
+        assert_eq!(Some(&31), module.labels.get(".return1"));
+    }
+
+    #[test]
+    fn errors() {
+        let error = |source: &str| Module::parse("test", source).unwrap_err();
+
+        assert_eq!(ParseError { line: 2, message: "unknown instruction 'sub'".to_string() }, error("halt\nsub 1, 2, [3]"));
+        assert_eq!("add takes 3 parameters, but has 2", error("add 1, [2]").message);
+        assert_eq!("can't write to an immediate parameter", error("add 1, 2, 3").message);
+        assert_eq!("'[rb*2]' isn't a valid parameter", error("out [rb*2]").message);
+        assert_eq!("label 'a' is defined twice", error("a: halt\na: halt").message);
+        assert_eq!("exported label 'b' isn't defined", error("export b\nhalt").message);
+    }
+}
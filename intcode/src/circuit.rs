@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::error::IntcodeError;
+use crate::search::{permutations, InputSearch};
+use crate::{Computer, ProgramState};
+
+// Circuits connect intcode programs the way day 7 connects amplifiers.  Each node runs a program
+// that reads a phase setting as its first input, and edges pass every value a node outputs to the
+// input of the nodes it's connected to.  A node connected to several others sends each of them a
+// copy of its output, and a node with several incoming edges reads their values in the order
+// they were sent.  Edges can form cycles.
+//
+// Circuits are described one statement per line, and '#' starts a comment:
+//
+//   # Day 7 part 2: five amplifiers in a feedback loop.
+//   node A amp          # node A runs the program named amp
+//   A -> B -> C -> D -> E -> A
+//   input A 0           # A reads 0 after its phase setting
+//   output E            # the circuit's output is what E sends
+//
+// Nodes don't need a node statement - a node that's only mentioned in edges runs the first
+// program passed to parse.  `A -> B, C` connects A to both B and C.
+//
+// Running a circuit gives each node a turn in the order they were declared, and a node's turn
+// lasts until it outputs a value or blocks.  The circuit runs until every node has halted or is
+// waiting for input that will never come.
+
+/// A statement in a circuit description is wrong.  Line is 1-indexed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+/// A node's program couldn't be executed while the circuit was running.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NodeError {
+    pub node: String,
+    pub pc: usize,
+    pub error: IntcodeError,
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "node {}: {} at pc {}", self.node, self.error, self.pc)
+    }
+}
+
+impl Error for NodeError {}
+
+/// A program running in a circuit.
+#[derive(Debug, Clone)]
+struct Node {
+    name: String,
+    program: Computer,
+    targets: Vec<usize>,
+    inputs: Vec<i64>,
+}
+
+/// Programs connected by edges that carry their outputs to each other's inputs.
+#[derive(Debug, Clone, Default)]
+pub struct Circuit {
+    nodes: Vec<Node>,
+    outputs: Vec<usize>,
+}
+
+/// Number of values a node received and sent while the circuit ran, and whether it halted.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Throughput {
+    pub node: String,
+    pub received: usize,
+    pub sent: usize,
+    pub halted: bool,
+}
+
+/// Result of running a circuit: the values sent by its output nodes, in the order they were sent,
+/// and each node's throughput in the order the nodes were declared.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Run {
+    pub outputs: Vec<i64>,
+    pub throughput: Vec<Throughput>,
+}
+
+impl Run {
+    /// Returns the last value sent by an output node.
+    pub fn last_output(&self) -> Option<i64> {
+        self.outputs.last().copied()
+    }
+}
+
+impl Circuit {
+    /// Constructs an empty circuit.
+    pub fn new() -> Circuit {
+        Circuit::default()
+    }
+
+    /// Parses a circuit description.  Programs are named so node statements can refer to them, and
+    /// nodes without a node statement run the first program.
+    pub fn parse(description: &str, programs: &[(&str, &Computer)]) -> Result<Circuit, ParseError> {
+        let mut circuit = Circuit::new();
+        let default = programs.first().map(|&(_, program)| program);
+        let programs: HashMap<&str, &Computer> = programs.iter().copied().collect();
+
+        for (i, line) in description.lines().enumerate() {
+            let error = |message: String| ParseError { line: i + 1, message };
+            let statement = line.split('#').next().unwrap().trim();
+            let words: Vec<&str> = statement.split_whitespace().collect();
+
+            match words.as_slice() {
+                [] => {},
+                ["node", name, program] => {
+                    if circuit.find(name).is_some() {
+                        return Err(error(format!("node {} is already defined", name)));
+                    }
+
+                    let program = programs.get(program).ok_or_else(|| error(format!("unknown program {}", program)))?;
+                    circuit = circuit.node(name, program);
+                },
+                ["input", name, values @ ..] if !values.is_empty() => {
+                    let node = circuit.find_or_add(name, default).map_err(error)?;
+                    for value in values {
+                        let value = value.parse().map_err(|_| error(format!("invalid input '{}'", value)))?;
+                        circuit.nodes[node].inputs.push(value);
+                    }
+                },
+                ["output", name] => {
+                    let node = circuit.find_or_add(name, default).map_err(error)?;
+                    circuit.outputs.push(node);
+                },
+                _ if statement.contains("->") => {
+                    let mut from: Option<Vec<usize>> = None;
+
+                    for group in statement.split("->") {
+                        let nodes = group.split(',')
+                            .map(|name| match name.trim() {
+                                "" => Err(format!("missing node in '{}'", statement)),
+                                name => circuit.find_or_add(name, default),
+                            })
+                            .collect::<Result<Vec<usize>, String>>()
+                            .map_err(error)?;
+
+                        for &source in from.iter().flatten() {
+                            circuit.nodes[source].targets.extend(&nodes);
+                        }
+
+                        from = Some(nodes);
+                    }
+                },
+                _ => return Err(error(format!("unknown statement '{}'", statement))),
+            }
+        }
+
+        Ok(circuit)
+    }
+
+    /// Adds a node that runs the given program.
+    pub fn node(mut self, name: &str, program: &Computer) -> Self {
+        self.nodes.push(Node { name: name.to_string(), program: program.clone(), targets: Vec::new(), inputs: Vec::new() });
+        self
+    }
+
+    /// Connects the output of one node to the input of another.  Panics if either node doesn't exist.
+    pub fn edge(mut self, from: &str, to: &str) -> Self {
+        let to = self.expect(to);
+        let from = self.expect(from);
+        self.nodes[from].targets.push(to);
+        self
+    }
+
+    /// Adds a value for the node to read after its phase setting.  Panics if the node doesn't exist.
+    pub fn input(mut self, node: &str, value: i64) -> Self {
+        let node = self.expect(node);
+        self.nodes[node].inputs.push(value);
+        self
+    }
+
+    /// Adds the values sent by the node to the circuit's output.  Panics if the node doesn't exist.
+    pub fn output(mut self, node: &str) -> Self {
+        let node = self.expect(node);
+        self.outputs.push(node);
+        self
+    }
+
+    /// Returns the names of the nodes, in the order they were declared.
+    pub fn names(&self) -> Vec<&str> {
+        self.nodes.iter().map(|node| node.name.as_str()).collect()
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.name == name)
+    }
+
+    fn expect(&self, name: &str) -> usize {
+        self.find(name).unwrap_or_else(|| panic!("Unknown node {}", name))
+    }
+
+    /// Returns the index of the named node, adding it with the default program if it doesn't exist.
+    fn find_or_add(&mut self, name: &str, default: Option<&Computer>) -> Result<usize, String> {
+        match (self.find(name), default) {
+            (Some(node), _) => Ok(node),
+            (None, Some(program)) => {
+                self.nodes.push(Node { name: name.to_string(), program: program.clone(), targets: Vec::new(), inputs: Vec::new() });
+                Ok(self.nodes.len() - 1)
+            },
+            (None, None) => Err(format!("node {} has no program", name)),
+        }
+    }
+
+    /// Runs the circuit with a phase setting for each node, in the order the nodes were declared,
+    /// until none of the nodes can make progress.  Panics if there isn't a phase for every node.
+    pub fn run(&self, phases: &[i64]) -> Result<Run, NodeError> {
+        assert_eq!(self.nodes.len(), phases.len(), "Circuit has {} nodes but got {} phases", self.nodes.len(), phases.len());
+
+        let mut programs: Vec<Computer> = self.nodes.iter().zip(phases)
+            .map(|(node, &phase)| {
+                let mut program = node.program.clone();
+                program.input(phase);
+                node.inputs.iter().for_each(|&value| program.input(value));
+                program
+            })
+            .collect();
+
+        let mut throughput: Vec<Throughput> = self.nodes.iter()
+            .map(|node| Throughput { node: node.name.clone(), received: 0, sent: 0, halted: false })
+            .collect();
+
+        let mut outputs = Vec::new();
+
+        while programs.iter().any(Computer::is_runnable) {
+            for i in 0..programs.len() {
+                let value = match next_output(&mut programs[i]) {
+                    Ok(Some(value)) => value,
+                    Ok(None) => continue,
+                    Err(error) => return Err(NodeError { node: self.nodes[i].name.clone(), pc: programs[i].pc(), error }),
+                };
+
+                throughput[i].sent += 1;
+                if self.outputs.contains(&i) {
+                    outputs.push(value);
+                }
+
+                for &target in &self.nodes[i].targets {
+                    programs[target].input(value);
+                    throughput[target].received += 1;
+                }
+            }
+        }
+
+        for (program, throughput) in programs.iter().zip(throughput.iter_mut()) {
+            throughput.halted = program.state() == &ProgramState::Done;
+        }
+
+        Ok(Run { outputs, throughput })
+    }
+
+    /// Returns the ordering of the phase settings that gives the highest last output, and the
+    /// output.  There has to be a phase setting for each node.  Orderings where a node fails or the
+    /// circuit doesn't output anything are skipped.
+    pub fn best_phases(&self, phases: &[i64]) -> Option<(Vec<i64>, i64)> {
+        InputSearch::new(self, permutations(phases))
+            .best(|circuit, phases| circuit.run(phases).ok()?.last_output())
+    }
+}
+
+/// Runs the program until it outputs a value or blocks.
+fn next_output(program: &mut Computer) -> Result<Option<i64>, IntcodeError> {
+    while program.is_runnable() {
+        program.step()?;
+
+        if let Some(value) = program.output.pop_front() {
+            return Ok(Some(value));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads a phase, then adds the phase to each value it reads and outputs the sum, until it
+    /// reads a 0 (which it passes on before halting).
+    fn adder() -> Computer {
+        Computer::new(vec![3, 20, 3, 21, 1006, 21, 16, 1, 20, 21, 22, 4, 22, 1105, 1, 2, 4, 21, 99, 0, 0, 0, 0])
+    }
+
+    #[test]
+    fn parse() {
+        let adder = adder();
+        let circuit = Circuit::parse("# fan out\nnode A add\nA -> B, C  # two targets\nB -> D\nC -> D\ninput A 1 0\noutput D", &[("add", &adder)]).unwrap();
+
+        assert_eq!(vec!["A", "B", "C", "D"], circuit.names());
+
+        assert_eq!(Err(ParseError { line: 1, message: "unknown program sub".to_string() }),
+                   Circuit::parse("node A sub", &[("add", &adder)]).map(|_| ()));
+        assert_eq!(Err(ParseError { line: 2, message: "unknown statement 'A => B'".to_string() }),
+                   Circuit::parse("A -> B\nA => B", &[("add", &adder)]).map(|_| ()));
+        assert_eq!(Err(ParseError { line: 1, message: "missing node in 'A -> -> B'".to_string() }),
+                   Circuit::parse("A -> -> B", &[("add", &adder)]).map(|_| ()));
+        assert_eq!(Err(ParseError { line: 1, message: "node A has no program".to_string() }),
+                   Circuit::parse("output A", &[]).map(|_| ()));
+    }
+
+    #[test]
+    fn fan_out_fan_in() {
+        // A sends 1 + 10 to B and C, which each add their phase and send it on to D.
+        let adder = adder();
+        let circuit = Circuit::parse("A -> B, C\nB, C -> D\ninput A 1 0\noutput D", &[("add", &adder)]).unwrap();
+        let run = circuit.run(&[10, 100, 1000, 5]).unwrap();
+
+        // D reads B's and C's values interleaved, and halts at B's 0.
+        assert_eq!(vec![116, 1016, 0], run.outputs);
+        assert_eq!(Throughput { node: "A".to_string(), received: 0, sent: 2, halted: true }, run.throughput[0]);
+        assert_eq!(Throughput { node: "D".to_string(), received: 4, sent: 3, halted: true }, run.throughput[3]);
+    }
+
+    #[test]
+    fn cycle() {
+        // Doubles the value it reads and outputs it, five times.
+        let doubler = Computer::new(vec![
+            3, 30, 3, 31, 1002, 31, 2, 31, 4, 31, 1001, 30, -1, 30, 1005, 30, 2, 99,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ]);
+
+        let circuit = Circuit::new()
+            .node("A", &doubler)
+            .node("B", &doubler)
+            .edge("A", "B")
+            .edge("B", "A")
+            .input("A", 1)
+            .output("B");
+
+        // A's phase is the number of times it loops.  B stops after 3, so A waits for the 4th forever.
+        let run = circuit.run(&[5, 3]).unwrap();
+        assert_eq!(vec![4, 16, 64], run.outputs);
+        assert!(!run.throughput[0].halted);
+        assert!(run.throughput[1].halted);
+
+        assert_eq!(Some((vec![5, 5], 1024)), circuit.best_phases(&[5, 5]));
+    }
+
+    #[test]
+    fn node_error() {
+        let broken = Computer::new(vec![3, 0, 42]);
+        let circuit = Circuit::new().node("A", &broken);
+
+        assert_eq!(Err(NodeError { node: "A".to_string(), pc: 2, error: IntcodeError::UnknownOpcode { opcode: 42 } }), circuit.run(&[1]));
+    }
+}
@@ -0,0 +1,1171 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::asm::Module;
+use crate::link::Linker;
+use crate::program::write_program;
+
+// A compiler for a small language with integer variables, functions, if and while.  It compiles
+// to assembly, then assembles and links it with the standard library, so the result is a program
+// Computer::load can run.
+//
+//   // Prints the factorials of the numbers it reads, until it reads 0.
+//   var count = 0;
+//
+//   fn factorial(n) {
+//       if (n <= 1) { return 1; }
+//       return n * factorial(n - 1);
+//   }
+//
+//   fn main() {
+//       var n = read();
+//       while (n != 0) {
+//           print(factorial(n));
+//           count = count + 1;
+//           n = read();
+//       }
+//   }
+//
+// Every value is an integer.  Operators are the ones Rust has for integers, except that comparisons,
+// ! and the short circuiting && and || produce 1 or 0, and conditions are true when they aren't 0.
+// Dividing by 0 halts the program.  read() reads an input value and print(x) outputs x.
+//
+// Variables declared outside functions are globals, and are initialized in order before main is
+// called.  Variables declared in a function last until the end of their block.  Functions use the
+// calling convention in asm.rs: each call moves the relative base past the caller's frame, so the
+// callee's frame starts with its return address, then its parameters, locals and temporary
+// values.  Functions can be recursive, and return 0 if they end without a return statement.
+
+/// A line and column in the source, both 1-indexed.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// The source couldn't be compiled.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CompileError {
+    pub position: Position,
+    pub message: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.position, self.message)
+    }
+}
+
+impl Error for CompileError {}
+
+/// Returns a compile error at the given position.
+fn error<T>(position: Position, message: String) -> Result<T, CompileError> {
+    Err(CompileError { position, message })
+}
+
+/// Where in the source the instructions at each address came from.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct SourceMap {
+    /// Address of the first instruction of each function and statement, in address order.
+    entries: Vec<(usize, Position)>,
+    /// First address after the compiled code.
+    end: usize,
+}
+
+impl SourceMap {
+    /// Returns the address each function and statement starts at, in address order.
+    pub fn entries(&self) -> &[(usize, Position)] {
+        &self.entries
+    }
+
+    /// Returns the position of the statement the instruction at the given address belongs to, or
+    /// None if the address isn't in the compiled code, like the standard library or globals.
+    pub fn position(&self, addr: usize) -> Option<Position> {
+        if addr >= self.end {
+            return None;
+        }
+
+        self.entries.iter().take_while(|(start, _)| *start <= addr).last().map(|&(_, position)| position)
+    }
+
+    /// Returns the addresses of the statements that start on the given line, which is where a
+    /// breakpoint on the line goes.
+    pub fn addresses(&self, line: usize) -> Vec<usize> {
+        self.entries.iter().filter(|(_, position)| position.line == line).map(|&(addr, _)| addr).collect()
+    }
+}
+
+/// A compiled program, and where its instructions came from.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Compiled {
+    pub program: Vec<i64>,
+    pub source_map: SourceMap,
+    /// Assembly the program was assembled from.
+    pub assembly: String,
+}
+
+impl Compiled {
+    /// Writes the program to the given file, in the format Computer::load reads.
+    pub fn save<P: AsRef<Path>>(&self, filename: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        write_program(&mut writer, &self.program)?;
+        writer.flush()
+    }
+}
+
+/// Compiles the source into a program.
+pub fn compile(source: &str) -> Result<Compiled, CompileError> {
+    let program = Parser::new(lex(source)?).program()?;
+    let generated = generate(&program)?;
+
+    let module = Module::parse("main", &generated.assembly)
+        .unwrap_or_else(|e| panic!("compiler generated invalid assembly at {}", e));
+    let linked = Linker::new().module(module.clone()).with_stdlib().link()
+        .unwrap_or_else(|e| panic!("compiler generated a module that doesn't link: {}", e));
+
+    // The compiled module is linked first, so its labels are addresses in the program.
+    let mut entries: Vec<(usize, Position)> = generated.positions.iter()
+        .map(|(label, position)| (module.labels[label], *position))
+        .collect();
+    entries.sort_by_key(|&(addr, _)| addr);
+
+    Ok(Compiled {
+        program: linked.program,
+        source_map: SourceMap { entries, end: module.labels[DATA] },
+        assembly: generated.assembly,
+    })
+}
+
+/// Label the compiler puts between the code and the globals.
+const DATA: &str = "_data";
+
+/// A piece of source text.
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token {
+    Number(i64),
+    /// A name or keyword.
+    Word(String),
+    Symbol(&'static str),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "'{}'", value),
+            Token::Word(word) => write!(f, "'{}'", word),
+            Token::Symbol(symbol) => write!(f, "'{}'", symbol),
+            Token::End => write!(f, "the end of the source"),
+        }
+    }
+}
+
+/// Symbols in the language.  Two character symbols come first, so they're matched before the
+/// one character symbols they start with.
+const SYMBOLS: [&str; 21] = [
+    "<=", ">=", "==", "!=", "&&", "||",
+    "(", ")", "{", "}", ",", ";", "=", "+", "-", "*", "/", "%", "<", ">", "!",
+];
+
+/// Words that can't be used as names.
+const KEYWORDS: [&str; 6] = ["fn", "var", "if", "else", "while", "return"];
+
+/// Splits the source into tokens, ending with Token::End.  '//' comments run to the end of the line.
+fn lex(source: &str) -> Result<Vec<(Token, Position)>, CompileError> {
+    let mut tokens = Vec::new();
+
+    for (i, line) in source.lines().enumerate() {
+        let line = line.split("//").next().unwrap();
+        let mut column = 0;
+
+        while column < line.len() {
+            let rest = &line[column..];
+            let position = Position { line: i + 1, column: column + 1 };
+            let c = rest.chars().next().unwrap();
+
+            if c.is_whitespace() {
+                column += c.len_utf8();
+                continue;
+            }
+
+            let length = if c.is_ascii_digit() {
+                let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                match rest[..digits].parse() {
+                    Ok(value) => tokens.push((Token::Number(value), position)),
+                    Err(_) => return error(position, format!("{} is too big", &rest[..digits])),
+                }
+                digits
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let length = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+                tokens.push((Token::Word(rest[..length].to_string()), position));
+                length
+            } else {
+                match SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+                    None => return error(position, format!("unexpected character '{}'", c)),
+                    Some(symbol) => {
+                        tokens.push((Token::Symbol(symbol), position));
+                        symbol.len()
+                    }
+                }
+            };
+
+            column += length;
+        }
+    }
+
+    let end = Position { line: source.lines().count() + 1, column: 1 };
+    tokens.push((Token::End, end));
+    Ok(tokens)
+}
+
+/// A function definition.
+#[derive(Debug, Clone)]
+struct Function {
+    name: String,
+    params: Vec<String>,
+    body: Vec<Statement>,
+    position: Position,
+}
+
+/// A statement, and where it starts.
+#[derive(Debug, Clone)]
+struct Statement {
+    kind: StatementKind,
+    position: Position,
+}
+
+#[derive(Debug, Clone)]
+enum StatementKind {
+    Var { name: String, value: Expr },
+    Assign { name: String, value: Expr },
+    If { condition: Expr, then: Vec<Statement>, otherwise: Vec<Statement> },
+    While { condition: Expr, body: Vec<Statement> },
+    Return(Option<Expr>),
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(i64),
+    Variable { name: String, position: Position },
+    Call { name: String, args: Vec<Expr>, position: Position },
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary { operator: &'static str, left: Box<Expr>, right: Box<Expr> },
+}
+
+impl Expr {
+    /// Returns true if evaluating the expression calls a function, which can assign globals.
+    fn calls_function(&self) -> bool {
+        match self {
+            Expr::Number(_) | Expr::Variable { .. } => false,
+            Expr::Call { name, args, .. } => !matches!(name.as_str(), "read" | "print") || args.iter().any(Expr::calls_function),
+            Expr::Negate(value) | Expr::Not(value) => value.calls_function(),
+            Expr::Binary { left, right, .. } => left.calls_function() || right.calls_function(),
+        }
+    }
+}
+
+/// A parsed program: its globals and their initializers, and its functions.
+#[derive(Debug, Clone)]
+struct Program {
+    globals: Vec<Statement>,
+    functions: Vec<Function>,
+}
+
+/// Binary operators from lowest to highest precedence.
+const PRECEDENCE: [&[&str]; 6] = [
+    &["||"],
+    &["&&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+/// Recursive descent parser over the tokens.
+struct Parser {
+    tokens: Vec<(Token, Position)>,
+    index: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<(Token, Position)>) -> Parser {
+        Parser { tokens, index: 0 }
+    }
+
+    /// Returns the next token without consuming it.
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index].0
+    }
+
+    /// Returns the position of the next token.
+    fn position(&self) -> Position {
+        self.tokens[self.index].1
+    }
+
+    /// Consumes the next token.
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.index].0.clone();
+        if token != Token::End {
+            self.index += 1;
+        }
+        token
+    }
+
+    /// Consumes the next token if it's the given symbol or keyword.
+    fn eat(&mut self, text: &str) -> bool {
+        let matches = match self.peek() {
+            Token::Symbol(symbol) => *symbol == text,
+            Token::Word(word) => word == text,
+            _ => false,
+        };
+
+        if matches {
+            self.next();
+        }
+        matches
+    }
+
+    /// Consumes the given symbol or keyword, failing if the next token is something else.
+    fn expect(&mut self, text: &str) -> Result<(), CompileError> {
+        if self.eat(text) {
+            Ok(())
+        } else {
+            error(self.position(), format!("expected '{}', found {}", text, self.peek()))
+        }
+    }
+
+    /// Consumes a name.
+    fn name(&mut self) -> Result<String, CompileError> {
+        let position = self.position();
+        match self.next() {
+            Token::Word(word) if KEYWORDS.contains(&word.as_str()) => error(position, format!("'{}' is a keyword", word)),
+            Token::Word(word) => Ok(word),
+            token => error(position, format!("expected a name, found {}", token)),
+        }
+    }
+
+    fn program(&mut self) -> Result<Program, CompileError> {
+        let mut program = Program { globals: Vec::new(), functions: Vec::new() };
+
+        while *self.peek() != Token::End {
+            let position = self.position();
+
+            if self.eat("fn") {
+                let name = self.name()?;
+                self.expect("(")?;
+
+                let mut params = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        params.push(self.name()?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+
+                let body = self.block()?;
+                program.functions.push(Function { name, params, body, position });
+            } else if matches!(self.peek(), Token::Word(word) if word == "var") {
+                program.globals.push(self.statement()?);
+            } else {
+                return error(position, format!("expected 'fn' or 'var', found {}", self.peek()));
+            }
+        }
+
+        Ok(program)
+    }
+
+    /// Parses statements between braces.
+    fn block(&mut self) -> Result<Vec<Statement>, CompileError> {
+        self.expect("{")?;
+
+        let mut statements = Vec::new();
+        while !self.eat("}") {
+            statements.push(self.statement()?);
+        }
+
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Statement, CompileError> {
+        let position = self.position();
+
+        let kind = if self.eat("var") {
+            let name = self.name()?;
+            self.expect("=")?;
+            let value = self.expr()?;
+            self.expect(";")?;
+            StatementKind::Var { name, value }
+        } else if self.eat("if") {
+            let condition = self.condition()?;
+            let then = self.block()?;
+            let otherwise = if !self.eat("else") {
+                Vec::new()
+            } else if matches!(self.peek(), Token::Word(word) if word == "if") {
+                vec![self.statement()?]
+            } else {
+                self.block()?
+            };
+            StatementKind::If { condition, then, otherwise }
+        } else if self.eat("while") {
+            let condition = self.condition()?;
+            StatementKind::While { condition, body: self.block()? }
+        } else if self.eat("return") {
+            let value = if self.eat(";") {
+                None
+            } else {
+                let value = self.expr()?;
+                self.expect(";")?;
+                Some(value)
+            };
+            StatementKind::Return(value)
+        } else if matches!(self.tokens.get(self.index + 1), Some((Token::Symbol("="), _))) {
+            let name = self.name()?;
+            self.expect("=")?;
+            let value = self.expr()?;
+            self.expect(";")?;
+            StatementKind::Assign { name, value }
+        } else {
+            let value = self.expr()?;
+            self.expect(";")?;
+            StatementKind::Expr(value)
+        };
+
+        Ok(Statement { kind, position })
+    }
+
+    /// Parses an expression in parentheses, for if and while.
+    fn condition(&mut self) -> Result<Expr, CompileError> {
+        self.expect("(")?;
+        let condition = self.expr()?;
+        self.expect(")")?;
+        Ok(condition)
+    }
+
+    fn expr(&mut self) -> Result<Expr, CompileError> {
+        self.binary(0)
+    }
+
+    /// Parses a chain of operators at the given precedence level, which are left associative.
+    fn binary(&mut self, level: usize) -> Result<Expr, CompileError> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+
+        let mut left = self.binary(level + 1)?;
+        loop {
+            let operator = match self.peek() {
+                Token::Symbol(symbol) if PRECEDENCE[level].contains(symbol) => *symbol,
+                _ => return Ok(left),
+            };
+            self.next();
+
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary { operator, left: Box::new(left), right: Box::new(right) };
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        if self.eat("-") {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+
+        let position = self.position();
+        match self.next() {
+            Token::Number(value) => Ok(Expr::Number(value)),
+            Token::Symbol("(") => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Word(name) if !KEYWORDS.contains(&name.as_str()) => {
+                if !self.eat("(") {
+                    return Ok(Expr::Variable { name, position });
+                }
+
+                let mut args = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+
+                Ok(Expr::Call { name, args, position })
+            }
+            token => error(position, format!("expected a value, found {}", token)),
+        }
+    }
+}
+
+/// Where a value lives while the program runs.
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Operand {
+    Immediate(i64),
+    /// A slot in the current function's frame, relative to the relative base.
+    Local(i64),
+    Global(String),
+}
+
+impl Operand {
+    /// Returns the operand as an assembly parameter, for when the relative base has been moved
+    /// forward by the given amount.
+    fn shifted(&self, shift: i64) -> String {
+        match self {
+            Operand::Immediate(value) => value.to_string(),
+            Operand::Local(slot) => format!("[rb{:+}]", slot - shift),
+            Operand::Global(name) => format!("[g_{}]", name),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.shifted(0))
+    }
+}
+
+/// Assembly for a whole program, and the labels that mark where each statement starts.
+struct Generated {
+    assembly: String,
+    positions: Vec<(String, Position)>,
+}
+
+/// Generates assembly for the program: start up code that initializes the globals and calls main,
+/// then the functions, then the globals.
+fn generate(program: &Program) -> Result<Generated, CompileError> {
+    let mut functions = HashMap::new();
+    for function in &program.functions {
+        if function.name == "read" || function.name == "print" {
+            return error(function.position, format!("'{}' is built in", function.name));
+        }
+        if functions.insert(function.name.as_str(), function.params.len()).is_some() {
+            return error(function.position, format!("function '{}' is defined twice", function.name));
+        }
+    }
+
+    match functions.get("main") {
+        Some(0) => {}
+        Some(_) => return error(program.functions.iter().find(|f| f.name == "main").unwrap().position, "main can't take parameters".to_string()),
+        None => return error(Position { line: 1, column: 1 }, "there's no main function".to_string()),
+    }
+
+    let mut globals = HashSet::new();
+    for global in &program.globals {
+        if let StatementKind::Var { name, .. } = &global.kind {
+            if !globals.insert(name.as_str()) {
+                return error(global.position, format!("global '{}' is defined twice", name));
+            }
+        }
+    }
+
+    let context = Context { functions, globals };
+    let mut lines = vec!["arb stack".to_string()];
+
+    // The start up code runs in a frame of its own, like a function without parameters.
+    let generator = FunctionGenerator::compile(&context, &[], &program.globals, true, 0)?;
+    lines.extend(generator.lines);
+    let mut positions = generator.positions;
+    let mut labels = generator.labels;
+
+    for function in &program.functions {
+        lines.push(String::new());
+        lines.push(format!("f_{}:", function.name));
+
+        let generator = FunctionGenerator::compile(&context, &function.params, &function.body, false, labels)?;
+        positions.push((format!("f_{}", function.name), function.position));
+        positions.extend(generator.positions);
+        lines.extend(generator.lines);
+        labels = generator.labels;
+    }
+
+    lines.push(String::new());
+    lines.push(format!("{}:", DATA));
+    for global in &program.globals {
+        if let StatementKind::Var { name, .. } = &global.kind {
+            lines.push(format!("g_{}: data 0", name));
+        }
+    }
+
+    Ok(Generated { assembly: lines.join("\n") + "\n", positions })
+}
+
+/// Names that are defined everywhere in the program.
+struct Context<'a> {
+    /// Number of parameters each function takes.
+    functions: HashMap<&'a str, usize>,
+    globals: HashSet<&'a str>,
+}
+
+/// Generates the assembly for a function's body.
+struct FunctionGenerator<'a> {
+    context: &'a Context<'a>,
+    lines: Vec<String>,
+    /// Labels that mark where each statement starts.
+    positions: Vec<(String, Position)>,
+    /// Number of labels generated so far in the program, which keeps labels unique.
+    labels: usize,
+    /// Whether this is the start up code, where top level variables are the globals.
+    start: bool,
+    /// Frame slot of each local in scope, innermost block last.
+    scopes: Vec<HashMap<String, i64>>,
+    /// First frame slot that isn't in use.
+    next_slot: i64,
+    /// Number of slots the function's frame needs.
+    frame: i64,
+}
+
+impl<'a> FunctionGenerator<'a> {
+    /// Generates the body of a function with the given parameters, or the start up code, which
+    /// calls main and halts instead of returning.  A function's frame has to be big enough for
+    /// every slot it uses, and calls have to move the relative base past it, so the body is
+    /// generated once to measure the frame and again to use it.
+    fn compile(context: &'a Context<'a>, params: &[String], body: &[Statement], start: bool, labels: usize)
+        -> Result<FunctionGenerator<'a>, CompileError> {
+        let mut frame = 0;
+
+        loop {
+            let mut generator = FunctionGenerator {
+                context,
+                lines: Vec::new(),
+                positions: Vec::new(),
+                labels,
+                start,
+                scopes: vec![HashMap::new()],
+                next_slot: params.len() as i64 + 1,
+                frame,
+            };
+
+            for (i, param) in params.iter().enumerate() {
+                generator.scopes[0].insert(param.clone(), i as i64 + 1);
+            }
+
+            body.iter().try_for_each(|statement| generator.statement(statement))?;
+
+            if start {
+                let main = Expr::Call { name: "main".to_string(), args: Vec::new(), position: Position { line: 1, column: 1 } };
+                generator.expr(&main, None)?;
+                generator.emit("halt".to_string());
+            } else {
+                generator.emit("mov 0, [rb+1]".to_string());
+                generator.emit("ret".to_string());
+            }
+
+            if generator.frame == frame && frame > 0 {
+                return Ok(generator);
+            }
+            frame = generator.frame.max(generator.next_slot);
+        }
+    }
+
+    fn emit(&mut self, line: String) {
+        self.lines.push(format!("    {}", line));
+    }
+
+    /// Returns a new label.
+    fn label(&mut self) -> String {
+        self.labels += 1;
+        format!("_L{}", self.labels)
+    }
+
+    /// Marks the address of the next instruction with the label.
+    fn place(&mut self, label: &str) {
+        self.lines.push(format!("{}:", label));
+    }
+
+    /// Returns a frame slot for a temporary value.  Slots are reused after each statement.
+    fn temp(&mut self) -> Operand {
+        let slot = self.next_slot;
+        self.next_slot += 1;
+        self.frame = self.frame.max(self.next_slot);
+
+        Operand::Local(slot)
+    }
+
+    /// Returns where the variable with the given name lives.
+    fn variable(&self, name: &str, position: Position) -> Result<Operand, CompileError> {
+        if let Some(&slot) = self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            return Ok(Operand::Local(slot));
+        }
+
+        if self.context.globals.contains(name) {
+            return Ok(Operand::Global(name.to_string()));
+        }
+
+        error(position, format!("'{}' isn't defined", name))
+    }
+
+    fn block(&mut self, statements: &[Statement]) -> Result<(), CompileError> {
+        let mark = self.next_slot;
+        self.scopes.push(HashMap::new());
+
+        statements.iter().try_for_each(|statement| self.statement(statement))?;
+
+        self.scopes.pop();
+        self.next_slot = mark;
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), CompileError> {
+        let mark = self.next_slot;
+
+        self.labels += 1;
+        let label = format!("_s{}", self.labels);
+        self.place(&label);
+        self.positions.push((label, statement.position));
+
+        match &statement.kind {
+            // Globals are defined before the program starts, so only their values are set here.
+            StatementKind::Var { name, value } if self.start && self.scopes.len() == 1 => {
+                self.expr_into(value, Operand::Global(name.clone()))?;
+            }
+            StatementKind::Var { name, value } => {
+                if self.scopes.last().unwrap().contains_key(name) {
+                    return error(statement.position, format!("'{}' is already defined in this block", name));
+                }
+
+                let slot = Operand::Local(mark);
+                self.next_slot = mark + 1;
+                self.frame = self.frame.max(self.next_slot);
+                self.expr_into(value, slot)?;
+
+                self.scopes.last_mut().unwrap().insert(name.clone(), mark);
+                self.next_slot = mark + 1;
+                return Ok(());
+            }
+            StatementKind::Assign { name, value } => {
+                let variable = self.variable(name, statement.position)?;
+                self.expr_into(value, variable)?;
+            }
+            StatementKind::If { condition, then, otherwise } => {
+                let condition = self.expr(condition, None)?;
+                self.next_slot = mark;
+
+                let otherwise_label = self.label();
+                self.emit(format!("jz {}, {}", condition, otherwise_label));
+                self.block(then)?;
+
+                if otherwise.is_empty() {
+                    self.place(&otherwise_label);
+                } else {
+                    let end = self.label();
+                    self.emit(format!("jmp {}", end));
+                    self.place(&otherwise_label);
+                    self.block(otherwise)?;
+                    self.place(&end);
+                }
+            }
+            StatementKind::While { condition, body } => {
+                let top = self.label();
+                let end = self.label();
+
+                self.place(&top);
+                let condition = self.expr(condition, None)?;
+                self.next_slot = mark;
+                self.emit(format!("jz {}, {}", condition, end));
+                self.block(body)?;
+                self.emit(format!("jmp {}", top));
+                self.place(&end);
+            }
+            StatementKind::Return(value) => {
+                let result = Operand::Local(1);
+                match value {
+                    Some(value) => self.expr_into(value, result)?,
+                    None => self.emit("mov 0, [rb+1]".to_string()),
+                }
+                self.emit("ret".to_string());
+            }
+            StatementKind::Expr(value) => {
+                self.expr(value, None)?;
+            }
+        }
+
+        self.next_slot = mark;
+        Ok(())
+    }
+
+    /// Evaluates the expression into the given location.
+    fn expr_into(&mut self, expr: &Expr, dst: Operand) -> Result<(), CompileError> {
+        let value = self.expr(expr, Some(dst.clone()))?;
+        if value != dst {
+            self.emit(format!("mov {}, {}", value, dst));
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates the expression, returning where its value is.  Constants and variables are used
+    /// where they are, and other values are stored in dst if it's given, or a temporary otherwise.
+    /// Every instruction that writes to dst comes after the last read of the expression's operands,
+    /// so dst can be one of the variables the expression reads.
+    fn expr(&mut self, expr: &Expr, dst: Option<Operand>) -> Result<Operand, CompileError> {
+        Ok(match expr {
+            Expr::Number(value) => Operand::Immediate(*value),
+            Expr::Variable { name, position } => self.variable(name, *position)?,
+            Expr::Call { name, args, position } => {
+                let expected = match name.as_str() {
+                    "read" => 0,
+                    "print" => 1,
+                    _ => match self.context.functions.get(name.as_str()) {
+                        Some(&params) => params,
+                        None => return error(*position, format!("function '{}' isn't defined", name)),
+                    },
+                };
+
+                if args.len() != expected {
+                    return error(*position, format!("{} takes {} arguments, but has {}", name, expected, args.len()));
+                }
+
+                let args = args.iter().enumerate()
+                    .map(|(i, arg)| {
+                        let value = self.expr(arg, None)?;
+                        Ok(self.pin(value, &args[i + 1..]))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                match name.as_str() {
+                    "read" => {
+                        let dst = self.target(&dst);
+                        self.emit(format!("in {}", dst));
+                        dst
+                    }
+                    "print" => {
+                        self.emit(format!("out {}", args[0]));
+                        args[0].clone()
+                    }
+                    _ => {
+                        let dst = self.target(&dst);
+                        self.call(&format!("f_{}", name), &args, std::slice::from_ref(&dst));
+                        dst
+                    }
+                }
+            }
+            Expr::Negate(value) => match self.expr(value, None)? {
+                Operand::Immediate(value) => Operand::Immediate(value.wrapping_neg()),
+                value => {
+                    let dst = self.target(&dst);
+                    self.emit(format!("mul {}, -1, {}", value, dst));
+                    dst
+                }
+            },
+            Expr::Not(value) => {
+                let value = self.expr(value, None)?;
+                let dst = self.target(&dst);
+                self.emit(format!("eq {}, 0, {}", value, dst));
+                dst
+            }
+            Expr::Binary { operator, left, right } if *operator == "&&" || *operator == "||" => {
+                // The result goes in a temporary, since it's written before the right side is read.
+                let result = self.temp();
+                let end = self.label();
+
+                let left = self.expr(left, None)?;
+                if *operator == "&&" {
+                    self.emit(format!("mov 0, {}", result));
+                    self.emit(format!("jz {}, {}", left, end));
+                } else {
+                    self.emit(format!("mov 1, {}", result));
+                    self.emit(format!("jnz {}, {}", left, end));
+                }
+
+                let right = self.expr(right, None)?;
+                self.emit(format!("eq {}, 0, {}", right, result));
+                self.emit(format!("eq {}, 0, {}", result, result));
+                self.place(&end);
+                result
+            }
+            Expr::Binary { operator, left, right } => {
+                let left = self.expr(left, None)?;
+                let left = self.pin(left, std::slice::from_ref(right));
+                let right = self.expr(right, None)?;
+
+                match *operator {
+                    "-" => match right {
+                        Operand::Immediate(value) => {
+                            let dst = self.target(&dst);
+                            self.emit(format!("add {}, {}, {}", left, value.wrapping_neg(), dst));
+                            dst
+                        }
+                        right => {
+                            let negated = self.temp();
+                            self.emit(format!("mul {}, -1, {}", right, negated));
+                            let dst = self.target(&dst);
+                            self.emit(format!("add {}, {}, {}", left, negated, dst));
+                            dst
+                        }
+                    },
+                    "/" | "%" => {
+                        if !matches!(right, Operand::Immediate(value) if value != 0) {
+                            let nonzero = self.label();
+                            self.emit(format!("jnz {}, {}", right, nonzero));
+                            self.emit("halt".to_string());
+                            self.place(&nonzero);
+                        }
+
+                        let dst = self.target(&dst);
+                        let unused = self.temp();
+                        let results = if *operator == "/" { [dst.clone(), unused] } else { [unused, dst.clone()] };
+                        self.call("divmod", &[left, right], &results);
+                        dst
+                    }
+                    operator => {
+                        let dst = self.target(&dst);
+                        let instruction = match operator {
+                            "+" => format!("add {}, {}, {}", left, right, dst),
+                            "*" => format!("mul {}, {}, {}", left, right, dst),
+                            "<" | ">=" => format!("lt {}, {}, {}", left, right, dst),
+                            ">" | "<=" => format!("lt {}, {}, {}", right, left, dst),
+                            "==" | "!=" => format!("eq {}, {}, {}", left, right, dst),
+                            _ => unreachable!("unknown operator {}", operator),
+                        };
+                        self.emit(instruction);
+
+                        // These are the opposite of the comparison above.
+                        if matches!(operator, ">=" | "<=" | "!=") {
+                            self.emit(format!("eq {}, 0, {}", dst, dst));
+                        }
+                        dst
+                    }
+                }
+            }
+        })
+    }
+
+    /// Copies a global into a temporary if any of the expressions evaluated after it call a function,
+    /// so the value that's used is the one it had when it was evaluated, left to right.
+    fn pin(&mut self, value: Operand, later: &[Expr]) -> Operand {
+        if !matches!(value, Operand::Global(_)) || !later.iter().any(Expr::calls_function) {
+            return value;
+        }
+
+        let temp = self.temp();
+        self.emit(format!("mov {}, {}", value, temp));
+        temp
+    }
+
+    /// Returns dst if it's given, or a new temporary.
+    fn target(&mut self, dst: &Option<Operand>) -> Operand {
+        dst.clone().unwrap_or_else(|| self.temp())
+    }
+
+    /// Calls a routine with the relative base moved past this function's frame, so the callee's
+    /// frame starts after it.
+    fn call(&mut self, routine: &str, args: &[Operand], results: &[Operand]) {
+        let shift = |operands: &[Operand]| operands.iter().map(|operand| operand.shifted(self.frame)).collect::<Vec<_>>().join(", ");
+        let mut call = format!("call {}", routine);
+        if !args.is_empty() {
+            call += &format!(", {}", shift(args));
+        }
+        call += &format!(" -> {}", shift(results));
+
+        self.emit(format!("arb {}", self.frame));
+        self.emit(call);
+        self.emit(format!("arb {}", -self.frame));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Computer;
+
+    /// Compiles and runs the source with the given input, returning its output.
+    fn run(source: &str, input: &[i64]) -> Vec<i64> {
+        let compiled = compile(source).unwrap_or_else(|e| panic!("{}", e));
+
+        let mut computer = Computer::new(compiled.program).with_strict_writes();
+        input.iter().for_each(|&value| computer.input(value));
+        computer.try_run().unwrap();
+        assert!(!computer.is_runnable());
+
+        computer.dump_output()
+    }
+
+    #[test]
+    fn programs() {
+        let factorials = "
+            // Prints the factorials of the numbers it reads, until it reads 0.
+            var count = 0;
+
+            fn factorial(n) {
+                if (n <= 1) { return 1; }
+                return n * factorial(n - 1);
+            }
+
+            fn main() {
+                var n = read();
+                while (n != 0) {
+                    print(factorial(n));
+                    count = count + 1;
+                    n = read();
+                }
+                print(count);
+            }
+        ";
+        assert_eq!(vec![1, 120, 3628800, 3], run(factorials, &[1, 5, 10, 0]));
+
+        let fizzbuzz = "
+            fn main() {
+                var i = 1;
+                while (i <= 15) {
+                    if (i % 15 == 0) { print(-3); }
+                    else if (i % 5 == 0) { print(-2); }
+                    else if (i % 3 == 0) { print(-1); }
+                    else { print(i); }
+                    i = i + 1;
+                }
+            }
+        ";
+        assert_eq!(vec![1, 2, -1, 4, -2, -1, 7, 8, -1, -2, 11, -1, 13, 14, -3], run(fizzbuzz, &[]));
+
+        let scopes = "
+            var total = 10;
+            var doubled = total * 2;
+
+            fn fib(n) {
+                if (n < 2) { return n; }
+                var a = fib(n - 1);
+                var b = fib(n - 2);
+                return a + b;
+            }
+
+            fn add(a, b, c) { total = total + a + b + c; }
+
+            fn main() {
+                var x = 1;
+                if (x) {
+                    var x = 2;
+                    print(x);
+                }
+                print(x);
+                print(fib(15));
+                add(1, 2, 3);
+                print(total + doubled);
+                print(add(0, 0, 0));
+            }
+        ";
+        assert_eq!(vec![2, 1, 610, 36, 0], run(scopes, &[]));
+
+        // Operands are evaluated left to right, so a global read before a call doesn't see the call's assignment.
+        let order = "
+            var c = 0;
+            fn inc() { c = c + 1; return c; }
+            fn two(a, b) { print(a); print(b); return 0; }
+
+            fn main() {
+                print(c + inc());
+                c = 0;
+                two(c, inc());
+                print(inc() + c);
+            }
+        ";
+        assert_eq!(vec![1, 0, 1, 4], run(order, &[]));
+    }
+
+    #[test]
+    fn operators() {
+        let cases = [
+            "1 + 2 * 3", "(1 + 2) * 3", "10 - 4 - 3", "-7 / 2", "-7 % 2", "7 / -2", "17 % 5",
+            "3 < 4", "4 < 3", "3 <= 3", "3 > 3", "3 >= 3", "2 == 2", "2 != 2",
+            "!0", "!5", "-(2 - 5)", "0 && 1", "2 && 3", "0 || 0", "0 || 4", "1 + 1 == 2 && 3 < 2 || 1",
+        ];
+        let expected = [
+            7, 9, 3, -3, -1, -3, 2,
+            1, 0, 1, 0, 1, 1, 0,
+            1, 0, 3, 0, 1, 0, 1, 1,
+        ];
+
+        for (case, &expected) in cases.iter().zip(expected.iter()) {
+            assert_eq!(vec![expected], run(&format!("fn main() {{ print({}); }}", case), &[]), "{}", case);
+
+            // Read the numbers instead, so the operators work on values that aren't constants.
+            let (source, numbers) = with_reads(case);
+            assert_eq!(vec![expected], run(&format!("fn main() {{ var x = {}; print(x); }}", source), &numbers), "{}", source);
+        }
+    }
+
+    /// Replaces the numbers in an expression with read(), returning the numbers to read.
+    fn with_reads(expr: &str) -> (String, Vec<i64>) {
+        let mut source = String::new();
+        let mut numbers = Vec::new();
+        let mut digits = String::new();
+
+        for c in expr.chars().chain(Some(' ')) {
+            if c.is_ascii_digit() {
+                digits.push(c);
+                continue;
+            }
+
+            if !digits.is_empty() {
+                source += "read()";
+                numbers.push(digits.parse().unwrap());
+                digits.clear();
+            }
+            source.push(c);
+        }
+
+        (source, numbers)
+    }
+
+    #[test]
+    fn division_by_zero_halts() {
+        assert_eq!(vec![1], run("fn main() { print(1); print(5 / read()); print(2); }", &[0]));
+    }
+
+    #[test]
+    fn source_map() {
+        let source = "var a = 1;\nfn main() {\n    print(a);\n    a = a + 1;\n}\n";
+        let compiled = compile(source).unwrap();
+
+        let lines: Vec<usize> = compiled.source_map.entries().iter().map(|(_, position)| position.line).collect();
+        assert_eq!(vec![1, 2, 3, 4], lines);
+
+        let print = compiled.source_map.addresses(3)[0];
+        assert_eq!(4, compiled.program[print]);
+        assert_eq!(Some(Position { line: 3, column: 5 }), compiled.source_map.position(print + 1));
+        assert_eq!(None, compiled.source_map.position(compiled.program.len() - 1));
+
+        let filename = std::env::temp_dir().join(format!("intcode-compiler-{}.txt", std::process::id()));
+        compiled.save(&filename).unwrap();
+        let mut computer = Computer::load(&filename).unwrap();
+        std::fs::remove_file(&filename).unwrap();
+        assert_eq!(Some(1), computer.run());
+    }
+
+    #[test]
+    fn errors() {
+        let error = |source: &str| compile(source).unwrap_err().to_string();
+
+        assert_eq!("line 1, column 1: there's no main function", error("fn f() {}"));
+        assert_eq!("line 1, column 21: 'y' isn't defined", error("fn main() { var x = y; }"));
+        assert_eq!("line 2, column 13: f takes 1 arguments, but has 2", error("fn f(a) {}\nfn main() { f(1, 2); }"));
+        assert_eq!("line 1, column 13: function 'g' isn't defined", error("fn main() { g(); }"));
+        assert_eq!("line 1, column 22: expected ';', found '}'", error("fn main() { print(1) }"));
+        assert_eq!("line 1, column 13: unexpected character '@'", error("fn main() { @ }"));
+        assert_eq!("line 1, column 24: 'x' is already defined in this block", error("fn main() { var x = 1; var x = 2; }"));
+        assert_eq!("line 1, column 1: 'print' is built in", error("fn print(x) {}\nfn main() {}"));
+        assert_eq!("line 1, column 1: main can't take parameters", error("fn main(x) {}"));
+        assert_eq!("line 1, column 4: 'while' is a keyword", error("fn while() {}"));
+    }
+}
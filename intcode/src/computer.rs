@@ -0,0 +1,687 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, Write};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::error::IntcodeError;
+use crate::extension::{CustomOpcode, Outcome};
+use crate::isa::{self, Isa};
+use crate::memory::Memory;
+use crate::program::{self, ProgramError};
+
+/// Opcode modes is a number that contains an opcode and parameters.  The opcode is in the
+/// last two digits, other digits encode the parameter mode from right to left.
+/// For example, 1002 means opcode '02', with the first parameter in mode 0, the second in mode 1,
+/// and the third parameter in mode 0.  The third parameter mode is omitted in the instruction.
+#[derive(Debug)]
+struct OpcodeModes {
+    opcode: u32,
+    modes: Vec<u32>,
+}
+
+impl OpcodeModes {
+    /// Parses the given number into an OpcodeModes
+    /// 102 means multiply, where a is in immediate mode and b and out are in position mode.
+    pub fn parse(number: i64) -> OpcodeModes {
+        let mut parsing_number = number;
+
+        let opcode = (parsing_number % 100) as u32;
+        parsing_number /= 100;
+
+        let mut modes = Vec::<u32>::new();
+        while parsing_number > 0 {
+            modes.push((parsing_number % 10) as u32);
+            parsing_number /= 10;
+        }
+
+        OpcodeModes { opcode, modes }
+    }
+
+    /// Returns the mode for the parameter at the given index.
+    fn parameter_mode(&self, index: usize) -> u32 {
+        if index < self.modes.len() {
+            self.modes[index]
+        } else {
+            0
+        }
+    }
+
+    /// Returns a parameter for the instruction at pc.
+    /// Parameter is 0-indexed, so parameter(vec![3,3,104,50,99], 2, 0) returns Parameter::Immediate(50).
+    pub fn parameter(&self, computer: &Computer, parameter: usize) -> Result<Parameter, IntcodeError> {
+        // Parameters start at index=instruction + 1, but modes is 0-indexed.
+        let parameter_mode = self.parameter_mode(parameter);
+        let parameter_value = computer.memory.peek(computer.pc + parameter + 1);
+        isa::check_mode(computer.isa, parameter_mode)?;
+
+        match parameter_mode {
+            0 => Ok(Parameter::Position(parameter_value as usize)),
+            1 => Ok(Parameter::Immediate(parameter_value)),
+            2 => Ok(Parameter::Relative((parameter_value + computer.relative_base) as usize)),
+            mode => Err(IntcodeError::InvalidParameterMode { mode }),
+        }
+    }
+
+    /// Returns the index where an instruction should store a value.  Immediate mode is treated like
+    /// position mode, unless the computer is strict.
+    pub fn index_parameter(&self, computer: &Computer, parameter: usize) -> Result<usize, IntcodeError> {
+        let parameter_mode = self.parameter_mode(parameter);
+        let parameter_value = computer.memory.peek(computer.pc + parameter + 1);
+        isa::check_mode(computer.isa, parameter_mode)?;
+
+        match parameter_mode {
+            1 if computer.strict_writes => Err(IntcodeError::ImmediateWrite { instruction: computer.memory.peek(computer.pc) }),
+            0 | 1 => Ok(parameter_value as usize),
+            2 => Ok((parameter_value + computer.relative_base) as usize),
+            mode => Err(IntcodeError::InvalidParameterMode { mode }),
+        }
+    }
+}
+
+/// A parameter is an instruction input or output, and has a mode that determines how the value is treated.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Parameter {
+    /// 0 - parameters are interpreted as positions
+    Position(usize),
+    /// 1 - parameters are values
+    Immediate(i64),
+    /// 2 - parameters are pc + relative base
+    Relative(usize),
+}
+
+impl Parameter {
+    /// Returns the value of this parameter in the given program.
+    pub fn value(&self, memory: &Memory) -> i64 {
+        match *self {
+            Parameter::Position(index) => memory.get(index),
+            Parameter::Immediate(value) => value,
+            Parameter::Relative(index) => memory.get(index),
+        }
+    }
+
+    /// Returns the address this parameter refers to, or None if it's an immediate value.
+    pub fn addr(&self) -> Option<usize> {
+        match *self {
+            Parameter::Position(index) | Parameter::Relative(index) => Some(index),
+            Parameter::Immediate(_) => None,
+        }
+    }
+}
+
+/// Instruction type.  Instructions can have a variable number of program values, determined by their type.
+#[derive(Debug, Clone)]
+enum Instruction {
+    /// Adds two numbers and stores them in a third. 1, 2, 3, 4 adds the numbers at 2 and 3 and stores them in 4.
+    Add {
+        a: Parameter,
+        b: Parameter,
+        out: usize,
+    },
+    /// Multiplies two numbers and stores them in a third. 1, 2, 3, 4 multiplies the numbers 2 and 3 and stores them in 4.
+    Multiply {
+        a: Parameter,
+        b: Parameter,
+        out: usize,
+    },
+    /// Takes an input value and saves it at a position.  3, 4 stores an input at 4
+    Input { to: usize },
+    /// Outputs a value to a position.  4, 5 outputs the value at 5.
+    Output { from: Parameter },
+    /// If the first parameter is non-zero, sets the program counter to the value from the second parameter
+    JumpIfTrue { what: Parameter, to: Parameter },
+    /// If the first parameter is zero, sets the program counter to the value from the second parameter
+    JumpIfFalse { what: Parameter, to: Parameter },
+    /// If the first parameter is less than the second parameter, stores 1 in the position given by the third parameter.  Otherwise stores 0.
+    LessThan {
+        a: Parameter,
+        b: Parameter,
+        out: usize,
+    },
+    /// If the first parameter equals the second parameter, stores 1 in the position given by the third parameter.  Otherwise stores 0.
+    Equals {
+        a: Parameter,
+        b: Parameter,
+        out: usize,
+    },
+    /// Adjusts the relative base by the value of its only parameter. The relative base increases (or decreases, if the value is negative) by the value of the parameter.
+    RelativeBaseOffset {
+        by: Parameter
+    },
+    /// Done with execution.  The program should stop after executing this instruction.
+    Halt,
+    /// An opcode registered with Computer::register_opcode.
+    Custom {
+        opcode: Arc<CustomOpcode>,
+        parameters: Vec<Parameter>,
+    },
+}
+
+impl Instruction {
+    /// Parses the instruction at the given program counter.
+    fn parse(computer: &Computer) -> Result<Instruction, IntcodeError> {
+        let pc = computer.pc;
+
+        // Opcode: last two digits are the instruction, proceeding are the modes for the parameters.
+        let opcode_modes = OpcodeModes::parse(computer.memory.peek(pc));
+
+        // Built in opcodes from a later level of the instruction set aren't treated as custom opcodes.
+        if matches!(opcode_modes.opcode, 1..=9 | 99) && !computer.isa.has_opcode(opcode_modes.opcode) {
+            return Err(IntcodeError::UnsupportedInstruction { instruction: computer.memory.peek(pc), isa: computer.isa });
+        }
+
+        Ok(match opcode_modes.opcode {
+            // Add two numbers and stores them in a third.
+            1 => Instruction::Add {
+                a: opcode_modes.parameter(computer, 0)?,
+                b: opcode_modes.parameter(computer, 1)?,
+                out: opcode_modes.index_parameter(computer, 2)?,
+            },
+            // Multiply two numbers and stores them in a third.
+            2 => Instruction::Multiply {
+                a: opcode_modes.parameter(computer, 0)?,
+                b: opcode_modes.parameter(computer, 1)?,
+                out: opcode_modes.index_parameter(computer, 2)?,
+            },
+            // Take an input value and saves it at a position.
+            3 => Instruction::Input {
+                to: opcode_modes.index_parameter(computer, 0)?,
+            },
+            // Output a value to a position.
+            4 => Instruction::Output {
+                from: opcode_modes.parameter(computer, 0)?,
+            },
+            // If the first parameter is non-zero, sets the program counter to the value from the second parameter
+            5 => Instruction::JumpIfTrue {
+                what: opcode_modes.parameter(computer, 0)?,
+                to: opcode_modes.parameter(computer, 1)?,
+            },
+            // If the first parameter is zero, sets the program counter to the value from the second parameter
+            6 => Instruction::JumpIfFalse {
+                what: opcode_modes.parameter(computer, 0)?,
+                to: opcode_modes.parameter(computer, 1)?,
+            },
+            // If the first parameter is less than the second parameter, stores 1 in the position given by the third parameter.  Otherwise stores 0.
+            7 => Instruction::LessThan {
+                a: opcode_modes.parameter(computer, 0)?,
+                b: opcode_modes.parameter(computer, 1)?,
+                out: opcode_modes.index_parameter(computer, 2)?,
+            },
+            // If the first parameter equals the second parameter, stores 1 in the position given by the third parameter.  Otherwise stores 0.
+            8 => Instruction::Equals {
+                a: opcode_modes.parameter(computer, 0)?,
+                b: opcode_modes.parameter(computer, 1)?,
+                out: opcode_modes.index_parameter(computer, 2)?,
+            },
+            // Opcode 9 adjusts the relative base by the value of its only parameter.  The relative base increases (or decreases, if the value is negative) by the value of the parameter.
+            9 => Instruction::RelativeBaseOffset {
+                by: opcode_modes.parameter(computer, 0)?,
+            },
+            // Done with execution.  The program should stop after executing this instruction.
+            99 => Instruction::Halt,
+            // Opcodes that aren't built in can be registered by the user.
+            n => match computer.opcodes.get(&n) {
+                Some(opcode) => Instruction::Custom {
+                    opcode: opcode.clone(),
+                    parameters: (0..opcode.parameters)
+                        .map(|parameter| opcode_modes.parameter(computer, parameter))
+                        .collect::<Result<_, _>>()?,
+                },
+                None => return Err(IntcodeError::UnknownOpcode { opcode: computer.memory.peek(pc) }),
+            },
+        })
+    }
+
+    /// Runs this instruction, modifying the computer if applicable.  Returns the program state.
+    fn run(&self, computer: &mut Computer) -> Result<ProgramState, IntcodeError> {
+        let pc = computer.pc;
+
+        computer.pc = match self {
+            // Add two numbers and stores them in a third.
+            Instruction::Add { a, b, out } => {
+                computer.memory.set(*out, a.value(&computer.memory) + b.value(&computer.memory))?;
+                pc + 4
+            }
+            // Multiply two numbers and stores them in a third.
+            Instruction::Multiply { a, b, out } => {
+                computer.memory.set(*out, a.value(&computer.memory) * b.value(&computer.memory))?;
+                pc + 4
+            }
+            // Take an input value and saves it at a position.
+            Instruction::Input { to } => {
+                if let Some(input) = computer.input.pop_front() {
+                    if let Err(e) = computer.memory.set(*to, input) {
+                        // Leave the input for the next attempt at this instruction.
+                        computer.input.push_front(input);
+                        return Err(e);
+                    }
+
+                    pc + 2
+                } else {
+                    return Ok(ProgramState::WaitingForInput);
+                }
+            }
+            // Output a value to a position.
+            Instruction::Output { from } => {
+                computer.output.push_back(from.value(&computer.memory));
+                pc + 2
+            }
+            // If the first parameter is non-zero, sets the program counter to the value from the second parameter
+            Instruction::JumpIfTrue { what, to } => {
+                if what.value(&computer.memory) != 0 {
+                    to.value(&computer.memory) as usize
+                } else {
+                    pc + 3
+                }
+            }
+            // If the first parameter is zero, sets the program counter to the value from the second parameter
+            Instruction::JumpIfFalse { what, to } => {
+                if what.value(&computer.memory) == 0 {
+                    to.value(&computer.memory) as usize
+                } else {
+                    pc + 3
+                }
+            }
+            // If the first parameter is less than the second parameter, stores 1 in the position given by the third parameter.  Otherwise stores 0.
+            Instruction::LessThan { a, b, out } => {
+                computer.memory.set(*out, if a.value(&computer.memory) < b.value(&computer.memory) {
+                    1
+                } else {
+                    0
+                })?;
+                pc + 4
+            }
+            // If the first parameter equals the second parameter, stores 1 in the position given by the third parameter.  Otherwise stores 0.
+            Instruction::Equals { a, b, out } => {
+                computer.memory.set(*out, if a.value(&computer.memory) == b.value(&computer.memory) {
+                    1
+                } else {
+                    0
+                })?;
+                pc + 4
+            }
+            Instruction::RelativeBaseOffset { by } => {
+                computer.relative_base += by.value(&computer.memory);
+                computer.pc + 2
+            }
+            // Done with execution.  The program should stop after executing this instruction.
+            Instruction::Halt => {
+                return Ok(ProgramState::Done);
+            }
+            // Run the handler for a registered opcode.
+            Instruction::Custom { opcode, parameters } => {
+                match (opcode.handler)(computer, parameters)? {
+                    Outcome::Next => pc + parameters.len() + 1,
+                    Outcome::JumpTo(addr) => addr,
+                    Outcome::WaitForInput => return Ok(ProgramState::WaitingForInput),
+                    Outcome::Halt => return Ok(ProgramState::Done),
+                }
+            }
+        };
+
+        Ok(ProgramState::Runnable)
+    }
+}
+
+/// State of the program at a specific program counter.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ProgramState {
+    Done,
+    Runnable,
+    WaitingForInput,
+}
+
+/// Computer input and output, for programs that talk to a device instead of queued input.
+pub trait ProgramIO {
+    fn input(&mut self) -> i64;
+    fn output(&mut self, value: i64);
+}
+
+/// Intcode computer.
+#[derive(Debug, Clone)]
+pub struct Computer {
+    pc: usize,
+    relative_base: i64,
+    state: ProgramState,
+    input: VecDeque<i64>,
+    pub output: VecDeque<i64>,
+    pub memory: Memory,
+    opcodes: HashMap<u32, Arc<CustomOpcode>>,
+    isa: Isa,
+    strict_writes: bool,
+}
+
+impl Computer {
+    /// Loads the intcode program in the given file into a computer.  Fails if the file can't be read,
+    /// or if any value in it isn't a number.
+    pub fn load<P: AsRef<Path>>(filename: P) -> Result<Computer, ProgramError> {
+        Ok(Computer::new(program::read_program(filename)?))
+    }
+
+    /// Constructs a new Computer that will run the given program.
+    pub fn new(program: Vec<i64>) -> Computer {
+        Computer {
+            pc: 0,
+            relative_base: 0,
+            state: ProgramState::Runnable,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            memory: Memory::for_program(&program),
+            opcodes: HashMap::new(),
+            isa: Isa::Full,
+            strict_writes: false,
+        }
+    }
+
+    /// Limits this computer to the given level of the instruction set.  Computers run the full
+    /// instruction set by default.
+    pub fn with_isa(mut self, isa: Isa) -> Self {
+        self.isa = isa;
+        self
+    }
+
+    /// Makes this computer reject instructions that write to an immediate mode parameter, instead of
+    /// writing to the parameter's value as an address.
+    pub fn with_strict_writes(mut self) -> Self {
+        self.strict_writes = true;
+        self
+    }
+
+    /// Returns the level of the instruction set this computer runs.
+    pub fn isa(&self) -> Isa {
+        self.isa
+    }
+
+    /// Adds an opcode to this computer's instruction set.  The instruction takes the given number of
+    /// parameters, and runs the handler when executed.  Clones of this computer share the opcode.
+    /// Panics if the opcode is one of the built in opcodes.
+    pub fn register_opcode<F>(&mut self, opcode: u32, name: &str, parameters: usize, handler: F)
+        where F: Fn(&mut Computer, &[Parameter]) -> Result<Outcome, IntcodeError> + Send + Sync + 'static {
+        assert!(!matches!(opcode, 1..=9 | 99), "Opcode {} is built in", opcode);
+
+        self.opcodes.insert(opcode, Arc::new(CustomOpcode {
+            name: name.to_string(),
+            parameters,
+            handler: Arc::new(handler),
+        }));
+    }
+
+    /// Returns the address of the next instruction the program will run.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Returns the relative base that relative mode parameters are offset from.
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    /// Moves the program counter to the given address, so the next instruction runs from there.
+    /// Makes the computer runnable again if it had halted or was waiting for input.
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+        self.state = ProgramState::Runnable;
+    }
+
+    /// Sets the relative base that relative mode parameters are offset from.
+    pub fn set_relative_base(&mut self, relative_base: i64) {
+        self.relative_base = relative_base;
+    }
+
+    /// Uses the given value as the next input to this computer.  Inputs will be used
+    /// in the order they were provided if input is called multiple times.
+    pub fn input(&mut self, value: i64) {
+        self.input.push_back(value);
+
+        if self.state == ProgramState::WaitingForInput {
+            self.state = ProgramState::Runnable;
+        }
+    }
+
+    /// Provides the given ascii string as input for the computer.
+    pub fn text_input(&mut self, value: &str) {
+        value.chars().for_each(|c| self.input(c as i64));
+    }
+
+    /// Returns whether this computer has input that the program hasn't read yet.
+    pub fn has_input(&self) -> bool {
+        !self.input.is_empty()
+    }
+
+    /// Returns the number of input values that the program hasn't read yet.
+    pub fn input_len(&self) -> usize {
+        self.input.len()
+    }
+
+    /// Returns the input values that the program hasn't read yet, next value first.
+    pub fn input_queue(&self) -> &VecDeque<i64> {
+        &self.input
+    }
+
+    /// Returns the state of the program.
+    pub fn state(&self) -> &ProgramState {
+        &self.state
+    }
+
+    /// Passes each value in the output to the given visitor.
+    pub fn visit_output(&self, visit: fn(&i64)) {
+        self.output.iter().for_each(visit)
+    }
+
+    /// Returns the last value this computer output, or empty if it hasn't output any values.
+    pub fn last_output(&self) -> Option<i64> {
+        self.output.back().cloned()
+    }
+
+    /// Returns all of the output this computer has produced, consuming it in the process.
+    pub fn dump_output(&mut self) -> Vec<i64> {
+        self.output.drain(..).collect()
+    }
+
+    /// Prints any new ascii output from the computer that hasn't been printed yet.
+    /// Consumes ASCII output, so the output methods won't see the output again.
+    pub fn print_output(&mut self) {
+        while let Some(value) = self.output.pop_front() {
+            if value < 256 {
+                print!("{}", value as u8 as char);
+            } else {
+                self.output.push_front(value);
+                return;
+            }
+        }
+    }
+
+    /// Returns whether this computer can run - if it isn't done or waiting for input.
+    pub fn is_runnable(&self) -> bool {
+        self.state == ProgramState::Runnable
+    }
+
+    /// Runs the program in this computer until it either halts or blocks waiting for input.
+    /// Returns the last value the program output.  Panics if the program can't be executed.
+    pub fn run(&mut self) -> Option<i64> {
+        self.try_run().unwrap_or_else(|e| panic!("{} at pc {}", e, self.pc))
+    }
+
+    /// Runs the program in this computer until it either halts or blocks waiting for input, returning
+    /// the last value the program output.  Stops at the instruction that caused an error if the
+    /// program can't be executed.
+    pub fn try_run(&mut self) -> Result<Option<i64>, IntcodeError> {
+        while self.is_runnable() {
+            self.step()?;
+        }
+
+        Ok(self.last_output())
+    }
+
+    /// Runs the program until it halts, asking the given io for input whenever the program
+    /// needs it and passing it every value the program outputs.
+    pub fn run_io<IO: ProgramIO>(&mut self, io: &mut IO) {
+        while self.state != ProgramState::Done {
+            self.run();
+            self.dump_output().into_iter().for_each(|value| io.output(value));
+
+            if self.state == ProgramState::WaitingForInput {
+                self.input(io.input());
+            }
+        }
+    }
+
+    /// Returns an iterator over the values the program outputs, starting with any output the computer
+    /// already has.  The program only runs as far as it needs to for each value.  When the program
+    /// reads input and there isn't any queued, the next value comes from the given input - use
+    /// iter::repeat_with to call a closure for each input.  The iterator ends when the program halts,
+    /// or when it needs input and the input has run out, and ends after returning an error if the
+    /// program can't be executed.
+    pub fn outputs<I: IntoIterator<Item = i64>>(&mut self, input: I) -> Outputs<'_, I::IntoIter> {
+        Outputs { computer: self, input: input.into_iter(), failed: false }
+    }
+
+    /// Runs the ASCII computer in interactive mode.
+    pub fn run_interactive(&mut self) -> Option<i64> {
+        while self.state != ProgramState::Done {
+            self.run();
+            self.print_output();
+
+            if self.state == ProgramState::WaitingForInput {
+                print!("> ");
+                io::stdout().flush().unwrap();
+
+                let stdin = io::stdin();
+                let line = stdin.lock().lines().next().unwrap().unwrap();
+
+                self.text_input(&line);
+                self.text_input("\n");
+            }
+        }
+
+        self.print_output();
+        self.last_output()
+    }
+
+    /// Runs the ASCII computer, providing the given input when prompted.
+    pub fn run_input(&mut self, input: Vec<&str>) -> Option<i64> {
+        let mut line_num = 0;
+        while self.state != ProgramState::Done {
+            self.run();
+            self.print_output();
+
+            if self.state == ProgramState::WaitingForInput {
+                let line = input[line_num];
+                line_num += 1;
+                println!("> {}", line);
+                self.text_input(line);
+                self.text_input("\n");
+            }
+        }
+
+        self.print_output();
+        self.last_output()
+    }
+
+    /// Runs the next instruction in the program, if possible, updating the program state.
+    pub fn step(&mut self) -> Result<(), IntcodeError> {
+        self.state = Instruction::parse(self)?.run(self)?;
+
+        Ok(())
+    }
+
+    /// Resets this program to its original state.
+    pub fn reset(&mut self) {
+        self.pc = 0;
+        self.relative_base = 0;
+        self.state = ProgramState::Runnable;
+        self.input = VecDeque::new();
+        self.output = VecDeque::new();
+        self.memory.reset();
+    }
+}
+
+/// Iterator over a program's output, returned by Computer::outputs.
+pub struct Outputs<'a, I> {
+    computer: &'a mut Computer,
+    input: I,
+    failed: bool,
+}
+
+impl<I: Iterator<Item = i64>> Iterator for Outputs<'_, I> {
+    type Item = Result<i64, IntcodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        loop {
+            if let Some(value) = self.computer.output.pop_front() {
+                return Some(Ok(value));
+            }
+
+            match self.computer.state {
+                ProgramState::Done => return None,
+                ProgramState::WaitingForInput => self.computer.input(self.input.next()?),
+                ProgramState::Runnable => {
+                    if let Err(e) = self.computer.step() {
+                        self.failed = true;
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+
+    use super::*;
+
+    /// Reads values and outputs each one doubled until it reads 0.
+    fn doubler() -> Computer {
+        Computer::new(vec![3, 20, 1006, 20, 14, 102, 2, 20, 21, 4, 21, 1105, 1, 0, 99])
+    }
+
+    #[test]
+    fn outputs() {
+        let mut computer = doubler();
+        computer.input(1);
+
+        // Queued input is read first, then the program pulls from the iterator as it needs it.
+        let outputs: Result<Vec<i64>, IntcodeError> = computer.outputs(vec![2, 3, 0]).collect();
+        assert_eq!(Ok(vec![2, 4, 6]), outputs);
+        assert_eq!(ProgramState::Done, *computer.state());
+
+        // Running out of input leaves the computer waiting for more.
+        computer.reset();
+        assert_eq!(vec![Ok(10)], computer.outputs(iter::once(5)).collect::<Vec<_>>());
+        assert_eq!(ProgramState::WaitingForInput, *computer.state());
+        assert_eq!(Some(Ok(14)), computer.outputs(iter::once(7)).next());
+    }
+
+    #[test]
+    fn outputs_run_lazily() {
+        let mut computer = doubler();
+        let mut reads = 0;
+        let counter = iter::repeat_with(|| {
+            reads += 1;
+            reads
+        });
+
+        let first: Vec<i64> = computer.outputs(counter).map(Result::unwrap).take_while(|&value| value < 7).collect();
+        assert_eq!(vec![2, 4, 6], first);
+        // Producing 8 to stop take_while needed a fourth read, but nothing more.
+        assert_eq!(4, reads);
+        assert!(computer.output.is_empty());
+    }
+
+    #[test]
+    fn outputs_stop_at_errors() {
+        let mut computer = Computer::new(vec![104, 1, 42]);
+        let mut outputs = computer.outputs(iter::empty());
+
+        assert_eq!(Some(Ok(1)), outputs.next());
+        assert!(matches!(outputs.next(), Some(Err(_))));
+        assert_eq!(None, outputs.next());
+        assert_eq!(2, computer.pc());
+    }
+}
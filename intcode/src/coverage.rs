@@ -0,0 +1,330 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::error::IntcodeError;
+use crate::transpile::{find_blocks, Block, Decoded};
+use crate::{Computer, ProgramState};
+
+// Coverage records which instructions a program ran, and which way each conditional jump went.
+// Running a computer through a Coverage instead of calling run records every instruction, and
+// coverage from several runs can be merged.  The report splits the program into basic blocks and
+// renders a listing in the style of gcov: each instruction with its hit count, '#####' for
+// instructions that never ran, and '-' for data.
+//
+//   let mut coverage = Coverage::new();
+//   computer.input(5);
+//   coverage.run(&mut computer)?;
+//   print!("{}", coverage.report(&program));
+
+/// Number of times a conditional jump jumped, and number of times it fell through.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Branch {
+    pub taken: usize,
+    pub not_taken: usize,
+}
+
+impl Branch {
+    /// Returns whether the jump only ever went one way.
+    pub fn is_partial(&self) -> bool {
+        self.taken == 0 || self.not_taken == 0
+    }
+}
+
+/// Hit counts for each executed address, and the directions taken by each conditional jump.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Coverage {
+    hits: BTreeMap<usize, usize>,
+    branches: BTreeMap<usize, Branch>,
+    /// Addresses jumps went to, including jumps through position and relative mode parameters.
+    targets: BTreeSet<usize>,
+}
+
+impl Coverage {
+    /// Constructs an empty coverage.
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    /// Runs the next instruction in the computer, recording it if it ran.  An input instruction
+    /// that's waiting for input hasn't run yet.
+    pub fn step(&mut self, computer: &mut Computer) -> Result<(), IntcodeError> {
+        let pc = computer.pc();
+        let instruction = computer.memory.peek(pc);
+
+        computer.step()?;
+
+        if *computer.state() == ProgramState::WaitingForInput && computer.pc() == pc {
+            return Ok(());
+        }
+
+        *self.hits.entry(pc).or_insert(0) += 1;
+
+        if !matches!(instruction % 100, 5 | 6) {
+            return Ok(());
+        }
+
+        let jumped = computer.pc() != pc + 3;
+        if jumped {
+            self.targets.insert(computer.pc());
+        }
+
+        // Jumps with an immediate condition always go the same way, so they aren't branches.
+        if instruction / 100 % 10 != 1 {
+            let branch = self.branches.entry(pc).or_default();
+            if jumped {
+                branch.taken += 1;
+            } else {
+                branch.not_taken += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the program in the computer until it either halts or blocks waiting for input, recording
+    /// every instruction.  Stops at the instruction that caused an error if the program can't be executed.
+    pub fn run(&mut self, computer: &mut Computer) -> Result<(), IntcodeError> {
+        while computer.is_runnable() {
+            self.step(computer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds the coverage from another run to this one.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (&addr, &hits) in &other.hits {
+            *self.hits.entry(addr).or_insert(0) += hits;
+        }
+
+        for (&addr, branch) in &other.branches {
+            let merged = self.branches.entry(addr).or_default();
+            merged.taken += branch.taken;
+            merged.not_taken += branch.not_taken;
+        }
+
+        self.targets.extend(&other.targets);
+    }
+
+    /// Returns the number of times the instruction at the given address ran.
+    pub fn hits(&self, addr: usize) -> usize {
+        self.hits.get(&addr).copied().unwrap_or(0)
+    }
+
+    /// Returns the directions taken by the conditional jump at the given address, or None if it never ran.
+    pub fn branch(&self, addr: usize) -> Option<Branch> {
+        self.branches.get(&addr).copied()
+    }
+
+    /// Returns a report on the coverage of the given program.  Code is found by following the
+    /// program from address 0 and from every address a jump went to, so the program should be the
+    /// one the computer started with.
+    pub fn report(&self, program: &[i64]) -> Report {
+        let entries: Vec<usize> = Some(0).into_iter().chain(self.targets.iter().copied()).collect();
+        let blocks = find_blocks(program, &entries);
+
+        let instructions: Vec<&Decoded> = blocks.iter().flat_map(|block| &block.instructions).collect();
+        let branches: Vec<usize> = instructions.iter()
+            .filter(|instruction| matches!(instruction.opcode, 5 | 6) && instruction.operands[0].mode != 1)
+            .map(|instruction| instruction.addr)
+            .collect();
+
+        Report {
+            instructions: instructions.len(),
+            covered: instructions.iter().filter(|instruction| self.hits(instruction.addr) > 0).count(),
+            blocks: blocks.len(),
+            uncovered: blocks.iter().filter(|block| self.hits(block.start()) == 0).map(|block| block.start()..block.end()).collect(),
+            branches: branches.len(),
+            untaken: branches.iter()
+                .filter_map(|&addr| self.branch(addr).map(|branch| (addr, branch)))
+                .filter(|(_, branch)| branch.is_partial())
+                .collect(),
+            listing: self.listing(program, &blocks),
+        }
+    }
+
+    /// Returns an annotated listing of the program, with a blank line before each block.
+    fn listing(&self, program: &[i64], blocks: &[Block]) -> String {
+        let code: HashMap<usize, &Decoded> = blocks.iter()
+            .flat_map(|block| &block.instructions)
+            .map(|instruction| (instruction.addr, instruction))
+            .collect();
+
+        let mut out = String::new();
+        let mut addr = 0;
+
+        while addr < program.len() {
+            if blocks.iter().any(|block| block.start() == addr) {
+                writeln!(out).unwrap();
+            }
+
+            if let Some(instruction) = code.get(&addr) {
+                let hits = match self.hits(addr) {
+                    0 => "#####".to_string(),
+                    hits => hits.to_string(),
+                };
+
+                let line = format!("{:>9}  {:>5}  {}", hits, addr, instruction);
+                match self.branch(addr) {
+                    Some(branch) => writeln!(out, "{:<48}{}", line, format_branch(&branch)).unwrap(),
+                    None => writeln!(out, "{}", line).unwrap(),
+                }
+
+                addr = instruction.next();
+            } else {
+                // Data runs until the next instruction, with up to 8 values on a line.
+                let start = addr;
+                while addr < program.len() && addr - start < 8 && !code.contains_key(&addr) {
+                    addr += 1;
+                }
+
+                let values: Vec<String> = program[start..addr].iter().map(i64::to_string).collect();
+                writeln!(out, "{:>9}  {:>5}  data {}", "-", start, values.join(", ")).unwrap();
+            }
+        }
+
+        out
+    }
+}
+
+/// Returns the annotation for a conditional jump, pointing out a direction it never went.
+fn format_branch(branch: &Branch) -> String {
+    let counts = format!("jumped {}, fell through {}", branch.taken, branch.not_taken);
+
+    match (branch.taken, branch.not_taken) {
+        (0, _) => format!("{}  <- never jumped", counts),
+        (_, 0) => format!("{}  <- never fell through", counts),
+        _ => counts,
+    }
+}
+
+/// Coverage of a program: how many of its instructions, blocks and branches ran, and an annotated listing.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Report {
+    /// Number of instructions in the program, and the number that ran.
+    pub instructions: usize,
+    pub covered: usize,
+    /// Number of basic blocks in the program, and the address range of each block that never ran.
+    pub blocks: usize,
+    pub uncovered: Vec<Range<usize>>,
+    /// Number of conditional jumps in the program, and the jumps that ran but only ever went one way.
+    pub branches: usize,
+    pub untaken: Vec<(usize, Branch)>,
+    listing: String,
+}
+
+impl Report {
+    /// Returns the listing of the program, with hit counts for each instruction.
+    pub fn listing(&self) -> &str {
+        &self.listing
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = if self.instructions == 0 {
+            100.0
+        } else {
+            self.covered as f64 * 100.0 / self.instructions as f64
+        };
+
+        writeln!(f, "Instructions: {} of {} ran ({:.1}%)", self.covered, self.instructions, percent)?;
+        writeln!(f, "Blocks: {} of {} never ran", self.uncovered.len(), self.blocks)?;
+        for block in &self.uncovered {
+            writeln!(f, "  {}..{}", block.start, block.end)?;
+        }
+
+        writeln!(f, "Branches: {} of {} only went one way", self.untaken.len(), self.branches)?;
+        for (addr, branch) in &self.untaken {
+            writeln!(f, "  {}: {}", addr, format_branch(branch))?;
+        }
+
+        write!(f, "{}", self.listing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads n, then outputs n, n - 1, ... 1.
+    fn countdown() -> Vec<i64> {
+        vec![
+            3, 15,              // 0: in [15]
+            1006, 15, 14,       // 2: jz [15], 14
+            4, 15,              // 5: out [15]
+            1001, 15, -1, 15,   // 7: add [15], -1, [15]
+            1105, 1, 2,         // 11: jnz 1, 2
+            99,                 // 14: halt
+            0,                  // 15: n
+        ]
+    }
+
+    fn run(n: i64) -> Coverage {
+        let mut computer = Computer::new(countdown());
+        let mut coverage = Coverage::new();
+
+        // The input instruction doesn't run until there's input.
+        coverage.run(&mut computer).unwrap();
+        assert_eq!(0, coverage.hits(0));
+
+        computer.input(n);
+        coverage.run(&mut computer).unwrap();
+        assert_eq!(ProgramState::Done, *computer.state());
+
+        coverage
+    }
+
+    #[test]
+    fn hits_and_branches() {
+        let coverage = run(3);
+
+        assert_eq!(1, coverage.hits(0));
+        assert_eq!(4, coverage.hits(2));
+        assert_eq!(3, coverage.hits(5));
+        assert_eq!(1, coverage.hits(14));
+        assert_eq!(0, coverage.hits(15));
+
+        assert_eq!(Some(Branch { taken: 1, not_taken: 3 }), coverage.branch(2));
+        // The jump back to the loop always jumps, so it isn't a branch.
+        assert_eq!(None, coverage.branch(11));
+    }
+
+    #[test]
+    fn report() {
+        let mut coverage = run(0);
+        let report = coverage.report(&countdown());
+
+        assert_eq!((3, 6), (report.covered, report.instructions));
+        assert_eq!((1, 4), (report.uncovered.len(), report.blocks));
+        assert_eq!(5..14, report.uncovered[0]);
+        assert_eq!((vec![(2, Branch { taken: 1, not_taken: 0 })], 1), (report.untaken.clone(), report.branches));
+
+        let lines: Vec<&str> = report.listing().lines().collect();
+        assert_eq!(vec![
+            "",
+            "        1      0  in [15]",
+            "",
+            "        1      2  jz [15], 14                   jumped 1, fell through 0  <- never fell through",
+            "",
+            "    #####      5  out [15]",
+            "    #####      7  add [15], -1, [15]",
+            "    #####     11  jnz 1, 2",
+            "",
+            "        1     14  halt",
+            "        -     15  data 0",
+        ], lines);
+
+        // A second run covers the rest of the program.
+        coverage.merge(&run(2));
+        let report = coverage.report(&countdown());
+
+        assert_eq!((6, 6), (report.covered, report.instructions));
+        assert!(report.uncovered.is_empty());
+        assert!(report.untaken.is_empty());
+        assert_eq!(Some(Branch { taken: 2, not_taken: 2 }), coverage.branch(2));
+        assert!(report.to_string().starts_with("Instructions: 6 of 6 ran (100.0%)\nBlocks: 0 of 4 never ran\n"));
+    }
+}
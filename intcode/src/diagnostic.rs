@@ -0,0 +1,251 @@
+use std::fmt;
+
+use crate::error::IntcodeError;
+use crate::Computer;
+
+// Day 5's TEST program and day 9's BOOST program check the interpreter running them.  TEST
+// outputs 0 for each check that passes, then a diagnostic code.  A check that fails outputs how far
+// off its result was instead.  BOOST outputs a single keycode if every opcode works, or a list of
+// the opcodes that are broken.  The harness runs a self-test on any interpreter and turns the
+// outputs into a report, which makes the puzzle inputs a regression gate for interpreter changes:
+//
+//   let report = diagnostic::run(&mut Computer::load("../day5/input.txt")?, Suite::Test, 5);
+//   assert!(report.passed(), "{}", report);
+//
+// The gate covers intcode's interpreter, which implements Interpreter.  Transpiled computers can't
+// run an instruction at a time to report where each output came from, so transpile's tests gate
+// them by running TEST and BOOST and matching the interpreter's outputs.  The standalone computers
+// in day9 and day21 don't depend on this crate and aren't gated.
+
+/// An intcode interpreter that can run a self-test.
+pub trait Interpreter {
+    /// Adds a value to the end of the input queue.
+    fn input(&mut self, value: i64);
+
+    /// Runs until the program outputs a value, returning the value and the pc of the instruction
+    /// that output it.  Returns None once the program halts or waits for input.
+    fn next_output(&mut self) -> Result<Option<Output>, Fault>;
+}
+
+/// A value output by the program, and the pc of the instruction that output it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Output {
+    pub pc: usize,
+    pub value: i64,
+}
+
+/// An error that stopped the interpreter, and the pc of the instruction that caused it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Fault {
+    pub pc: usize,
+    pub message: String,
+}
+
+impl Interpreter for Computer {
+    fn input(&mut self, value: i64) {
+        Computer::input(self, value);
+    }
+
+    fn next_output(&mut self) -> Result<Option<Output>, Fault> {
+        while self.is_runnable() {
+            let pc = self.pc();
+            self.step().map_err(|e: IntcodeError| Fault { pc, message: e.to_string() })?;
+
+            if let Some(value) = self.output.pop_front() {
+                return Ok(Some(Output { pc, value }));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Which self-test program is running, which determines how its outputs are read.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Suite {
+    /// Day 5's thermal environment supervision terminal.  Every output but the last is a check.
+    Test,
+    /// Day 9's basic operation of system keycode program.  More than one output lists broken opcodes.
+    Boost,
+}
+
+/// Something the self-test found wrong with the interpreter.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Failure {
+    /// TEST check number `check` (from 0) was off by `value`.
+    Check { check: usize, pc: usize, value: i64 },
+    /// BOOST reported the opcode as broken.
+    Opcode { opcode: i64, pc: usize },
+    /// The interpreter stopped with an error.
+    Fault(Fault),
+    /// The program halted without a diagnostic code.
+    NoOutput,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Check { check, pc, value } => write!(f, "check {} failed at pc {}: off by {}", check, pc, value),
+            Failure::Opcode { opcode, pc } => write!(f, "opcode {} reported broken at pc {}", opcode, pc),
+            Failure::Fault(Fault { pc, message }) => write!(f, "{} at pc {}", message, pc),
+            Failure::NoOutput => write!(f, "no diagnostic code"),
+        }
+    }
+}
+
+/// Result of running a self-test: the diagnostic code if it finished, and any failures.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Report {
+    pub suite: Suite,
+    pub system_id: i64,
+    pub outputs: Vec<Output>,
+    pub code: Option<i64>,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    /// Returns true if the self-test produced a diagnostic code without any failures.
+    pub fn passed(&self) -> bool {
+        self.code.is_some() && self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} system {}: ", self.suite, self.system_id)?;
+
+        match self.code {
+            Some(code) if self.failures.is_empty() => return write!(f, "passed with code {}", code),
+            Some(code) => write!(f, "code {}, ", code)?,
+            None => {},
+        }
+
+        write!(f, "{} failed", self.failures.len())?;
+        self.failures.iter().try_for_each(|failure| write!(f, "\n  {}", failure))
+    }
+}
+
+/// Runs the self-test in the interpreter with the given system ID as input, and reads its outputs.
+pub fn run<I: Interpreter>(interpreter: &mut I, suite: Suite, system_id: i64) -> Report {
+    interpreter.input(system_id);
+
+    let mut outputs = Vec::new();
+    let fault = loop {
+        match interpreter.next_output() {
+            Ok(Some(output)) => outputs.push(output),
+            Ok(None) => break None,
+            Err(fault) => break Some(fault),
+        }
+    };
+
+    let mut failures = Vec::new();
+    let code = match suite {
+        Suite::Test => {
+            // A faulting program didn't get to output its code, so every output was a check.
+            let (last, checks) = match (outputs.split_last(), &fault) {
+                (Some((last, checks)), None) => (Some(last.value), checks),
+                _ => (None, &outputs[..]),
+            };
+
+            failures.extend(checks.iter().enumerate()
+                .filter(|(_, output)| output.value != 0)
+                .map(|(check, output)| Failure::Check { check, pc: output.pc, value: output.value }));
+
+            last
+        },
+        Suite::Boost => match outputs.as_slice() {
+            [keycode] => Some(keycode.value),
+            broken => {
+                failures.extend(broken.iter().map(|output| Failure::Opcode { opcode: output.value, pc: output.pc }));
+                None
+            },
+        },
+    };
+
+    // A faulting program didn't get to output its code.
+    let code = match fault {
+        Some(fault) => {
+            failures.push(Failure::Fault(fault));
+            None
+        },
+        None => code,
+    };
+
+    if code.is_none() && failures.is_empty() {
+        failures.push(Failure::NoOutput);
+    }
+
+    Report { suite, system_id, outputs, code, failures }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suite() {
+        let passing = run(&mut Computer::new(vec![104, 0, 104, 0, 104, 777, 99]), Suite::Test, 1);
+        assert!(passing.passed());
+        assert_eq!(Some(777), passing.code);
+
+        let failing = run(&mut Computer::new(vec![104, 0, 104, 3, 104, 777, 99]), Suite::Test, 1);
+        assert!(!failing.passed());
+        assert_eq!(vec![Failure::Check { check: 1, pc: 2, value: 3 }], failing.failures);
+        assert_eq!("Test system 1: code 777, 1 failed\n  check 1 failed at pc 2: off by 3", failing.to_string());
+    }
+
+    #[test]
+    fn boost_suite() {
+        let passing = run(&mut Computer::new(vec![3, 7, 4, 7, 99, 0, 0, 0]), Suite::Boost, 2);
+        assert_eq!("Boost system 2: passed with code 2", passing.to_string());
+
+        let failing = run(&mut Computer::new(vec![104, 203, 104, 9, 99]), Suite::Boost, 1);
+        assert_eq!(vec![Failure::Opcode { opcode: 203, pc: 0 }, Failure::Opcode { opcode: 9, pc: 2 }], failing.failures);
+
+        let silent = run(&mut Computer::new(vec![3, 0, 99]), Suite::Boost, 1);
+        assert_eq!(vec![Failure::NoOutput], silent.failures);
+    }
+
+    #[test]
+    fn fault() {
+        let report = run(&mut Computer::new(vec![104, 0, 104, 0, 42]), Suite::Test, 1);
+
+        assert_eq!(None, report.code);
+        assert_eq!(vec![Failure::Fault(Fault { pc: 4, message: "Unknown opcode 42".to_string() })], report.failures);
+
+        // The check output just before the fault is still checked.
+        let report = run(&mut Computer::new(vec![104, 0, 104, 5, 42]), Suite::Test, 1);
+        assert_eq!(vec![
+            Failure::Check { check: 1, pc: 2, value: 5 },
+            Failure::Fault(Fault { pc: 4, message: "Unknown opcode 42".to_string() }),
+        ], report.failures);
+    }
+
+    /// Regression gate: the interpreter has to pass the puzzle inputs' self-tests in every mode.
+    #[test]
+    fn self_tests() {
+        let test = Computer::load("../day5/input.txt").unwrap();
+        let boost = Computer::load("../day9/input.txt").unwrap();
+
+        for (mut computer, suite, system_id, code) in [
+            (test.clone(), Suite::Test, 1, 13346482),
+            (test, Suite::Test, 5, 12111395),
+            (boost.clone(), Suite::Boost, 1, 3497884671),
+            (boost, Suite::Boost, 2, 46470),
+        ] {
+            let report = run(&mut computer, suite, system_id);
+            assert!(report.passed(), "{}", report);
+            assert_eq!(Some(code), report.code);
+        }
+    }
+
+    #[test]
+    fn broken_interpreter() {
+        // Rejecting writes to the program makes TEST fault at its first instruction that writes.
+        let mut computer = Computer::load("../day5/input.txt").unwrap();
+        computer.memory.protect(0..1000);
+
+        let report = run(&mut computer, Suite::Test, 1);
+        assert_eq!(vec![Failure::Fault(Fault { pc: 0, message: "Write of 1 to address 225 was rejected".to_string() })], report.failures);
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::frame::{FrameError, Frames};
+use crate::ProgramIO;
+
+// A Display is a screen for programs that draw tiles by outputting (x, y, tile) triples.  It keeps
+// the tiles on a sparse canvas, so it works without a terminal, and renders them as text or as a
+// PPM image using a palette.  Special coordinates hold values instead of tiles, like the arcade's
+// score at (-1, 0).
+//
+//   let palette = Palette::new().tile(0, ' ', [0, 0, 0]).tile(1, '#', [255, 255, 255]);
+//   let mut display = Display::new(palette).with_special(-1, 0);
+//   computer.run_io(&mut display);
+//   print!("{}", display);
+//   display.save_ppm("screen.ppm", 4)?;
+//
+// Programs can draw anywhere, so rendering fails instead of allocating a frame or image with more
+// than MAX_PIXELS tiles or pixels.
+
+/// Most tiles in a frame or text rendering, and most pixels in an image, so a program that draws
+/// far apart tiles can't exhaust memory.
+pub const MAX_PIXELS: usize = 1 << 24;
+
+/// How a tile is drawn: a character for text, and an RGB color for images.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Paint {
+    pub char: char,
+    pub color: [u8; 3],
+}
+
+/// The paint used for each tile id.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Palette {
+    tiles: BTreeMap<i64, Paint>,
+    unknown: Paint,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette { tiles: BTreeMap::new(), unknown: Paint { char: '?', color: [255, 0, 255] } }
+    }
+}
+
+impl Palette {
+    /// Constructs a palette that draws every tile as a magenta '?'.
+    pub fn new() -> Palette {
+        Palette::default()
+    }
+
+    /// Draws the given tile id with a character and color.
+    pub fn tile(mut self, id: i64, char: char, color: [u8; 3]) -> Self {
+        self.tiles.insert(id, Paint { char, color });
+        self
+    }
+
+    /// Returns the paint for the given tile id.
+    pub fn paint(&self, id: i64) -> Paint {
+        self.tiles.get(&id).copied().unwrap_or(self.unknown)
+    }
+}
+
+/// Smallest and largest coordinates that have been drawn, inclusive.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Bounds {
+    pub min_x: i64,
+    pub max_x: i64,
+    pub min_y: i64,
+    pub max_y: i64,
+}
+
+impl Bounds {
+    /// Returns the number of columns inside the bounds, or None if it doesn't fit in a usize.
+    pub fn width(&self) -> Option<usize> {
+        usize::try_from(i128::from(self.max_x) - i128::from(self.min_x) + 1).ok()
+    }
+
+    /// Returns the number of rows inside the bounds, or None if it doesn't fit in a usize.
+    pub fn height(&self) -> Option<usize> {
+        usize::try_from(i128::from(self.max_y) - i128::from(self.min_y) + 1).ok()
+    }
+
+    /// Returns the number of pixels in an image of the bounds with each tile drawn as a square of
+    /// scale pixels, or None if it doesn't fit in a usize.
+    fn pixels(&self, scale: usize) -> Option<usize> {
+        self.width()?.checked_mul(self.height()?)?.checked_mul(scale)?.checked_mul(scale)
+    }
+}
+
+/// The drawn tiles are too far apart to render in MAX_PIXELS tiles or pixels.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SizeError {
+    pub bounds: Bounds,
+    pub scale: usize,
+}
+
+impl fmt::Display for SizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Bounds { min_x, max_x, min_y, max_y } = self.bounds;
+        write!(f, "tiles from ({}, {}) to ({}, {}) at scale {} are more than {} pixels", min_x, min_y, max_x, max_y, self.scale, MAX_PIXELS)
+    }
+}
+
+impl Error for SizeError {}
+
+impl From<SizeError> for io::Error {
+    fn from(e: SizeError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    }
+}
+
+/// Function that answers a program's input instructions by looking at the screen.
+type InputFn = Box<dyn FnMut(&Display) -> i64>;
+
+/// Headless screen for programs that draw tiles.  Coordinates that were never drawn show tile 0.
+pub struct Display {
+    palette: Palette,
+    specials: HashSet<(i64, i64)>,
+    tiles: HashMap<(i64, i64), i64>,
+    values: HashMap<(i64, i64), i64>,
+    draws: Frames<(i64, i64, i64)>,
+    input: Option<InputFn>,
+}
+
+impl Display {
+    /// Constructs an empty display that draws tiles with the given palette.
+    pub fn new(palette: Palette) -> Display {
+        Display {
+            palette,
+            specials: HashSet::new(),
+            tiles: HashMap::new(),
+            values: HashMap::new(),
+            draws: Frames::new(),
+            input: None,
+        }
+    }
+
+    /// Treats the given coordinate as a value instead of a tile, like a score.
+    pub fn with_special(mut self, x: i64, y: i64) -> Self {
+        self.specials.insert((x, y));
+        self
+    }
+
+    /// Answers the program's input instructions with the given function of the screen.  Without
+    /// an input function, the program reads 0.
+    pub fn with_input<F: FnMut(&Display) -> i64 + 'static>(mut self, input: F) -> Self {
+        self.input = Some(Box::new(input));
+        self
+    }
+
+    /// Adds an output value, returning whether it completed a draw.
+    pub fn push(&mut self, value: i64) -> bool {
+        match self.draws.push(value) {
+            Some((x, y, value)) if self.specials.contains(&(x, y)) => {
+                self.values.insert((x, y), value);
+                true
+            }
+            Some((x, y, tile)) => {
+                self.tiles.insert((x, y), tile);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds output values, like the output of a computer that ran until it needed input.
+    pub fn extend<I: IntoIterator<Item = i64>>(&mut self, values: I) {
+        values.into_iter().for_each(|value| {
+            self.push(value);
+        });
+    }
+
+    /// Checks that the program's output ended on a draw, failing if part of a draw is left over.
+    pub fn finish(&self) -> Result<(), FrameError> {
+        self.draws.finish()
+    }
+
+    /// Returns the tile at the given coordinate.
+    pub fn tile(&self, x: i64, y: i64) -> i64 {
+        self.tiles.get(&(x, y)).copied().unwrap_or(0)
+    }
+
+    /// Returns the last value drawn at a special coordinate, or None if it hasn't been drawn.
+    pub fn special(&self, x: i64, y: i64) -> Option<i64> {
+        self.values.get(&(x, y)).copied()
+    }
+
+    /// Returns a coordinate showing the given tile, or None if the tile isn't on the screen.
+    /// If the tile is in several places, any one of them may be returned.
+    pub fn find(&self, tile: i64) -> Option<(i64, i64)> {
+        self.tiles.iter().find(|(_, &drawn)| drawn == tile).map(|(&position, _)| position)
+    }
+
+    /// Returns the number of coordinates showing each tile.
+    pub fn counts(&self) -> BTreeMap<i64, usize> {
+        let mut counts = BTreeMap::new();
+        for &tile in self.tiles.values() {
+            *counts.entry(tile).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    /// Returns the number of coordinates showing the given tile.
+    pub fn count(&self, tile: i64) -> usize {
+        self.tiles.values().filter(|&&drawn| drawn == tile).count()
+    }
+
+    /// Returns the bounds of the drawn tiles, or None if nothing has been drawn.
+    pub fn bounds(&self) -> Option<Bounds> {
+        let mut positions = self.tiles.keys();
+        let &(x, y) = positions.next()?;
+
+        Some(positions.fold(Bounds { min_x: x, max_x: x, min_y: y, max_y: y }, |bounds, &(x, y)| Bounds {
+            min_x: bounds.min_x.min(x),
+            max_x: bounds.max_x.max(x),
+            min_y: bounds.min_y.min(y),
+            max_y: bounds.max_y.max(y),
+        }))
+    }
+
+    /// Returns the tiles inside the bounds, one row at a time.  Fails if there would be more than
+    /// MAX_PIXELS tiles.
+    pub fn frame(&self) -> Result<Vec<Vec<i64>>, SizeError> {
+        let bounds = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return Ok(Vec::new()),
+        };
+
+        if !matches!(bounds.pixels(1), Some(tiles) if tiles <= MAX_PIXELS) {
+            return Err(SizeError { bounds, scale: 1 });
+        }
+
+        Ok((bounds.min_y..=bounds.max_y)
+            .map(|y| (bounds.min_x..=bounds.max_x).map(|x| self.tile(x, y)).collect())
+            .collect())
+    }
+
+    /// Renders the screen as text, with a line for each row.  Fails like frame.
+    pub fn render(&self) -> Result<String, SizeError> {
+        Ok(self.frame()?.iter()
+            .map(|row| row.iter().map(|&tile| self.palette.paint(tile).char).chain(Some('\n')).collect::<String>())
+            .collect())
+    }
+
+    /// Renders the screen as a binary PPM image, drawing each tile as a square of scale pixels.
+    /// Fails if the image would have more than MAX_PIXELS pixels.
+    pub fn to_ppm(&self, scale: usize) -> Result<Vec<u8>, SizeError> {
+        if let Some(bounds) = self.bounds() {
+            if !matches!(bounds.pixels(scale), Some(pixels) if pixels <= MAX_PIXELS) {
+                return Err(SizeError { bounds, scale });
+            }
+        }
+
+        let frame = self.frame()?;
+        let width = frame.first().map_or(0, Vec::len);
+
+        let mut image = format!("P6\n{} {}\n255\n", width * scale, frame.len() * scale).into_bytes();
+        for row in &frame {
+            for _ in 0..scale {
+                for &tile in row {
+                    let color = self.palette.paint(tile).color;
+                    (0..scale).for_each(|_| image.extend_from_slice(&color));
+                }
+            }
+        }
+
+        Ok(image)
+    }
+
+    /// Writes the screen to the given file as a PPM image.
+    pub fn save_ppm<P: AsRef<Path>>(&self, filename: P, scale: usize) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        writer.write_all(&self.to_ppm(scale)?)?;
+        writer.flush()
+    }
+}
+
+impl fmt::Display for Display {
+    /// Writes the rendered screen, or why it can't be rendered.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.render() {
+            Ok(text) => write!(f, "{}", text),
+            Err(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl ProgramIO for Display {
+    fn input(&mut self) -> i64 {
+        match self.input.take() {
+            Some(mut input) => {
+                let value = input(self);
+                self.input = Some(input);
+                value
+            }
+            None => 0,
+        }
+    }
+
+    fn output(&mut self, value: i64) {
+        self.push(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Computer;
+
+    fn palette() -> Palette {
+        Palette::new().tile(0, '.', [0, 0, 0]).tile(1, '#', [255, 255, 255])
+    }
+
+    #[test]
+    fn draws() {
+        let mut display = Display::new(palette()).with_special(-1, 0);
+        assert_eq!(None, display.bounds());
+        assert_eq!(Ok(String::new()), display.render());
+
+        display.extend(vec![1, 1, 1, 3, 2, 1, -1, 0, 500, 2, 1, 7, 3]);
+        assert_eq!(Some(Bounds { min_x: 1, max_x: 3, min_y: 1, max_y: 2 }), display.bounds());
+        assert_eq!(Some(500), display.special(-1, 0));
+        assert_eq!(Ok(vec![vec![1, 7, 0], vec![0, 0, 1]]), display.frame());
+        assert_eq!("#?.\n..#\n", display.to_string());
+        assert_eq!(vec![(1, 2), (7, 1)], display.counts().into_iter().collect::<Vec<_>>());
+        assert_eq!(Some((2, 1)), display.find(7));
+
+        // The 3 that starts the next draw isn't drawn yet.
+        assert!(!display.push(2));
+        assert!(display.finish().is_err());
+        assert!(display.push(0));
+        assert_eq!(0, display.tile(3, 2));
+        assert_eq!(Ok(()), display.finish());
+
+        let ppm = display.to_ppm(2).unwrap();
+        assert!(ppm.starts_with(b"P6\n6 4\n255\n"));
+        assert_eq!(11 + 6 * 4 * 3, ppm.len());
+        assert_eq!(&[255, 255, 255, 255, 255, 255, 255, 0, 255], &ppm[11..20]);
+
+        let too_large = SizeError { bounds: Bounds { min_x: 1, max_x: 3, min_y: 1, max_y: 2 }, scale: 4096 };
+        assert_eq!(Err(too_large), display.to_ppm(4096));
+    }
+
+    #[test]
+    fn far_apart_tiles() {
+        let mut display = Display::new(palette());
+        display.extend(vec![i64::MIN, 0, 1, i64::MAX, 0, 1]);
+
+        let bounds = Bounds { min_x: i64::MIN, max_x: i64::MAX, min_y: 0, max_y: 0 };
+        assert_eq!((None, Some(1)), (bounds.width(), bounds.height()));
+        assert_eq!(Err(SizeError { bounds, scale: 1 }), display.frame());
+        assert_eq!(Err(SizeError { bounds, scale: 1 }), display.render());
+        assert_eq!(Err(SizeError { bounds, scale: 2 }), display.to_ppm(2));
+        assert!(display.to_string().starts_with("tiles from ("));
+
+        // One column past MAX_PIXELS tiles.
+        let mut display = Display::new(palette());
+        display.extend(vec![0, 0, 1, 4096, 4095, 1]);
+        assert!(display.frame().is_err());
+    }
+
+    #[test]
+    fn program_io() {
+        // Reads x twice, drawing a wall at (x, 0) each time.
+        let mut computer = Computer::new(vec![3, 100, 4, 100, 104, 0, 104, 1, 3, 100, 4, 100, 104, 0, 104, 1, 99]);
+        let mut reads = 0;
+        let mut display = Display::new(palette()).with_input(move |display| {
+            reads += 1;
+            display.count(1) as i64 + reads
+        });
+
+        computer.run_io(&mut display);
+        assert_eq!("#.#\n", display.to_string());
+    }
+}
@@ -0,0 +1,40 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::isa::Isa;
+
+/// IntcodeError is returned when a program does something the computer can't execute.  The computer
+/// stops at the instruction that caused the error, so running it again returns the same error.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum IntcodeError {
+    /// The instruction at pc isn't a built in or registered opcode.
+    UnknownOpcode { opcode: i64 },
+    /// A parameter's mode isn't position, immediate or relative.
+    InvalidParameterMode { mode: u32 },
+    /// A memory hook rejected a write to the given address.
+    WriteRejected { addr: usize, value: i64 },
+    /// A custom opcode's handler failed.
+    Extension { opcode: u32, message: String },
+    /// The instruction's opcode is built in, but isn't part of the computer's instruction set.
+    UnsupportedInstruction { instruction: i64, isa: Isa },
+    /// A parameter's mode isn't part of the computer's instruction set.
+    UnsupportedMode { mode: u32, isa: Isa },
+    /// A strict computer ran an instruction that writes to an immediate mode parameter.
+    ImmediateWrite { instruction: i64 },
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntcodeError::UnknownOpcode { opcode } => write!(f, "Unknown opcode {}", opcode),
+            IntcodeError::InvalidParameterMode { mode } => write!(f, "Invalid parameter mode {}", mode),
+            IntcodeError::Extension { opcode, message } => write!(f, "Opcode {} failed: {}", opcode, message),
+            IntcodeError::WriteRejected { addr, value } => write!(f, "Write of {} to address {} was rejected", value, addr),
+            IntcodeError::UnsupportedInstruction { instruction, isa } => write!(f, "Instruction {} isn't in the {} instruction set", instruction, isa),
+            IntcodeError::UnsupportedMode { mode, isa } => write!(f, "Parameter mode {} isn't in the {} instruction set", mode, isa),
+            IntcodeError::ImmediateWrite { instruction } => write!(f, "Instruction {} writes to an immediate mode parameter", instruction),
+        }
+    }
+}
+
+impl Error for IntcodeError {}
@@ -0,0 +1,117 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::computer::{Computer, Parameter};
+use crate::error::IntcodeError;
+
+// Custom opcodes extend the instruction set without changing the interpreter.  A custom opcode has
+// a fixed number of parameters, which are decoded with the usual parameter modes, and a handler
+// that runs the instruction:
+//
+//   // 50 prints its only parameter.
+//   computer.register_opcode(50, "print", 1, |computer, parameters| {
+//       println!("{}", parameters[0].value(&computer.memory));
+//       Ok(Outcome::Next)
+//   });
+
+/// What the computer does after running a custom instruction.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    /// Continue with the instruction after this one.
+    Next,
+    /// Continue at the given address.
+    JumpTo(usize),
+    /// Block until more input is available, then run this instruction again.
+    WaitForInput,
+    /// Stop the program.
+    Halt,
+}
+
+/// Handler for a custom opcode.  Receives the computer and the decoded parameters of the instruction.
+pub type OpcodeHandler = dyn Fn(&mut Computer, &[Parameter]) -> Result<Outcome, IntcodeError> + Send + Sync;
+
+/// A custom opcode registered with Computer::register_opcode.
+#[derive(Clone)]
+pub struct CustomOpcode {
+    pub name: String,
+    pub parameters: usize,
+    pub(crate) handler: Arc<OpcodeHandler>,
+}
+
+impl fmt::Debug for CustomOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.name, self.parameters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::ProgramState;
+
+    #[test]
+    fn debug_print() {
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let print_to = printed.clone();
+
+        // Prints 42, then the value at address 7, then halts.
+        let mut computer = Computer::new(vec![150, 42, 50, 7, 99, 0, 0, 11]);
+        computer.register_opcode(50, "print", 1, move |computer, parameters| {
+            print_to.lock().unwrap().push(parameters[0].value(&computer.memory));
+            Ok(Outcome::Next)
+        });
+
+        computer.run();
+
+        assert_eq!(vec![42, 11], *printed.lock().unwrap());
+        assert_eq!(&ProgramState::Done, computer.state());
+    }
+
+    #[test]
+    fn assertion() {
+        // Asserts that the two parameters are equal.  The second assertion fails.
+        let mut computer = Computer::new(vec![11151, 3, 3, 11151, 4, 5, 99]);
+        computer.register_opcode(51, "assert_eq", 2, |computer, parameters| {
+            let (a, b) = (parameters[0].value(&computer.memory), parameters[1].value(&computer.memory));
+
+            if a == b {
+                Ok(Outcome::Next)
+            } else {
+                Err(IntcodeError::Extension { opcode: 51, message: format!("{} != {}", a, b) })
+            }
+        });
+
+        assert_eq!(Err(IntcodeError::Extension { opcode: 51, message: "4 != 5".to_string() }), computer.try_run());
+        assert_eq!(3, computer.pc());
+    }
+
+    #[test]
+    fn jump_and_write() {
+        // 52 stores 1 at its second parameter and jumps to its first.
+        let mut computer = Computer::new(vec![152, 4, 9, 99, 4, 9, 99, 0, 0, 0]);
+        computer.register_opcode(52, "set_and_jump", 2, |computer, parameters| {
+            let addr = parameters[1].addr().unwrap();
+            computer.memory.set(addr, 1)?;
+
+            Ok(Outcome::JumpTo(parameters[0].value(&computer.memory) as usize))
+        });
+
+        assert_eq!(Some(1), computer.run());
+    }
+
+    #[test]
+    fn unknown_opcode() {
+        let mut computer = Computer::new(vec![1101, 1, 1, 5, 50, 0]);
+
+        assert_eq!(Err(IntcodeError::UnknownOpcode { opcode: 50 }), computer.try_run());
+        assert_eq!(4, computer.pc());
+    }
+
+    #[test]
+    #[should_panic(expected = "Opcode 2 is built in")]
+    fn builtin_opcode() {
+        Computer::new(vec![99]).register_opcode(2, "multiply", 3, |_, _| Ok(Outcome::Next));
+    }
+}
@@ -0,0 +1,168 @@
+use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
+
+// Many programs talk in fixed size frames: a painting robot outputs (color, turn) pairs, an arcade
+// cabinet outputs (x, y, tile) triples, and a network node outputs (address, x, y) packets.
+// A protocol is declared once by implementing Frame for the type each frame becomes, and Frames
+// groups output values into frames as they arrive.
+//
+//   impl Frame for Tile {
+//       const ARITY: usize = 3;
+//
+//       fn decode(values: &[i64]) -> Tile {
+//           Tile { x: values[0], y: values[1], id: values[2] }
+//       }
+//   }
+//
+//   let tiles: Vec<Tile> = frame::decode(&computer.dump_output())?;
+
+/// A value made from a fixed number of consecutive output values.
+pub trait Frame: Sized {
+    /// Number of output values in a frame.
+    const ARITY: usize;
+
+    /// Constructs a frame from exactly ARITY values.
+    fn decode(values: &[i64]) -> Self;
+}
+
+impl Frame for (i64, i64) {
+    const ARITY: usize = 2;
+
+    fn decode(values: &[i64]) -> Self {
+        (values[0], values[1])
+    }
+}
+
+impl Frame for (i64, i64, i64) {
+    const ARITY: usize = 3;
+
+    fn decode(values: &[i64]) -> Self {
+        (values[0], values[1], values[2])
+    }
+}
+
+/// Output ended partway through a frame.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FrameError {
+    /// Number of values in a full frame.
+    pub arity: usize,
+    /// Values in the incomplete frame.
+    pub leftover: Vec<i64>,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "output ended with {} of the {} values in a frame: {:?}", self.leftover.len(), self.arity, self.leftover)
+    }
+}
+
+impl Error for FrameError {}
+
+/// Decodes output values into frames, keeping values that don't make up a full frame yet until
+/// the rest of the frame arrives.
+#[derive(Debug, Clone)]
+pub struct Frames<F> {
+    partial: Vec<i64>,
+    frame: PhantomData<F>,
+}
+
+impl<F: Frame> Default for Frames<F> {
+    fn default() -> Self {
+        Frames { partial: Vec::with_capacity(F::ARITY), frame: PhantomData }
+    }
+}
+
+impl<F: Frame> Frames<F> {
+    /// Constructs a decoder that hasn't seen any values.
+    pub fn new() -> Frames<F> {
+        Frames::default()
+    }
+
+    /// Adds an output value, returning a frame if the value completes one.
+    pub fn push(&mut self, value: i64) -> Option<F> {
+        self.partial.push(value);
+
+        if self.partial.len() == F::ARITY {
+            let frame = F::decode(&self.partial);
+            self.partial.clear();
+            Some(frame)
+        } else {
+            None
+        }
+    }
+
+    /// Adds output values, returning the frames they complete in order.
+    pub fn extend<I: IntoIterator<Item = i64>>(&mut self, values: I) -> Vec<F> {
+        values.into_iter().filter_map(|value| self.push(value)).collect()
+    }
+
+    /// Returns the values in the frame that hasn't been completed yet.
+    pub fn partial(&self) -> &[i64] {
+        &self.partial
+    }
+
+    /// Checks that the output ended on a frame boundary, failing if part of a frame is left over.
+    pub fn finish(&self) -> Result<(), FrameError> {
+        if self.partial.is_empty() {
+            Ok(())
+        } else {
+            Err(FrameError { arity: F::ARITY, leftover: self.partial.clone() })
+        }
+    }
+}
+
+/// Decodes a complete output into frames.  Fails if the output doesn't end on a frame boundary.
+pub fn decode<F: Frame>(values: &[i64]) -> Result<Vec<F>, FrameError> {
+    let mut frames = Frames::new();
+    let decoded = frames.extend(values.iter().copied());
+    frames.finish()?;
+
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Eq, PartialEq)]
+    enum Draw {
+        Tile { x: i64, y: i64, id: i64 },
+        Score(i64),
+    }
+
+    impl Frame for Draw {
+        const ARITY: usize = 3;
+
+        fn decode(values: &[i64]) -> Self {
+            match values {
+                [-1, 0, score] => Draw::Score(*score),
+                [x, y, id] => Draw::Tile { x: *x, y: *y, id: *id },
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn streaming() {
+        let mut frames: Frames<Draw> = Frames::new();
+
+        assert_eq!(None, frames.push(1));
+        assert_eq!(None, frames.push(2));
+        assert_eq!(&[1, 2], frames.partial());
+        assert_eq!(Some(Draw::Tile { x: 1, y: 2, id: 3 }), frames.push(3));
+        assert!(frames.partial().is_empty());
+
+        assert_eq!(vec![Draw::Score(12345)], frames.extend(vec![-1, 0, 12345, 4, 5]));
+        assert_eq!(Err(FrameError { arity: 3, leftover: vec![4, 5] }), frames.finish());
+    }
+
+    #[test]
+    fn decode_output() {
+        assert_eq!(Ok(vec![(0, 1), (1, 0)]), decode::<(i64, i64)>(&[0, 1, 1, 0]));
+        assert_eq!(Ok(vec![]), decode::<(i64, i64, i64)>(&[]));
+
+        let error = decode::<(i64, i64, i64)>(&[255, 1, 2, 3]).unwrap_err();
+        assert_eq!("output ended with 1 of the 3 values in a frame: [3]", error.to_string());
+    }
+}
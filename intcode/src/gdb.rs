@@ -0,0 +1,828 @@
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use crate::error::IntcodeError;
+use crate::{Computer, ProgramState};
+
+// A GdbStub lets a debugger that speaks the GDB remote serial protocol drive a computer over TCP,
+// so existing debugger front ends can step through intcode programs.  It supports a subset of the
+// protocol: reading and writing registers and memory, breakpoints, stepping, continuing and
+// interrupting.
+//
+//   let computer = Computer::load("input.txt")?;
+//   let computer = GdbStub::new(computer).listen("127.0.0.1:1234")?;
+//
+//   $ gdb-multiarch
+//   (gdb) target remote localhost:1234
+//   (gdb) monitor input 5
+//   (gdb) break *0x40
+//   (gdb) continue
+//   (gdb) x/4gd 0x40
+//
+// gdb only accepts target descriptions for architectures it was built with, so the stub describes
+// itself as a 64-bit RISC-V CPU, and needs a gdb with RISC-V support like Debian's gdb-multiarch.
+// The RISC-V pc is the program's pc, fp (x8) is the relative base, and the other registers always
+// read as 0.  Disassembly is meaningless, so examine memory as giant words ('x/gd') instead.  The
+// stub advertises vCont so gdb steps with the stub rather than decoding RISC-V instructions.
+//
+// Debuggers address memory in bytes, so each intcode value is 8 little endian bytes: the value at
+// address a lives at byte 8a, and pc and fp hold byte addresses too.  Program output is printed on
+// the debugger's console whenever the program stops, and 'monitor input' queues input values,
+// 'monitor text' queues a line of ASCII input, and 'monitor reset' restarts the program.  Ctrl-C
+// interrupts a program stuck in a loop.
+
+/// Bytes in each intcode value, as the debugger sees memory.
+const WORD: usize = 8;
+
+/// Largest packet the stub accepts, which also limits memory reads and writes.
+const PACKET_SIZE: usize = 0x1000;
+
+/// Instructions to run between checks for an interrupt from the debugger.
+const POLL_STEPS: usize = 4096;
+
+/// Registers in a 'g' packet: RISC-V's x0 through x31, then pc.
+const REGISTERS: u64 = 33;
+
+/// Register number of fp (x8), which holds the relative base.
+const FP: u64 = 8;
+
+/// Register number of pc.
+const PC: u64 = 32;
+
+/// Registers the stub describes to the debugger, laid out like gdb's own 64-bit RISC-V description.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <architecture>riscv:rv64</architecture>
+  <feature name="org.gnu.gdb.riscv.cpu">
+    <reg name="zero" bitsize="64" type="int" regnum="0"/>
+    <reg name="ra" bitsize="64" type="code_ptr"/>
+    <reg name="sp" bitsize="64" type="data_ptr"/>
+    <reg name="gp" bitsize="64" type="data_ptr"/>
+    <reg name="tp" bitsize="64" type="data_ptr"/>
+    <reg name="t0" bitsize="64" type="int"/>
+    <reg name="t1" bitsize="64" type="int"/>
+    <reg name="t2" bitsize="64" type="int"/>
+    <reg name="fp" bitsize="64" type="data_ptr"/>
+    <reg name="s1" bitsize="64" type="int"/>
+    <reg name="a0" bitsize="64" type="int"/>
+    <reg name="a1" bitsize="64" type="int"/>
+    <reg name="a2" bitsize="64" type="int"/>
+    <reg name="a3" bitsize="64" type="int"/>
+    <reg name="a4" bitsize="64" type="int"/>
+    <reg name="a5" bitsize="64" type="int"/>
+    <reg name="a6" bitsize="64" type="int"/>
+    <reg name="a7" bitsize="64" type="int"/>
+    <reg name="s2" bitsize="64" type="int"/>
+    <reg name="s3" bitsize="64" type="int"/>
+    <reg name="s4" bitsize="64" type="int"/>
+    <reg name="s5" bitsize="64" type="int"/>
+    <reg name="s6" bitsize="64" type="int"/>
+    <reg name="s7" bitsize="64" type="int"/>
+    <reg name="s8" bitsize="64" type="int"/>
+    <reg name="s9" bitsize="64" type="int"/>
+    <reg name="s10" bitsize="64" type="int"/>
+    <reg name="s11" bitsize="64" type="int"/>
+    <reg name="t3" bitsize="64" type="int"/>
+    <reg name="t4" bitsize="64" type="int"/>
+    <reg name="t5" bitsize="64" type="int"/>
+    <reg name="t6" bitsize="64" type="int"/>
+    <reg name="pc" bitsize="64" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+/// Why the program stopped running.
+enum Stop {
+    /// Stopped after a single step, or at a breakpoint.
+    Trap,
+    /// The debugger interrupted the program.
+    Interrupted,
+    /// The program needs input that hasn't been queued.
+    Input,
+    /// The program halted.
+    Halted,
+    /// The program can't be executed.
+    Error(IntcodeError),
+}
+
+/// Packets to send in response to a command, and whether the session is over.
+struct Reply {
+    packets: Vec<String>,
+    close: bool,
+}
+
+impl Reply {
+    /// Responds with a single packet.
+    fn packet(packet: &str) -> Reply {
+        Reply { packets: vec![packet.to_string()], close: false }
+    }
+}
+
+/// Stream to a debugger, which the stub checks for interrupts while the program runs.
+pub trait Connection: Read + Write {
+    /// Returns whether the debugger sent an interrupt (0x03), consuming it, without waiting for
+    /// the debugger to send anything.
+    fn interrupted(&mut self) -> io::Result<bool>;
+}
+
+impl Connection for TcpStream {
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.set_nonblocking(true)?;
+
+        let mut byte = [0];
+        let interrupted = loop {
+            match self.peek(&mut byte) {
+                // Acks for the last reply can arrive ahead of the interrupt.
+                Ok(1) if byte[0] == b'+' => self.read_exact(&mut byte)?,
+                Ok(1) if byte[0] == 0x03 => {
+                    self.read_exact(&mut byte)?;
+                    break Ok(true);
+                }
+                Ok(_) => break Ok(false),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(false),
+                Err(e) => break Err(e),
+            }
+        };
+
+        self.set_nonblocking(false)?;
+        interrupted
+    }
+}
+
+/// Debugger session for a computer.
+pub struct GdbStub {
+    computer: Computer,
+    breakpoints: BTreeSet<usize>,
+}
+
+impl GdbStub {
+    /// Constructs a stub that debugs the given computer.
+    pub fn new(computer: Computer) -> GdbStub {
+        GdbStub { computer, breakpoints: BTreeSet::new() }
+    }
+
+    /// Returns the computer being debugged.
+    pub fn computer(&self) -> &Computer {
+        &self.computer
+    }
+
+    /// Returns the computer being debugged, ending the session.
+    pub fn into_computer(self) -> Computer {
+        self.computer
+    }
+
+    /// Waits for a debugger to connect to the given address and serves it until it detaches,
+    /// returning the computer in the state the debugger left it.
+    pub fn listen<A: ToSocketAddrs>(mut self, addr: A) -> io::Result<Computer> {
+        let listener = TcpListener::bind(addr)?;
+        self.serve(&listener)?;
+
+        Ok(self.computer)
+    }
+
+    /// Accepts one debugger connection from the listener and serves it until it detaches.
+    pub fn serve(&mut self, listener: &TcpListener) -> io::Result<()> {
+        let (stream, _) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        self.session(stream)
+    }
+
+    /// Answers packets from the debugger on the given stream until it detaches, kills the program,
+    /// or disconnects.
+    pub fn session<S: Connection>(&mut self, mut stream: S) -> io::Result<()> {
+        let mut last = Vec::new();
+
+        while let Some(packet) = read_packet(&mut stream, &last)? {
+            let reply = self.handle(&packet, &mut stream)?;
+
+            for packet in &reply.packets {
+                last = frame(packet);
+                stream.write_all(&last)?;
+            }
+            stream.flush()?;
+
+            if reply.close {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs a command packet, returning the packets to send back.  Resuming the program watches
+    /// the stream for interrupts.
+    fn handle<S: Connection>(&mut self, packet: &str, stream: &mut S) -> io::Result<Reply> {
+        let (command, args) = packet.split_at(packet.len().min(1));
+
+        let reply = match command {
+            "?" => Reply::packet(if *self.computer.state() == ProgramState::Done { "W00" } else { "S05" }),
+            "g" => Reply::packet(&self.read_registers()),
+            "G" => Reply::packet(self.write_registers(args)),
+            "p" => Reply::packet(&self.read_register(args)),
+            "P" => Reply::packet(self.write_register(args)),
+            "m" => Reply::packet(&self.read_memory(args)),
+            "M" => Reply::packet(self.write_memory(args)),
+            "Z" => Reply::packet(self.breakpoint(args, true)),
+            "z" => Reply::packet(self.breakpoint(args, false)),
+            "s" | "c" => {
+                if let Some(addr) = parse_hex(args) {
+                    self.computer.set_pc(addr as usize / WORD);
+                }
+
+                Reply { packets: self.resume(command == "s", stream)?, close: false }
+            }
+            "v" if args == "Cont?" => Reply::packet("vCont;c;C;s;S"),
+            "v" if args.starts_with("Cont;") => {
+                // Only one thread, so the first action applies to it.  Signals are ignored.
+                let single_step = args[5..].starts_with(['s', 'S']);
+                Reply { packets: self.resume(single_step, stream)?, close: false }
+            }
+            "q" if args.starts_with("Rcmd,") => Reply { packets: self.monitor(&args[5..]), close: false },
+            "q" => Reply::packet(&query(args)),
+            "H" | "T" => Reply::packet("OK"),
+            "D" => Reply { packets: vec!["OK".to_string()], close: true },
+            "k" => Reply { packets: Vec::new(), close: true },
+            _ => Reply::packet(""),
+        };
+
+        Ok(reply)
+    }
+
+    /// Returns the registers as a 'g' packet: x0 through x31, then pc.
+    fn read_registers(&self) -> String {
+        (0..REGISTERS).map(|register| self.register(register)).collect()
+    }
+
+    /// Sets the registers from a 'G' packet.  Only pc and fp are kept.
+    fn write_registers(&mut self, args: &str) -> &'static str {
+        let words: Option<Vec<i64>> = (0..REGISTERS as usize)
+            .map(|register| args.get(register * 2 * WORD..(register + 1) * 2 * WORD).and_then(parse_word))
+            .collect();
+
+        match words {
+            Some(words) if args.len() == REGISTERS as usize * 2 * WORD => {
+                self.computer.set_pc(words[PC as usize] as usize / WORD);
+                self.computer.set_relative_base(words[FP as usize] / WORD as i64);
+                "OK"
+            }
+            _ => "E01",
+        }
+    }
+
+    /// Returns a register's value as little endian hex, for a 'p' packet.
+    fn read_register(&self, args: &str) -> String {
+        match parse_hex(args) {
+            Some(register) if register < REGISTERS => self.register(register),
+            _ => "E01".to_string(),
+        }
+    }
+
+    /// Returns a register's value as little endian hex.
+    fn register(&self, register: u64) -> String {
+        let value = match register {
+            PC => (self.computer.pc() * WORD) as i64,
+            FP => self.computer.relative_base() * WORD as i64,
+            _ => 0,
+        };
+
+        to_hex(&value.to_le_bytes())
+    }
+
+    /// Sets a register from a 'P' packet, like 20=1800000000000000.  Writes to registers other
+    /// than pc and fp are ignored.
+    fn write_register(&mut self, args: &str) -> &'static str {
+        let (register, value) = match args.split_once('=') {
+            Some((register, value)) => (parse_hex(register), parse_word(value)),
+            None => return "E01",
+        };
+
+        match (register, value) {
+            (Some(PC), Some(value)) => self.computer.set_pc(value as usize / WORD),
+            (Some(FP), Some(value)) => self.computer.set_relative_base(value / WORD as i64),
+            (Some(register), Some(_)) if register < REGISTERS => {}
+            _ => return "E01",
+        }
+
+        "OK"
+    }
+
+    /// Reads memory for an 'm' packet, like 40,10 for 16 bytes starting at byte 64.
+    fn read_memory(&self, args: &str) -> String {
+        let (start, end) = match parse_memory_range(args) {
+            Some(range) => range,
+            None => return "E01".to_string(),
+        };
+
+        let bytes: Vec<u8> = (start..end)
+            .map(|byte| self.computer.memory.peek(byte / WORD).to_le_bytes()[byte % WORD])
+            .collect();
+
+        to_hex(&bytes)
+    }
+
+    /// Writes memory for an 'M' packet, like 40,8:0100000000000000.  Writes go through the memory's
+    /// hooks, and fail if a hook rejects one.
+    fn write_memory(&mut self, args: &str) -> &'static str {
+        let (range, data) = match args.split_once(':') {
+            Some((range, data)) => (parse_memory_range(range), parse_bytes(data)),
+            None => return "E01",
+        };
+
+        let (start, end, bytes) = match (range, data) {
+            (Some((start, end)), Some(bytes)) if bytes.len() == end - start => (start, end, bytes),
+            _ => return "E01",
+        };
+
+        let mut byte = start;
+        while byte < end {
+            let addr = byte / WORD;
+            let mut value = self.computer.memory.peek(addr).to_le_bytes();
+
+            while byte < end && byte / WORD == addr {
+                value[byte % WORD] = bytes[byte - start];
+                byte += 1;
+            }
+
+            if self.computer.memory.set(addr, i64::from_le_bytes(value)).is_err() {
+                return "E02";
+            }
+        }
+
+        "OK"
+    }
+
+    /// Adds or removes a breakpoint for a 'Z' or 'z' packet, like 0,40,1.  Software and hardware
+    /// breakpoints are the same thing, and watchpoints aren't supported.
+    fn breakpoint(&mut self, args: &str, insert: bool) -> &'static str {
+        let mut parts = args.split(',');
+
+        let addr = match (parts.next(), parts.next().and_then(parse_hex)) {
+            (Some("0"), Some(addr)) | (Some("1"), Some(addr)) => addr as usize / WORD,
+            _ => return "",
+        };
+
+        if insert {
+            self.breakpoints.insert(addr);
+        } else {
+            self.breakpoints.remove(&addr);
+        }
+
+        "OK"
+    }
+
+    /// Runs one instruction, or runs until the program reaches a breakpoint or the debugger
+    /// interrupts it, returning the program's output and a stop reply.
+    fn resume<S: Connection>(&mut self, single_step: bool, stream: &mut S) -> io::Result<Vec<String>> {
+        let mut steps = 0;
+        let stop = loop {
+            if let Err(e) = self.computer.step() {
+                break Stop::Error(e);
+            }
+
+            steps += 1;
+            if steps % POLL_STEPS == 0 && stream.interrupted()? {
+                break Stop::Interrupted;
+            }
+
+            match self.computer.state() {
+                ProgramState::Done => break Stop::Halted,
+                ProgramState::WaitingForInput => break Stop::Input,
+                ProgramState::Runnable if single_step || self.breakpoints.contains(&self.computer.pc()) => break Stop::Trap,
+                ProgramState::Runnable => {}
+            }
+        };
+
+        let mut console: String = self.computer.dump_output().iter().map(|value| format!("{}\n", value)).collect();
+        let reply = match stop {
+            Stop::Trap => "S05".to_string(),
+            Stop::Interrupted => "S02".to_string(),
+            Stop::Input => {
+                console.push_str("Waiting for input - queue some with 'monitor input'\n");
+                "S05".to_string()
+            }
+            Stop::Halted => "W00".to_string(),
+            Stop::Error(e) => {
+                console.push_str(&format!("{} at pc {}\n", e, self.computer.pc()));
+                "S04".to_string()
+            }
+        };
+
+        let mut packets = console_packets(&console);
+        packets.push(reply);
+        Ok(packets)
+    }
+
+    /// Runs a 'monitor' command, given as hex encoded text.
+    fn monitor(&mut self, hex: &str) -> Vec<String> {
+        let command = match parse_bytes(hex).and_then(|bytes| String::from_utf8(bytes).ok()) {
+            Some(command) => command,
+            None => return vec!["E01".to_string()],
+        };
+
+        let (name, rest) = command.split_once(' ').unwrap_or((&command, ""));
+        let message = match name {
+            "input" => {
+                match rest.split_whitespace().map(str::parse).collect::<Result<Vec<i64>, _>>() {
+                    Ok(values) => {
+                        values.iter().for_each(|&value| self.computer.input(value));
+                        format!("Queued {} input values\n", values.len())
+                    }
+                    Err(e) => format!("Invalid input: {}\n", e),
+                }
+            }
+            "text" => {
+                self.computer.text_input(rest);
+                self.computer.text_input("\n");
+                format!("Queued {} characters of input\n", rest.len() + 1)
+            }
+            "reset" => {
+                self.computer.reset();
+                "Reset the program\n".to_string()
+            }
+            _ => "Commands: input <values>, text <line>, reset\n".to_string(),
+        };
+
+        let mut packets = console_packets(&message);
+        packets.push("OK".to_string());
+        packets
+    }
+}
+
+/// Answers a general query packet, without its leading 'q'.
+fn query(args: &str) -> String {
+    if args.starts_with("Supported") {
+        return format!("PacketSize={:x};qXfer:features:read+;vContSupported+", PACKET_SIZE);
+    }
+
+    if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+        return match parse_range(range) {
+            Some((offset, len)) => {
+                let end = offset.saturating_add(len);
+                let chunk = TARGET_XML.get(offset.min(TARGET_XML.len())..end.min(TARGET_XML.len())).unwrap_or("");
+                let more = end < TARGET_XML.len();
+                format!("{}{}", if more { "m" } else { "l" }, chunk)
+            }
+            None => "E01".to_string(),
+        };
+    }
+
+    match args {
+        "Attached" => "1",
+        "C" => "QC1",
+        "fThreadInfo" => "m1",
+        "sThreadInfo" => "l",
+        _ => "",
+    }.to_string()
+}
+
+/// Splits text for the debugger's console into 'O' packets.
+fn console_packets(text: &str) -> Vec<String> {
+    text.as_bytes().chunks(1024).map(|chunk| format!("O{}", to_hex(chunk))).collect()
+}
+
+/// Reads the next packet from the debugger, acknowledging it.  Resends the last packet if the
+/// debugger asks for it again, and returns None when the debugger disconnects.
+fn read_packet<S: Read + Write>(stream: &mut S, last: &[u8]) -> io::Result<Option<String>> {
+    let mut byte = [0];
+
+    loop {
+        if stream.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+
+        match byte[0] {
+            b'$' => {}
+            b'-' => {
+                stream.write_all(last)?;
+                continue;
+            }
+            // Acks, and interrupts while the program isn't running.
+            _ => continue,
+        }
+
+        let mut data = Vec::new();
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+
+            if byte[0] == b'#' {
+                break;
+            }
+            data.push(byte[0]);
+        }
+
+        let mut checksum = [0; 2];
+        stream.read_exact(&mut checksum)?;
+
+        let expected = std::str::from_utf8(&checksum).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if expected == Some(checksum_of(&data)) {
+            stream.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&unescape(&data)).into_owned()));
+        }
+
+        stream.write_all(b"-")?;
+    }
+}
+
+/// Frames a packet for sending: $data#checksum, with protocol characters in the data escaped.
+fn frame(packet: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(packet.len());
+    for &byte in packet.as_bytes() {
+        if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+            data.push(b'}');
+            data.push(byte ^ 0x20);
+        } else {
+            data.push(byte);
+        }
+    }
+
+    let mut framed = vec![b'$'];
+    framed.extend_from_slice(&data);
+    framed.extend_from_slice(format!("#{:02x}", checksum_of(&data)).as_bytes());
+    framed
+}
+
+/// Removes the escapes from packet data.
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(data.len());
+    let mut escaped = false;
+
+    for &byte in data {
+        match (escaped, byte) {
+            (false, b'}') => escaped = true,
+            (true, byte) => {
+                bytes.push(byte ^ 0x20);
+                escaped = false;
+            }
+            (false, byte) => bytes.push(byte),
+        }
+    }
+
+    bytes
+}
+
+/// Returns the checksum of packet data: the sum of its bytes, modulo 256.
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// Parses a hex number.
+fn parse_hex(hex: &str) -> Option<u64> {
+    u64::from_str_radix(hex, 16).ok()
+}
+
+/// Parses an address and length, like 40,10.
+fn parse_range(args: &str) -> Option<(usize, usize)> {
+    let (start, len) = args.split_once(',')?;
+    Some((parse_hex(start)? as usize, parse_hex(len)? as usize))
+}
+
+/// Parses the address and length of an 'm' or 'M' packet into a range of bytes, rejecting
+/// lengths that don't fit in a packet and ranges past the end of the address space.
+fn parse_memory_range(args: &str) -> Option<(usize, usize)> {
+    let (start, len) = parse_range(args)?;
+    if len > PACKET_SIZE / 2 {
+        return None;
+    }
+
+    Some((start, start.checked_add(len)?))
+}
+
+/// Parses a value sent as 8 little endian bytes of hex.
+fn parse_word(hex: &str) -> Option<i64> {
+    let bytes = parse_bytes(hex)?;
+    let word: [u8; WORD] = bytes.as_slice().try_into().ok()?;
+
+    Some(i64::from_le_bytes(word))
+}
+
+/// Parses pairs of hex digits into bytes.
+fn parse_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// Formats bytes as pairs of hex digits.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpStream;
+    use std::thread;
+    use std::thread::JoinHandle;
+
+    /// Scripted debugger that talks to a stub over loopback.
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        /// Starts a stub for the program on a free port, and connects to it.
+        fn connect(program: Vec<i64>) -> (Client, JoinHandle<Computer>) {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            let stub = thread::spawn(move || {
+                let mut stub = GdbStub::new(Computer::new(program));
+                stub.serve(&listener).unwrap();
+                stub.into_computer()
+            });
+
+            let stream = TcpStream::connect(addr).unwrap();
+            stream.set_nodelay(true).unwrap();
+
+            (Client { stream }, stub)
+        }
+
+        /// Sends a packet, returning the replies up to and including the first one that isn't
+        /// console output.
+        fn send(&mut self, packet: &str) -> Vec<String> {
+            self.stream.write_all(&frame(packet)).unwrap();
+            assert_eq!(b'+', self.read_byte());
+
+            let mut replies = Vec::new();
+            loop {
+                let reply = self.read_reply();
+                let console = reply.starts_with('O') && reply != "OK";
+                replies.push(reply);
+
+                if !console {
+                    return replies;
+                }
+            }
+        }
+
+        /// Reads a reply packet and acknowledges it.
+        fn read_reply(&mut self) -> String {
+            assert_eq!(b'$', self.read_byte());
+
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte() {
+                    b'#' => break,
+                    byte => data.push(byte),
+                }
+            }
+
+            let checksum = [self.read_byte(), self.read_byte()];
+            assert_eq!(format!("{:02x}", checksum_of(&data)).as_bytes(), &checksum);
+
+            self.stream.write_all(b"+").unwrap();
+            String::from_utf8(unescape(&data)).unwrap()
+        }
+
+        fn read_byte(&mut self) -> u8 {
+            let mut byte = [0];
+            self.stream.read_exact(&mut byte).unwrap();
+            byte[0]
+        }
+    }
+
+    /// Returns a 'g' packet with the given pc and fp, and every other register 0.
+    fn registers(pc: i64, fp: i64) -> String {
+        (0..REGISTERS).map(|register| match register {
+            PC => to_hex(&pc.to_le_bytes()),
+            FP => to_hex(&fp.to_le_bytes()),
+            _ => "0".repeat(16),
+        }).collect()
+    }
+
+    /// Decodes the text in console output packets.
+    fn console(replies: &[String]) -> String {
+        replies.iter()
+            .filter(|reply| reply.starts_with('O') && *reply != "OK")
+            .map(|reply| String::from_utf8(parse_bytes(&reply[1..]).unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn registers_and_memory() {
+        let (mut client, stub) = Client::connect(vec![109, 5, 1101, 2, 3, 12, 99]);
+
+        assert_eq!(vec!["PacketSize=1000;qXfer:features:read+;vContSupported+"], client.send("qSupported:multiprocess+"));
+        assert_eq!(vec!["S05"], client.send("?"));
+
+        let target = client.send("qXfer:features:read:target.xml:0,1000").remove(0);
+        assert!(target.starts_with('l') && target.contains("<architecture>riscv:rv64</architecture>"));
+        assert_eq!(REGISTERS as usize, target.matches("<reg ").count());
+        assert_eq!(vec!["l"], client.send(&format!("qXfer:features:read:target.xml:{:x},ffffffffffffffff", TARGET_XML.len())));
+
+        // 109,5 then 1101,2,3,... as bytes.
+        assert_eq!(vec!["6d000000000000000500000000000000"], client.send("m0,10"));
+        assert_eq!(vec!["0500"], client.send("m8,2"));
+
+        assert_eq!(vec!["vCont;c;C;s;S"], client.send("vCont?"));
+        assert_eq!(vec!["S05"], client.send("vCont;s:1"));
+        assert_eq!(vec![registers(0x10, 0x28)], client.send("g"));
+
+        // Point the add at address 4 instead of 12, and move the relative base.
+        assert_eq!(vec!["OK"], client.send("M28,1:04"));
+        assert_eq!(vec!["OK"], client.send("P8=0800000000000000"));
+        assert_eq!(vec!["0800000000000000"], client.send("p8"));
+        assert_eq!(vec!["1000000000000000"], client.send("p20"));
+
+        // Other registers ignore writes.
+        assert_eq!(vec!["OK"], client.send("P5=0100000000000000"));
+        assert_eq!(vec!["0000000000000000"], client.send("p5"));
+        assert_eq!(vec!["E01"], client.send("p21"));
+        assert_eq!(vec!["E01"], client.send("G1000"));
+        assert_eq!(vec!["OK"], client.send(&format!("G{}", registers(0x10, 0x08))));
+
+        assert_eq!(vec!["W00"], client.send("c"));
+        assert_eq!(vec!["0500000000000000"], client.send("m20,8"));
+        assert_eq!(vec!["OK"], client.send("D"));
+
+        let computer = stub.join().unwrap();
+        assert_eq!(5, computer.memory.get(4));
+        assert_eq!(1, computer.relative_base());
+    }
+
+    #[test]
+    fn breakpoints_and_input() {
+        // Reads values and outputs each one doubled, halting when it reads 0.
+        let (mut client, stub) = Client::connect(vec![3, 20, 1006, 20, 14, 102, 2, 20, 21, 4, 21, 1105, 1, 0, 99]);
+
+        let replies = client.send("c");
+        assert_eq!("S05", replies.last().unwrap());
+        assert!(console(&replies).starts_with("Waiting for input"));
+
+        let replies = client.send(&format!("qRcmd,{}", to_hex(b"input 3 4")));
+        assert_eq!(("Queued 2 input values\n".to_string(), "OK"), (console(&replies), replies.last().unwrap().as_str()));
+
+        // Break on the output instruction at 9, which is byte 0x48.
+        assert_eq!(vec!["OK"], client.send("Z0,48,1"));
+        assert_eq!(vec!["S05"], client.send("c"));
+        assert_eq!(vec!["4800000000000000"], client.send("p20"));
+
+        let replies = client.send("c");
+        assert_eq!(("6\n".to_string(), "S05"), (console(&replies), replies.last().unwrap().as_str()));
+
+        assert_eq!(vec!["OK"], client.send("z0,48,1"));
+        let replies = client.send("c");
+        assert!(console(&replies).starts_with("8\nWaiting for input"));
+
+        client.send(&format!("qRcmd,{}", to_hex(b"input 0")));
+        assert_eq!(vec!["W00"], client.send("c"));
+        assert_eq!(vec!["W00"], client.send("?"));
+
+        client.stream.write_all(&frame("k")).unwrap();
+        assert_eq!(ProgramState::Done, *stub.join().unwrap().state());
+    }
+
+    #[test]
+    fn errors_and_framing() {
+        let (mut client, stub) = Client::connect(vec![1, 0, 0, 0, 42]);
+
+        // A corrupt packet is rejected, and the stub waits for it to be resent.
+        client.stream.write_all(b"$g#00").unwrap();
+        assert_eq!(b'-', client.read_byte());
+
+        let replies = client.send("c");
+        assert_eq!(("Unknown opcode 42 at pc 4\n".to_string(), "S04"), (console(&replies), replies.last().unwrap().as_str()));
+
+        assert_eq!(vec![""], client.send("vMustReplyEmpty"));
+        assert_eq!(vec!["E01"], client.send("m12"));
+        assert_eq!(vec!["E01"], client.send("M0,2:01"));
+
+        // Lengths past the packet size, and ranges past the end of the address space.
+        assert_eq!(vec!["E01"], client.send("m0,801"));
+        assert_eq!(vec!["E01"], client.send("m0,ffffffffffffffff"));
+        assert_eq!(vec!["E01"], client.send("mfffffffffffffffc,8"));
+        assert_eq!(vec!["E01"], client.send("Mfffffffffffffffc,8:0100000000000000"));
+        assert_eq!(vec!["0000000000000000"], client.send("mfffffffffffffff0,8"));
+        assert_eq!(vec!["OK"], client.send("D"));
+
+        assert_eq!(4, stub.join().unwrap().pc());
+    }
+
+    #[test]
+    fn interrupts() {
+        // Jumps back to itself forever.
+        let (mut client, stub) = Client::connect(vec![1105, 1, 0]);
+
+        client.stream.write_all(&frame("vCont;c")).unwrap();
+        assert_eq!(b'+', client.read_byte());
+
+        client.stream.write_all(&[0x03]).unwrap();
+        assert_eq!("S02", client.read_reply());
+        assert_eq!(vec!["0000000000000000"], client.send("p20"));
+
+        // Interrupts while the program is stopped are ignored.
+        client.stream.write_all(&[0x03]).unwrap();
+        assert_eq!(vec!["S05"], client.send("s"));
+        assert_eq!(vec!["OK"], client.send("D"));
+
+        assert_eq!(ProgramState::Runnable, *stub.join().unwrap().state());
+    }
+}
@@ -0,0 +1,465 @@
+use std::ops::Range;
+
+use crate::search::{product, InputSearch};
+use crate::{Computer, ProgramState};
+
+// Goal seeking finds values to patch into a program's memory so that it leaves a target value at
+// an output address, like day 2's noun and verb:
+//
+//   let solution = GoalSeek::new(&computer, 0, 19690720)
+//       .patch(1, 0..100)
+//       .patch(2, 0..100)
+//       .seek();
+//
+// Before enumerating patches, the seeker probes the program to see whether the output is a linear
+// function of the patched values, or monotonic in one of them.  Linear outputs are solved directly
+// and monotonic outputs are binary searched, so only programs without either shape pay for a brute
+// force search.  Every solution is checked by running the program, so a probe that guesses the
+// shape wrong can miss solutions but never returns a wrong one - if a shaped search finds nothing,
+// the seeker falls back to brute force.  Each evaluation runs a fresh clone of the program as the
+// caller set it up, with its memory patches and queued input, and a patch that doesn't halt within
+// the instruction budget counts as a failure.
+
+/// Number of evenly spaced points sampled along a dimension when checking for monotonicity.
+const MONOTONIC_SAMPLES: usize = 9;
+
+/// Default number of instructions a patched program can run before it's treated as never halting.
+const DEFAULT_BUDGET: usize = 1_000_000;
+
+/// How a goal seek found its solutions.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Strategy {
+    /// The output is a linear function of the patched values, and was solved directly.
+    Linear,
+    /// The output is monotonic in one patched value, which was binary searched.
+    Monotonic,
+    /// Every combination of patched values was tried.
+    BruteForce,
+}
+
+/// Patched values that leave the target value at the output address, in the order the patches
+/// were added, and the strategy that found them.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Solution {
+    pub values: Vec<i64>,
+    pub strategy: Strategy,
+}
+
+/// A memory address, and the range of values to try patching into it.
+#[derive(Debug, Clone)]
+struct Patch {
+    addr: usize,
+    values: Range<i64>,
+}
+
+impl Patch {
+    fn len(&self) -> i64 {
+        (self.values.end - self.values.start).max(0)
+    }
+}
+
+/// Search for memory patches that make a program leave a target value at an output address.
+pub struct GoalSeek<'a> {
+    program: &'a Computer,
+    patches: Vec<Patch>,
+    output: usize,
+    target: i64,
+    budget: usize,
+}
+
+impl<'a> GoalSeek<'a> {
+    /// Constructs a goal seek for patches that leave target at the output address when the
+    /// program halts.
+    pub fn new(program: &'a Computer, output: usize, target: i64) -> GoalSeek<'a> {
+        GoalSeek { program, patches: Vec::new(), output, target, budget: DEFAULT_BUDGET }
+    }
+
+    /// Adds an address to patch, and the values to try at it.
+    pub fn patch(mut self, addr: usize, values: Range<i64>) -> Self {
+        self.patches.push(Patch { addr, values });
+        self
+    }
+
+    /// Sets the number of instructions a patched program can run before the seeker gives up on it,
+    /// so patches that make the program loop forever don't hang the search.
+    pub fn with_budget(mut self, budget: usize) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Patches the values into a fresh copy of the program, runs it until it halts, and returns the
+    /// value at the output address.  Returns None if the program fails, waits for input, or runs out
+    /// of its instruction budget.
+    pub fn evaluate(&self, values: &[i64]) -> Option<i64> {
+        let mut computer = self.program.clone();
+        for (patch, &value) in self.patches.iter().zip(values) {
+            computer.memory.set(patch.addr, value).ok()?;
+        }
+
+        for _ in 0..self.budget {
+            if !computer.is_runnable() {
+                break;
+            }
+
+            computer.step().ok()?;
+        }
+
+        Some(computer.memory.get(self.output)).filter(|_| computer.state() == &ProgramState::Done)
+    }
+
+    /// Returns a set of patched values that reach the target, or None if there aren't any.
+    pub fn seek(&self) -> Option<Solution> {
+        let (strategy, mut found) = self.search(true);
+        found.pop().map(|values| Solution { values, strategy })
+    }
+
+    /// Returns the sets of patched values that reach the target, sorted, and the strategy that found
+    /// them.  These are all of the solutions the detected strategy finds.  Linear and monotonic
+    /// shapes are only probed at a few points, so a program that fools the probes can have
+    /// solutions that aren't returned.  Brute force only runs if the shaped strategies find nothing.
+    pub fn seek_all(&self) -> (Strategy, Vec<Vec<i64>>) {
+        let (strategy, mut found) = self.search(false);
+        found.sort();
+        (strategy, found)
+    }
+
+    /// Finds solutions with the cheapest strategy that the program's shape allows.
+    fn search(&self, first_only: bool) -> (Strategy, Vec<Vec<i64>>) {
+        if self.patches.iter().any(|patch| patch.len() == 0) {
+            return (Strategy::BruteForce, Vec::new());
+        }
+
+        if let Some(model) = self.linear_model() {
+            let found = self.solve_linear(&model, first_only);
+            if !found.is_empty() {
+                return (Strategy::Linear, found);
+            }
+        }
+
+        if let Some(dim) = self.monotonic_dimension() {
+            let found = self.solve_monotonic(dim, first_only);
+            if !found.is_empty() {
+                return (Strategy::Monotonic, found);
+            }
+        }
+
+        (Strategy::BruteForce, self.brute_force(first_only))
+    }
+
+    /// Returns the patched values at the start of each range, offset by the given amounts.
+    fn at(&self, offsets: &[i64]) -> Vec<i64> {
+        self.patches.iter().zip(offsets).map(|(patch, offset)| patch.values.start + offset).collect()
+    }
+
+    /// Returns true if the patched values are a solution.
+    fn is_solution(&self, values: &[i64]) -> bool {
+        self.evaluate(values) == Some(self.target)
+    }
+
+    /// Probes the program for an output of the form base + sum(coefficient * offset), where offset
+    /// is each value's distance from the start of its range.  Returns the base and coefficients.
+    fn linear_model(&self) -> Option<LinearModel> {
+        let dims = self.patches.len();
+        let base = self.evaluate(&self.at(&vec![0; dims]))? as i128;
+
+        let mut coefficients = Vec::with_capacity(dims);
+        for dim in 0..dims {
+            let coefficient = if self.patches[dim].len() > 1 {
+                let mut offsets = vec![0; dims];
+                offsets[dim] = 1;
+                self.evaluate(&self.at(&offsets))? as i128 - base
+            } else {
+                0
+            };
+
+            coefficients.push(coefficient);
+        }
+
+        let model = LinearModel { base, coefficients };
+
+        // Check the model at the far end of each range, at the far corner, and in the middle.
+        let ends: Vec<i64> = self.patches.iter().map(|patch| patch.len() - 1).collect();
+        let mut probes = vec![ends.clone(), ends.iter().map(|end| end / 2).collect()];
+        for dim in 0..dims {
+            let mut offsets = vec![0; dims];
+            offsets[dim] = ends[dim];
+            probes.push(offsets);
+        }
+
+        for offsets in probes {
+            if self.evaluate(&self.at(&offsets)).map(i128::from) != Some(model.predict(&offsets)) {
+                return None;
+            }
+        }
+
+        Some(model)
+    }
+
+    /// Solves the linear model for the target, checking each candidate by running the program.
+    fn solve_linear(&self, model: &LinearModel, first_only: bool) -> Vec<Vec<i64>> {
+        // Assign the values with the largest coefficients first - they narrow the rest the most.
+        let mut order: Vec<usize> = (0..self.patches.len()).collect();
+        order.sort_by_key(|&dim| std::cmp::Reverse(model.coefficients[dim].abs()));
+
+        let mut found = Vec::new();
+        let mut offsets = vec![0; self.patches.len()];
+        let remaining = self.target as i128 - model.base;
+
+        self.assign_linear(model, &order, remaining, &mut offsets, first_only, &mut found);
+        found
+    }
+
+    /// Assigns offsets to the dimensions in order so that the coefficients times the offsets add up
+    /// to remaining, skipping offsets that leave the rest of the dimensions unable to make up the
+    /// difference.  Returns true once the search should stop.
+    fn assign_linear(&self, model: &LinearModel, order: &[usize], remaining: i128, offsets: &mut Vec<i64>,
+                     first_only: bool, found: &mut Vec<Vec<i64>>) -> bool {
+        let (dim, rest) = match order.split_first() {
+            Some(split) => split,
+            None => {
+                if remaining == 0 {
+                    let values = self.at(offsets);
+                    if self.is_solution(&values) {
+                        found.push(values);
+                        return first_only;
+                    }
+                }
+
+                return false;
+            }
+        };
+
+        // Range of sums that the remaining dimensions can make.
+        let (low, high) = rest.iter().fold((0, 0), |(low, high), &dim| {
+            let end = model.coefficients[dim] * (self.patches[dim].len() - 1) as i128;
+            (low + end.min(0), high + end.max(0))
+        });
+
+        let coefficient = model.coefficients[*dim];
+        let end = (self.patches[*dim].len() - 1) as i128;
+        let (first, last) = match coefficient {
+            0 => (0, end),
+            c if c > 0 => (ceil_div(remaining - high, c), floor_div(remaining - low, c)),
+            c => (ceil_div(remaining - low, c), floor_div(remaining - high, c)),
+        };
+
+        for offset in first.max(0)..=last.min(end) {
+            offsets[*dim] = offset as i64;
+            if self.assign_linear(model, rest, remaining - coefficient * offset, offsets, first_only, found) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Returns the largest dimension that the output looks monotonic in, sampling it at a few points
+    /// with the other values at the start and end of their ranges.
+    fn monotonic_dimension(&self) -> Option<usize> {
+        let dims = self.patches.len();
+        let ends: Vec<i64> = self.patches.iter().map(|patch| patch.len() - 1).collect();
+
+        let mut candidates: Vec<usize> = (0..dims).filter(|&dim| ends[dim] > 0).collect();
+        candidates.sort_by_key(|&dim| std::cmp::Reverse(ends[dim]));
+
+        candidates.into_iter().find(|&dim| {
+            [vec![0; dims], ends.clone()].iter().all(|corner| {
+                let samples = (0..MONOTONIC_SAMPLES)
+                    .map(|i| {
+                        let mut offsets = corner.clone();
+                        offsets[dim] = ends[dim] * i as i64 / (MONOTONIC_SAMPLES - 1) as i64;
+                        self.evaluate(&self.at(&offsets))
+                    })
+                    .collect::<Option<Vec<i64>>>();
+
+                samples.is_some_and(|samples| {
+                    samples.windows(2).all(|w| w[0] <= w[1]) || samples.windows(2).all(|w| w[0] >= w[1])
+                })
+            })
+        })
+    }
+
+    /// Binary searches the monotonic dimension for every combination of the other values.
+    fn solve_monotonic(&self, dim: usize, first_only: bool) -> Vec<Vec<i64>> {
+        let others: Vec<Range<i64>> = self.patches.iter().enumerate()
+            .map(|(i, patch)| if i == dim { 0..1 } else { patch.values.clone() })
+            .collect();
+
+        let solve_slice = |_: &mut Computer, slice: &[i64]| -> Option<Vec<Vec<i64>>> {
+            let value_at = |value: i64| {
+                let mut values = slice.to_vec();
+                values[dim] = value;
+                self.evaluate(&values)
+            };
+
+            let Range { start, end } = self.patches[dim].values;
+            let ascending = value_at(start)? <= value_at(end - 1)?;
+
+            // First value whose output isn't before the target in the direction of the slice.
+            let (mut low, mut high) = (start, end);
+            while low < high {
+                let mid = low + (high - low) / 2;
+                let output = value_at(mid)?;
+                if (ascending && output < self.target) || (!ascending && output > self.target) {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+
+            let mut found = Vec::new();
+            for value in low..end {
+                if value_at(value)? != self.target {
+                    break;
+                }
+
+                let mut values = slice.to_vec();
+                values[dim] = value;
+                found.push(values);
+
+                if first_only {
+                    break;
+                }
+            }
+
+            Some(found).filter(|found| !found.is_empty())
+        };
+
+        let search = InputSearch::new(self.program, product(&others));
+        if first_only {
+            search.find_any(solve_slice).map(|(_, found)| found).unwrap_or_default()
+        } else {
+            // InputSearch::all only keeps the slices, so solve the matching slices again for their values.
+            search.all(|computer, slice| solve_slice(computer, slice).is_some())
+                .into_iter()
+                .flat_map(|slice| solve_slice(&mut self.program.clone(), &slice).unwrap_or_default())
+                .collect()
+        }
+    }
+
+    /// Tries every combination of patched values.
+    fn brute_force(&self, first_only: bool) -> Vec<Vec<i64>> {
+        let ranges: Vec<Range<i64>> = self.patches.iter().map(|patch| patch.values.clone()).collect();
+        let search = InputSearch::new(self.program, product(&ranges));
+        let matches = |_: &mut Computer, values: &[i64]| self.is_solution(values);
+
+        if first_only {
+            search.find_any(|computer, values| Some(()).filter(|_| matches(computer, values)))
+                .map(|(values, _)| vec![values])
+                .unwrap_or_default()
+        } else {
+            search.all(matches)
+        }
+    }
+}
+
+/// Output modelled as base + the sum of each coefficient times its value's offset into its range.
+struct LinearModel {
+    base: i128,
+    coefficients: Vec<i128>,
+}
+
+impl LinearModel {
+    fn predict(&self, offsets: &[i64]) -> i128 {
+        self.base + self.coefficients.iter().zip(offsets).map(|(c, &offset)| c * offset as i128).sum::<i128>()
+    }
+}
+
+fn floor_div(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) { q - 1 } else { q }
+}
+
+fn ceil_div(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if a % b != 0 && (a < 0) == (b < 0) { q + 1 } else { q }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear() {
+        // [0] = [14] * 100 + [15] + 7
+        let computer = Computer::new(vec![1002, 14, 100, 13, 1, 13, 15, 13, 1001, 13, 7, 0, 99, 0, 0, 0]);
+        let seek = GoalSeek::new(&computer, 0, 4242).patch(14, 0..100).patch(15, 0..100);
+
+        assert_eq!(Some(Solution { values: vec![42, 35], strategy: Strategy::Linear }), seek.seek());
+        assert_eq!((Strategy::Linear, vec![vec![42, 35]]), seek.seek_all());
+
+        // A wider range for [15] has a second solution.
+        let seek = GoalSeek::new(&computer, 0, 4242).patch(14, 0..100).patch(15, 0..200);
+        assert_eq!((Strategy::Linear, vec![vec![41, 135], vec![42, 35]]), seek.seek_all());
+    }
+
+    #[test]
+    fn monotonic() {
+        // [0] = [5] * [5]
+        let computer = Computer::new(vec![2, 5, 5, 0, 99, 0]);
+        let seek = GoalSeek::new(&computer, 0, 1024).patch(5, 0..1000);
+
+        assert_eq!(Some(Solution { values: vec![32], strategy: Strategy::Monotonic }), seek.seek());
+        assert_eq!((Strategy::BruteForce, vec![]), GoalSeek::new(&computer, 0, 1025).patch(5, 0..1000).seek_all());
+    }
+
+    #[test]
+    fn brute_force() {
+        // [0] = [14] * [14] * [15] * [15], which isn't monotonic in either value.
+        let computer = Computer::new(vec![2, 14, 14, 0, 2, 15, 15, 13, 2, 0, 13, 0, 99, 0, 0, 0]);
+        let (strategy, found) = GoalSeek::new(&computer, 0, 9).patch(14, -5..5).patch(15, -1..2).seek_all();
+
+        assert_eq!(Strategy::BruteForce, strategy);
+        assert_eq!(vec![vec![-3, -1], vec![-3, 1], vec![3, -1], vec![3, 1]], found);
+    }
+
+    #[test]
+    fn fooled_probes() {
+        // [0] = [21] + 4 * ([21] == 3), which is linear everywhere the probes look.
+        let mut program = vec![1008, 21, 3, 20, 1002, 20, 4, 20, 1, 21, 20, 0, 99];
+        program.resize(22, 0);
+        let computer = Computer::new(program);
+        let seek = GoalSeek::new(&computer, 0, 7).patch(21, 0..10);
+
+        assert_eq!(Some(7), seek.evaluate(&[3]));
+        assert_eq!((Strategy::Linear, vec![vec![7]]), seek.seek_all());
+    }
+
+    #[test]
+    fn patched_template() {
+        // [0] = [5] + [6], with [6] patched to 100 on the template before seeking.
+        let mut computer = Computer::new(vec![1, 5, 6, 0, 99, 0, 0]);
+        computer.memory.set(6, 100).unwrap();
+
+        assert_eq!(Some(Solution { values: vec![5], strategy: Strategy::Linear }),
+                   GoalSeek::new(&computer, 0, 105).patch(5, 0..10).seek());
+        assert_eq!(None, GoalSeek::new(&computer, 0, 5).patch(5, 0..10).seek());
+
+        // Input queued on the template is read by every evaluation: [0] = input + [5]
+        let mut computer = Computer::new(vec![3, 0, 1, 0, 7, 0, 99, 0]);
+        computer.input(40);
+        assert_eq!(Some(Solution { values: vec![2], strategy: Strategy::Linear }),
+                   GoalSeek::new(&computer, 0, 42).patch(7, 0..10).seek());
+    }
+
+    #[test]
+    fn looping_patches() {
+        // Patching [4] to 0 turns the halt into a jump back to the start, which loops forever.
+        let computer = Computer::new(vec![1105, 1, 4, 0, 99]);
+        let seek = GoalSeek::new(&computer, 0, 1105).patch(4, 0..100).with_budget(1000);
+
+        assert_eq!(None, seek.evaluate(&[0]));
+        assert_eq!(Some(1105), seek.evaluate(&[99]));
+        assert_eq!(vec![vec![99]], seek.seek_all().1);
+    }
+
+    #[test]
+    fn failing_patches() {
+        // Patching [0] changes the first instruction - most opcodes fail or don't halt.
+        let computer = Computer::new(vec![1, 0, 0, 0, 99]);
+
+        assert_eq!(None, GoalSeek::new(&computer, 0, 5).patch(0, 3..8).seek());
+        assert_eq!(Some(Solution { values: vec![1], strategy: Strategy::BruteForce }),
+                   GoalSeek::new(&computer, 0, 2).patch(0, 0..8).seek());
+    }
+}
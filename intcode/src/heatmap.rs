@@ -0,0 +1,396 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::error::IntcodeError;
+use crate::transpile::Decoded;
+use crate::{Computer, ProgramState};
+
+// A Heatmap counts how often a program reads, writes and executes each address, to map out where
+// a program keeps its code, stack and data.  Running a computer through a Heatmap instead of
+// calling run records every access.  The counts export as a CSV with a row per address, or as an
+// image with a pixel per address, laid out in rows: a grayscale PGM of how often each address was
+// touched, or a PPM that also colors addresses by region.
+//
+//   let mut heatmap = Heatmap::new();
+//   heatmap.run(&mut computer)?;
+//   heatmap.save_csv("heatmap.csv")?;
+//   heatmap.save_ppm("heatmap.ppm", 64, 4)?;
+//
+// Addresses that were part of an executed instruction are code, addresses that were only accessed
+// through relative mode parameters are stack, and everything else that was accessed is data.
+// Parameters that point at negative addresses aren't recorded.
+
+/// Most pixels an image can have, so a program that touches a huge address can't exhaust memory.
+pub const MAX_PIXELS: usize = 1 << 24;
+
+/// The ways the program touched an address.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Access {
+    /// Reads through position or relative mode parameters.
+    pub reads: usize,
+    /// Writes through position or relative mode parameters.
+    pub writes: usize,
+    /// Times the address was fetched as part of an instruction that ran: its opcode or a parameter.
+    pub executes: usize,
+    /// Reads and writes that went through relative mode parameters.
+    pub relative: usize,
+}
+
+impl Access {
+    /// Returns the total number of times the address was touched.
+    pub fn total(&self) -> usize {
+        self.reads + self.writes + self.executes
+    }
+
+    /// Returns the region the address belongs to.
+    pub fn region(&self) -> Region {
+        if self.executes > 0 {
+            Region::Code
+        } else if self.relative == self.reads + self.writes {
+            Region::Stack
+        } else {
+            Region::Data
+        }
+    }
+}
+
+/// What a program uses an address for.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Region {
+    Code,
+    Stack,
+    Data,
+}
+
+impl Region {
+    /// Returns the name of the region, as it appears in the CSV.
+    pub fn name(self) -> &'static str {
+        match self {
+            Region::Code => "code",
+            Region::Stack => "stack",
+            Region::Data => "data",
+        }
+    }
+
+    /// Returns the color the region is drawn with at full brightness.
+    fn color(self) -> [u8; 3] {
+        match self {
+            Region::Code => [255, 96, 32],
+            Region::Stack => [64, 255, 64],
+            Region::Data => [64, 160, 255],
+        }
+    }
+}
+
+/// Why the accesses can't be rendered as an image.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ImageError {
+    /// The width or scale was 0.
+    Empty { width: usize, scale: usize },
+    /// The image would have more than MAX_PIXELS pixels.
+    TooLarge { addresses: usize, scale: usize },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Empty { width, scale } => write!(f, "image width ({}) and scale ({}) must be at least 1", width, scale),
+            ImageError::TooLarge { addresses, scale } => {
+                write!(f, "{} addresses at scale {} is more than {} pixels", addresses, scale, MAX_PIXELS)
+            }
+        }
+    }
+}
+
+impl Error for ImageError {}
+
+impl From<ImageError> for io::Error {
+    fn from(e: ImageError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, e)
+    }
+}
+
+/// Access counts for each address a program touched.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Heatmap {
+    accesses: BTreeMap<usize, Access>,
+}
+
+impl Heatmap {
+    /// Constructs an empty heatmap.
+    pub fn new() -> Heatmap {
+        Heatmap::default()
+    }
+
+    /// Runs the next instruction in the computer, recording the addresses it touched if it ran.
+    /// An input instruction that's waiting for input hasn't run yet.  Custom opcodes are recorded
+    /// as executing their opcode, since their parameters aren't known.
+    pub fn step(&mut self, computer: &mut Computer) -> Result<(), IntcodeError> {
+        let pc = computer.pc();
+        let relative_base = computer.relative_base();
+        let words: Vec<i64> = (pc..pc.saturating_add(4)).map(|addr| computer.memory.peek(addr)).collect();
+
+        computer.step()?;
+
+        if *computer.state() == ProgramState::WaitingForInput && computer.pc() == pc {
+            return Ok(());
+        }
+
+        let decoded = match Decoded::parse(&words, 0) {
+            Some(decoded) => decoded,
+            None => {
+                self.access(pc).executes += 1;
+                return Ok(());
+            }
+        };
+
+        (pc..=pc.saturating_add(decoded.operands.len())).for_each(|addr| self.access(addr).executes += 1);
+
+        let writes = match decoded.opcode {
+            1 | 2 | 7 | 8 => Some(2),
+            3 => Some(0),
+            _ => None,
+        };
+
+        for (i, operand) in decoded.operands.iter().enumerate() {
+            let addr = match operand.mode {
+                0 => Some(operand.value),
+                2 => relative_base.checked_add(operand.value),
+                _ => continue,
+            };
+
+            let addr = match addr.and_then(|addr| usize::try_from(addr).ok()) {
+                Some(addr) => addr,
+                None => continue,
+            };
+
+            let access = self.access(addr);
+            if writes == Some(i) {
+                access.writes += 1;
+            } else {
+                access.reads += 1;
+            }
+
+            if operand.mode == 2 {
+                access.relative += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the program in the computer until it either halts or blocks waiting for input, recording
+    /// every access.  Stops at the instruction that caused an error if the program can't be executed.
+    pub fn run(&mut self, computer: &mut Computer) -> Result<(), IntcodeError> {
+        while computer.is_runnable() {
+            self.step(computer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds the accesses from another run to this one.
+    pub fn merge(&mut self, other: &Heatmap) {
+        for (&addr, other) in &other.accesses {
+            let access = self.access(addr);
+            access.reads += other.reads;
+            access.writes += other.writes;
+            access.executes += other.executes;
+            access.relative += other.relative;
+        }
+    }
+
+    /// Returns the accesses to the given address, or None if the program never touched it.
+    pub fn get(&self, addr: usize) -> Option<Access> {
+        self.accesses.get(&addr).copied()
+    }
+
+    /// Returns the accesses to each address the program touched, in address order.
+    pub fn accesses(&self) -> impl Iterator<Item = (usize, Access)> + '_ {
+        self.accesses.iter().map(|(&addr, &access)| (addr, access))
+    }
+
+    /// Returns the counts for an address, adding it if it hasn't been touched.
+    fn access(&mut self, addr: usize) -> &mut Access {
+        self.accesses.entry(addr).or_default()
+    }
+
+    /// Renders the accesses as CSV, with a header and a row for each address that was touched.
+    pub fn to_csv(&self) -> String {
+        let mut csv = "address,reads,writes,executes,relative,region\n".to_string();
+        for (addr, access) in self.accesses() {
+            writeln!(csv, "{},{},{},{},{},{}", addr, access.reads, access.writes, access.executes, access.relative, access.region().name()).unwrap();
+        }
+
+        csv
+    }
+
+    /// Writes the accesses to the given file as CSV.
+    pub fn save_csv<P: AsRef<Path>>(&self, filename: P) -> io::Result<()> {
+        save(filename, self.to_csv().as_bytes())
+    }
+
+    /// Renders the accesses as a binary PGM image, with width addresses on each row, from address 0
+    /// through the highest address that was touched.  Each address is a square of scale pixels, and
+    /// is brighter the more it was touched.  Addresses that were never touched are black.  Fails if
+    /// width or scale is 0, or the image would have more than MAX_PIXELS pixels.
+    pub fn to_pgm(&self, width: usize, scale: usize) -> Result<Vec<u8>, ImageError> {
+        self.image("P5", width, scale, |_, brightness| vec![(brightness * 255.0).round() as u8])
+    }
+
+    /// Writes the accesses to the given file as a PGM image.
+    pub fn save_pgm<P: AsRef<Path>>(&self, filename: P, width: usize, scale: usize) -> io::Result<()> {
+        save(filename, &self.to_pgm(width, scale)?)
+    }
+
+    /// Renders the accesses as a binary PPM image laid out like to_pgm, with code in orange, stack in
+    /// green and data in blue.
+    pub fn to_ppm(&self, width: usize, scale: usize) -> Result<Vec<u8>, ImageError> {
+        self.image("P6", width, scale, |access, brightness| match access {
+            Some(access) => access.region().color().iter().map(|&channel| (f64::from(channel) * brightness).round() as u8).collect(),
+            None => vec![0, 0, 0],
+        })
+    }
+
+    /// Writes the accesses to the given file as a PPM image.
+    pub fn save_ppm<P: AsRef<Path>>(&self, filename: P, width: usize, scale: usize) -> io::Result<()> {
+        save(filename, &self.to_ppm(width, scale)?)
+    }
+
+    /// Renders an image with a pixel for each address, given the pixel for an address's accesses and
+    /// brightness.  Brightness goes from 0 for untouched addresses to 1 for the most touched address,
+    /// on a log scale so addresses that are touched a few times are still visible next to a loop.
+    fn image<F: Fn(Option<Access>, f64) -> Vec<u8>>(&self, format: &str, width: usize, scale: usize, pixel: F) -> Result<Vec<u8>, ImageError> {
+        if width == 0 || scale == 0 {
+            return Err(ImageError::Empty { width, scale });
+        }
+
+        let len = self.accesses.keys().next_back().map_or(0, |&max| max.saturating_add(1));
+        let height = len.div_ceil(width);
+
+        let pixels = height.checked_mul(width).and_then(|addresses| addresses.checked_mul(scale)).and_then(|pixels| pixels.checked_mul(scale));
+        if !matches!(pixels, Some(pixels) if pixels <= MAX_PIXELS) {
+            return Err(ImageError::TooLarge { addresses: len, scale });
+        }
+        let most = self.accesses.values().map(Access::total).max().unwrap_or(0);
+
+        let pixels: Vec<Vec<u8>> = (0..height * width)
+            .map(|addr| {
+                let access = self.get(addr);
+                let brightness = access.map_or(0.0, |access| ((access.total() + 1) as f64).ln() / ((most + 1) as f64).ln());
+                pixel(access, brightness)
+            })
+            .collect();
+
+        let mut image = format!("{}\n{} {}\n255\n", format, width * scale, height * scale).into_bytes();
+        for row in pixels.chunks(width) {
+            for _ in 0..scale {
+                for pixel in row {
+                    (0..scale).for_each(|_| image.extend_from_slice(pixel));
+                }
+            }
+        }
+
+        Ok(image)
+    }
+}
+
+/// Writes the contents to the given file.
+fn save<P: AsRef<Path>>(filename: P, contents: &[u8]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writer.write_all(contents)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accesses() {
+        // Stores its input at 16, copies it onto the stack at 20, and outputs the sum of both copies from 17.
+        let program = vec![109, 20, 3, 16, 21001, 16, 0, 0, 201, 0, 16, 17, 4, 17, 99];
+        let mut computer = Computer::new(program).with_strict_writes();
+        computer.input(5);
+
+        let mut heatmap = Heatmap::new();
+        heatmap.run(&mut computer).unwrap();
+        assert_eq!(Some(10), computer.last_output());
+
+        assert_eq!(Some(Access { reads: 0, writes: 0, executes: 1, relative: 0 }), heatmap.get(0));
+        assert_eq!(Some(Access { reads: 2, writes: 1, executes: 0, relative: 0 }), heatmap.get(16));
+        assert_eq!(Some(Access { reads: 1, writes: 1, executes: 0, relative: 2 }), heatmap.get(20));
+        assert_eq!(None, heatmap.get(15));
+
+        assert_eq!(Region::Code, heatmap.get(14).unwrap().region());
+        assert_eq!(Region::Data, heatmap.get(16).unwrap().region());
+        assert_eq!(Region::Stack, heatmap.get(20).unwrap().region());
+
+        let csv = heatmap.to_csv();
+        assert!(csv.starts_with("address,reads,writes,executes,relative,region\n0,0,0,1,0,code\n"));
+        assert!(csv.ends_with("\n17,1,1,0,0,data\n20,1,1,0,2,stack\n"));
+    }
+
+    #[test]
+    fn waiting_for_input() {
+        let mut computer = Computer::new(vec![3, 5, 4, 5, 99]);
+        let mut heatmap = Heatmap::new();
+
+        heatmap.run(&mut computer).unwrap();
+        assert_eq!(None, heatmap.get(0));
+
+        computer.input(1);
+        heatmap.run(&mut computer).unwrap();
+        assert_eq!(Some(Access { reads: 1, writes: 1, executes: 0, relative: 0 }), heatmap.get(5));
+
+        let mut merged = heatmap.clone();
+        merged.merge(&heatmap);
+        assert_eq!(Some(Access { reads: 2, writes: 2, executes: 0, relative: 0 }), merged.get(5));
+    }
+
+    #[test]
+    fn images() {
+        let mut computer = Computer::new(vec![3, 5, 4, 5, 99]);
+        computer.input(1);
+        let mut heatmap = Heatmap::new();
+        heatmap.run(&mut computer).unwrap();
+
+        // 6 addresses in rows of 4.  Address 5 was touched twice, and the code once each.
+        let pgm = heatmap.to_pgm(4, 1).unwrap();
+        assert!(pgm.starts_with(b"P5\n4 2\n255\n"));
+        assert_eq!(&[161, 161, 161, 161, 161, 255, 0, 0], &pgm[11..]);
+
+        let ppm = heatmap.to_ppm(4, 2).unwrap();
+        assert!(ppm.starts_with(b"P6\n8 4\n255\n"));
+        assert_eq!(11 + 8 * 4 * 3, ppm.len());
+        assert_eq!(&[161, 61, 20], &ppm[11..14]);
+
+        // Address 5 is data, in the second column of the second row.
+        let pixel = 11 + (2 * 8 + 2) * 3;
+        assert_eq!(&[64, 160, 255], &ppm[pixel..pixel + 3]);
+
+        assert_eq!(Err(ImageError::Empty { width: 0, scale: 1 }), heatmap.to_pgm(0, 1));
+        assert_eq!(Err(ImageError::Empty { width: 4, scale: 0 }), heatmap.to_ppm(4, 0));
+        assert_eq!(Err(ImageError::TooLarge { addresses: 6, scale: 4096 }), heatmap.to_pgm(4, 4096));
+    }
+
+    #[test]
+    fn huge_addresses() {
+        // Reads from address -1 through the relative base, and writes to address 2^40.
+        let program = vec![109, -5, 1201, 4, 0, 1099511627776, 99];
+        let mut computer = Computer::new(program);
+        let mut heatmap = Heatmap::new();
+        heatmap.run(&mut computer).unwrap();
+
+        assert_eq!(vec![0, 1, 2, 3, 4, 5, 6, 1 << 40], heatmap.accesses().map(|(addr, _)| addr).collect::<Vec<_>>());
+        assert_eq!(Some(Access { reads: 0, writes: 1, executes: 0, relative: 0 }), heatmap.get(1 << 40));
+        assert_eq!(Err(ImageError::TooLarge { addresses: (1 << 40) + 1, scale: 1 }), heatmap.to_ppm(64, 1));
+    }
+}
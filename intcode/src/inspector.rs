@@ -0,0 +1,634 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use crate::error::IntcodeError;
+use crate::memory::WriteWatch;
+use crate::transpile::Decoded;
+use crate::{Computer, ProgramState};
+
+// The inspector is a full screen view of a computer while it runs: the disassembly around pc, the
+// registers, a scrollable view of memory that highlights recent writes, and the input and output
+// queues.  The program can be run, paused, and stepped one instruction at a time.
+//
+//   let computer = Computer::load("input.txt")?;
+//   let computer = intcode::inspector::inspect(computer);
+//
+// Keys: space runs or pauses, 's' steps, '+' and '-' change the speed, the arrow and page keys
+// scroll memory, 'p' scrolls memory to pc, 'h' switches memory between hex and decimal, 'i' queues
+// input, 'r' resets the program and 'q' quits.
+//
+// The curses front end needs the 'inspector' feature.  Session holds the state behind it, and
+// renders each pane as lines of styled text, so it doesn't need a terminal.
+
+/// Values shown on each row of the memory pane.
+const MEMORY_COLUMNS: usize = 8;
+
+/// Writes stay highlighted for this many steps.
+const RECENT_STEPS: usize = 64;
+
+/// Number of previously run instructions shown above pc in the disassembly.
+const HISTORY: usize = 4;
+
+/// Instructions run per frame, and milliseconds between frames, from slowest to fastest.
+const SPEEDS: [(usize, u64); 6] = [(1, 1000), (1, 250), (1, 50), (10, 50), (100, 30), (1000, 30)];
+
+/// How a span of text should be drawn.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Style {
+    Normal,
+    /// Instructions that ran before the current one.
+    Faded,
+    /// The instruction at pc.
+    Current,
+    /// An address written in the last few steps.
+    Recent,
+    /// An address written by the last step.
+    Latest,
+}
+
+/// Text drawn in a single style.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+impl Span {
+    fn new(text: String, style: Style) -> Span {
+        Span { text, style }
+    }
+}
+
+/// A line in a pane, made of styled spans.
+pub type Line = Vec<Span>;
+
+/// Something the user asked the inspector to do.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Command {
+    /// Runs the program if it's paused, or pauses it if it's running.
+    RunPause,
+    /// Pauses the program and runs a single instruction.
+    Step,
+    Faster,
+    Slower,
+    /// Scrolls memory by the given number of rows, down if positive.
+    Scroll(isize),
+    /// Scrolls memory to the row containing pc.
+    ShowPc,
+    /// Switches memory between hex and decimal.
+    Hex,
+    /// Queues input: a number is a single value, and anything else is ASCII text followed by a newline.
+    Input(String),
+    /// Resets the program to its original state.
+    Reset,
+}
+
+/// State of an inspector: the computer, whether it's running, and what it's done recently.
+pub struct Session {
+    computer: Computer,
+    watch: Arc<WriteWatch>,
+    running: bool,
+    speed: usize,
+    steps: usize,
+    /// Addresses of the last few instructions that ran, oldest first.
+    history: VecDeque<usize>,
+    /// Step that last wrote each address.
+    writes: HashMap<usize, usize>,
+    /// First row shown in the memory pane.
+    scroll: usize,
+    hex: bool,
+    error: Option<IntcodeError>,
+}
+
+impl Session {
+    /// Constructs a paused session for the given computer.  The session watches every write to the
+    /// computer's memory until it ends.
+    pub fn new(mut computer: Computer) -> Session {
+        let watch = Arc::new(WriteWatch::default());
+        computer.memory.add_hook(0..usize::MAX, watch.clone());
+
+        Session {
+            computer,
+            watch,
+            running: false,
+            speed: 2,
+            steps: 0,
+            history: VecDeque::new(),
+            writes: HashMap::new(),
+            scroll: 0,
+            hex: false,
+            error: None,
+        }
+    }
+
+    /// Returns the computer being inspected.
+    pub fn computer(&self) -> &Computer {
+        &self.computer
+    }
+
+    /// Returns the computer being inspected, ending the session.  The computer keeps its own
+    /// hooks, but the session stops watching its writes.
+    pub fn into_computer(mut self) -> Computer {
+        self.computer.memory.remove_hook(&self.watch);
+        self.computer
+    }
+
+    /// Returns whether the program is running, rather than paused.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Returns the number of instructions run since the session started or was reset.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Returns the number of milliseconds between frames at the current speed.
+    pub fn frame_delay(&self) -> u64 {
+        SPEEDS[self.speed].1
+    }
+
+    /// Carries out the given command.
+    pub fn apply(&mut self, command: Command) {
+        match command {
+            Command::RunPause => self.running = !self.running && self.error.is_none(),
+            Command::Step => {
+                self.running = false;
+                self.step();
+            }
+            Command::Faster => self.speed = (self.speed + 1).min(SPEEDS.len() - 1),
+            Command::Slower => self.speed = self.speed.saturating_sub(1),
+            Command::Scroll(rows) => {
+                let last_row = self.computer.memory.to_program().len().saturating_sub(1) / MEMORY_COLUMNS;
+                let row = self.scroll as isize + rows;
+                self.scroll = row.max(0).min(last_row as isize) as usize;
+            }
+            Command::ShowPc => self.scroll = self.computer.pc() / MEMORY_COLUMNS,
+            Command::Hex => self.hex = !self.hex,
+            Command::Input(text) => {
+                let text = text.trim();
+                match text.parse::<i64>() {
+                    Ok(value) => self.computer.input(value),
+                    Err(_) if !text.is_empty() => self.computer.text_input(&format!("{}\n", text)),
+                    Err(_) => {}
+                }
+            }
+            Command::Reset => {
+                self.computer.reset();
+                self.watch.take_writes();
+                self.running = false;
+                self.steps = 0;
+                self.history.clear();
+                self.writes.clear();
+                self.error = None;
+            }
+        }
+    }
+
+    /// Runs one frame's worth of instructions if the program is running.  Pauses when the program
+    /// stops, either by halting, waiting for input, or failing.
+    pub fn tick(&mut self) {
+        for _ in 0..SPEEDS[self.speed].0 {
+            if !self.running {
+                break;
+            }
+
+            self.step();
+        }
+    }
+
+    /// Runs the instruction at pc, pausing if the program can't run.
+    fn step(&mut self) {
+        if !self.computer.is_runnable() || self.error.is_some() {
+            self.running = false;
+            return;
+        }
+
+        let pc = self.computer.pc();
+        if let Err(e) = self.computer.step() {
+            self.error = Some(e);
+            self.running = false;
+            return;
+        }
+
+        // An input instruction that's waiting for input hasn't run yet.
+        if *self.computer.state() == ProgramState::WaitingForInput && self.computer.pc() == pc {
+            self.running = false;
+            return;
+        }
+
+        self.steps += 1;
+        self.history.push_back(pc);
+        if self.history.len() > HISTORY {
+            self.history.pop_front();
+        }
+
+        for write in self.watch.take_writes() {
+            self.writes.insert(write.addr, self.steps);
+        }
+    }
+
+    /// Returns a description of what the program is doing.
+    pub fn status(&self) -> String {
+        if let Some(e) = &self.error {
+            return format!("failed: {}", e);
+        }
+
+        match self.computer.state() {
+            ProgramState::Done => "halted".to_string(),
+            ProgramState::WaitingForInput if !self.computer.has_input() => "waiting for input".to_string(),
+            _ if self.running => "running".to_string(),
+            _ => "paused".to_string(),
+        }
+    }
+
+    /// Returns the registers pane.
+    pub fn registers(&self) -> Vec<Line> {
+        let (steps, delay) = SPEEDS[self.speed];
+
+        vec![
+            format!("pc     {}", self.computer.pc()),
+            format!("rb     {}", self.computer.relative_base()),
+            format!("state  {:?}", self.computer.state()),
+            format!("steps  {}", self.steps),
+            format!("speed  {} every {}ms", steps, delay),
+            self.status(),
+        ].into_iter().map(|text| vec![Span::new(text, Style::Normal)]).collect()
+    }
+
+    /// Returns the disassembly pane: the last few instructions that ran, then the instruction at pc
+    /// and the ones after it.
+    pub fn disassembly(&self, rows: usize) -> Vec<Line> {
+        let pc = self.computer.pc();
+        let mut lines: Vec<Line> = self.history.iter()
+            .filter(|&&addr| addr != pc)
+            .map(|&addr| self.instruction_line(addr, Style::Faded).0)
+            .collect();
+
+        let mut addr = pc;
+        let mut style = Style::Current;
+        while lines.len() < rows {
+            let (line, next) = self.instruction_line(addr, style);
+            lines.push(line);
+            addr = next;
+            style = Style::Normal;
+        }
+
+        let skip = lines.len().saturating_sub(rows);
+        lines.split_off(skip)
+    }
+
+    /// Returns a line for the instruction at the given address, and the address after it.
+    fn instruction_line(&self, addr: usize, style: Style) -> (Line, usize) {
+        let code: Vec<i64> = (addr..addr + 4).map(|addr| self.computer.memory.peek(addr)).collect();
+        let marker = if style == Style::Current { ">" } else { " " };
+
+        let (text, next) = match Decoded::parse(&code, 0) {
+            Some(decoded) => (decoded.to_string(), addr + decoded.operands.len() + 1),
+            None => (format!("data {}", code[0]), addr + 1),
+        };
+
+        (vec![Span::new(format!("{} {:>6}  {}", marker, addr, text), style)], next)
+    }
+
+    /// Returns the memory pane, starting at the scrolled row.  The value at pc and values written
+    /// recently are highlighted.
+    pub fn memory(&self, rows: usize) -> Vec<Line> {
+        (self.scroll..self.scroll + rows).map(|row| {
+            let start = row * MEMORY_COLUMNS;
+            let mut line = vec![Span::new(self.format_addr(start), Style::Normal)];
+
+            for addr in start..start + MEMORY_COLUMNS {
+                let style = match self.writes.get(&addr) {
+                    _ if addr == self.computer.pc() => Style::Current,
+                    Some(&step) if step == self.steps => Style::Latest,
+                    Some(&step) if self.steps - step < RECENT_STEPS => Style::Recent,
+                    _ => Style::Normal,
+                };
+
+                line.push(Span::new(" ".to_string(), Style::Normal));
+                line.push(Span::new(self.format_value(self.computer.memory.peek(addr)), style));
+            }
+
+            line
+        }).collect()
+    }
+
+    fn format_addr(&self, addr: usize) -> String {
+        if self.hex {
+            format!("{:>6x}:", addr)
+        } else {
+            format!("{:>6}:", addr)
+        }
+    }
+
+    fn format_value(&self, value: i64) -> String {
+        if !self.hex {
+            format!("{:>8}", value)
+        } else if value < 0 {
+            format!("{:>8}", format!("-{:x}", value.unsigned_abs()))
+        } else {
+            format!("{:>8x}", value)
+        }
+    }
+
+    /// Returns the I/O pane: the input queue, the output queue, and the output as text if it's
+    /// all printable ASCII, with '|' for newlines.  Queues that don't fit in the width show their most recent values.
+    pub fn io(&self, width: usize) -> Vec<Line> {
+        let input = self.computer.input_queue();
+        let output = &self.computer.output;
+
+        let width = width.saturating_sub(12);
+        let mut lines = vec![
+            format!("in  ({:>4}) {}", input.len(), tail(input.iter().map(i64::to_string), width)),
+            format!("out ({:>4}) {}", output.len(), tail(output.iter().map(i64::to_string), width)),
+        ];
+
+        if !output.is_empty() && output.iter().all(|&value| value == 10 || (32..127).contains(&value)) {
+            let text: String = output.iter().map(|&value| if value == 10 { '|' } else { value as u8 as char }).collect();
+            let skip = text.len().saturating_sub(width);
+            lines.push(format!("text       {}", &text[skip..]));
+        }
+
+        lines.into_iter().map(|text| vec![Span::new(text, Style::Normal)]).collect()
+    }
+}
+
+/// Joins the values with spaces, keeping as many of the last values as fit in the width.
+fn tail<I: DoubleEndedIterator<Item = String>>(values: I, width: usize) -> String {
+    let mut kept = VecDeque::new();
+    let mut len = 0;
+
+    for value in values.rev() {
+        if len + value.len() + 1 > width.saturating_sub(4) {
+            kept.push_front("...".to_string());
+            break;
+        }
+
+        len += value.len() + 1;
+        kept.push_front(value);
+    }
+
+    kept.into_iter().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(feature = "inspector")]
+pub use self::ui::inspect;
+
+#[cfg(feature = "inspector")]
+mod ui {
+    use pancurses::{cbreak, curs_set, endwin, initscr, noecho, Input, Window, A_BOLD, A_DIM, A_REVERSE, A_UNDERLINE};
+
+    use super::{Command, Line, Session, Style};
+    use crate::Computer;
+
+    /// Width of the left column, which holds the registers and the disassembly.
+    const LEFT: i32 = 40;
+
+    /// Number of rows the registers pane uses.
+    const REGISTERS: i32 = 6;
+
+    /// Number of rows the I/O pane uses.
+    const IO: i32 = 3;
+
+    /// Runs the inspector on the given computer until the user quits, then returns the computer.
+    pub fn inspect(computer: Computer) -> Computer {
+        let mut session = Session::new(computer);
+
+        let window = initscr();
+        cbreak();
+        noecho();
+        curs_set(0);
+        window.keypad(true);
+
+        loop {
+            draw(&window, &session);
+            window.timeout(if session.is_running() { session.frame_delay() as i32 } else { -1 });
+
+            let (rows, _) = window.get_max_yx();
+            let page = (rows - REGISTERS - IO - 3).max(1) as isize;
+
+            let command = match window.getch() {
+                Some(Input::Character('q')) => break,
+                Some(Input::Character(' ')) => Some(Command::RunPause),
+                Some(Input::Character('s')) => Some(Command::Step),
+                Some(Input::Character('+')) | Some(Input::Character('=')) => Some(Command::Faster),
+                Some(Input::Character('-')) => Some(Command::Slower),
+                Some(Input::Character('p')) => Some(Command::ShowPc),
+                Some(Input::Character('h')) => Some(Command::Hex),
+                Some(Input::Character('r')) => Some(Command::Reset),
+                Some(Input::Character('i')) => prompt(&window).map(Command::Input),
+                Some(Input::KeyUp) => Some(Command::Scroll(-1)),
+                Some(Input::KeyDown) => Some(Command::Scroll(1)),
+                Some(Input::KeyPPage) => Some(Command::Scroll(-page)),
+                Some(Input::KeyNPage) => Some(Command::Scroll(page)),
+                _ => None,
+            };
+
+            if let Some(command) = command {
+                session.apply(command);
+            }
+
+            session.tick();
+        }
+
+        endwin();
+        session.into_computer()
+    }
+
+    /// Draws every pane.  The left column has the registers above the disassembly, memory is on the
+    /// right, and the I/O pane and key help are along the bottom.
+    fn draw(window: &Window, session: &Session) {
+        let (rows, cols) = window.get_max_yx();
+        let middle = (rows - REGISTERS - IO - 3).max(1);
+
+        window.erase();
+        draw_lines(window, 0, 0, LEFT, &session.registers());
+        window.mvaddstr(REGISTERS, 0, "-".repeat(cols as usize));
+        draw_lines(window, REGISTERS + 1, 0, LEFT, &session.disassembly(middle as usize));
+        draw_lines(window, 0, LEFT, cols - LEFT, &session.memory((REGISTERS + 1 + middle) as usize));
+        window.mvaddstr(REGISTERS + 1 + middle, 0, "-".repeat(cols as usize));
+        draw_lines(window, REGISTERS + 2 + middle, 0, cols, &session.io(cols as usize));
+        window.mvaddstr(rows - 1, 0, "space run/pause  s step  +/- speed  arrows/pgup/pgdn scroll  p pc  h hex  i input  r reset  q quit");
+        window.refresh();
+    }
+
+    /// Draws lines starting at the given row and column, cutting them off at the given width.
+    fn draw_lines(window: &Window, y: i32, x: i32, width: i32, lines: &[Line]) {
+        for (row, line) in lines.iter().enumerate() {
+            let mut col = 0;
+
+            for span in line {
+                let text: String = span.text.chars().take((width - col).max(0) as usize).collect();
+                let attributes = match span.style {
+                    Style::Normal => 0,
+                    Style::Faded => A_DIM,
+                    Style::Current => A_REVERSE,
+                    Style::Recent => A_UNDERLINE,
+                    Style::Latest => A_BOLD | A_UNDERLINE,
+                };
+
+                window.attron(attributes);
+                window.mvaddstr(y + row as i32, x + col, &text);
+                window.attroff(attributes);
+                col += text.chars().count() as i32;
+            }
+        }
+    }
+
+    /// Asks for a line of input on the bottom row.  Returns None if the user presses escape.
+    fn prompt(window: &Window) -> Option<String> {
+        let (rows, _) = window.get_max_yx();
+        let mut text = String::new();
+
+        window.timeout(-1);
+        curs_set(1);
+
+        let result = loop {
+            window.mv(rows - 1, 0);
+            window.clrtoeol();
+            window.mvaddstr(rows - 1, 0, format!("input> {}", text));
+            window.refresh();
+
+            match window.getch() {
+                Some(Input::Character('\n')) | Some(Input::KeyEnter) => break Some(text),
+                Some(Input::Character('\x1b')) => break None,
+                Some(Input::Character('\x7f')) | Some(Input::Character('\x08')) | Some(Input::KeyBackspace) => {
+                    text.pop();
+                }
+                Some(Input::Character(c)) if !c.is_control() => text.push(c),
+                _ => {}
+            }
+        };
+
+        curs_set(0);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(|line| line.iter().map(|span| span.text.as_str()).collect()).collect()
+    }
+
+    /// Reads n, then counts down from n to 1 in [16], outputting each value.
+    fn countdown() -> Computer {
+        Computer::new(vec![
+            3, 16,              // 0: in [16]
+            1006, 16, 15,       // 2: jz [16], 15
+            4, 16,              // 5: out [16]
+            1001, 16, -1, 16,   // 7: add [16], -1, [16]
+            1105, 1, 2,         // 11: jnz 1, 2
+            0,                  // 14
+            99,                 // 15: halt
+            0,                  // 16: n
+        ])
+    }
+
+    #[test]
+    fn step_and_run() {
+        let mut session = Session::new(countdown());
+
+        // Stepping an input instruction without input doesn't run it.
+        session.apply(Command::Step);
+        assert_eq!(0, session.steps());
+        assert_eq!("waiting for input", session.status());
+
+        session.apply(Command::Input("2".to_string()));
+        session.apply(Command::Step);
+        assert_eq!((1, 2), (session.steps(), session.computer().pc()));
+        assert_eq!("paused", session.status());
+
+        // Running stops when the program halts.
+        session.apply(Command::RunPause);
+        assert!(session.is_running());
+        for _ in 0..100 {
+            session.tick();
+        }
+
+        assert!(!session.is_running());
+        assert_eq!("halted", session.status());
+        assert_eq!(vec![2, 1], session.computer().output.iter().copied().collect::<Vec<_>>());
+
+        session.apply(Command::Reset);
+        assert_eq!((0, 0), (session.steps(), session.computer().pc()));
+    }
+
+    #[test]
+    fn into_computer() {
+        let mut computer = countdown();
+        computer.memory.protect(17..18);
+
+        let session = Session::new(computer);
+        let watch = session.watch.clone();
+        let mut computer = session.into_computer();
+
+        // The session's watch is gone, but the computer's own hook is still there.
+        computer.input(2);
+        computer.run();
+        assert!(watch.take_writes().is_empty());
+        assert!(computer.memory.set(17, 1).is_err());
+    }
+
+    #[test]
+    fn panes() {
+        let mut session = Session::new(countdown());
+        session.apply(Command::Input("3".to_string()));
+        for _ in 0..4 {
+            session.apply(Command::Step);
+        }
+
+        assert_eq!(vec![
+            "pc     11",
+            "rb     0",
+            "state  Runnable",
+            "steps  4",
+            "speed  1 every 50ms",
+            "paused",
+        ], text(&session.registers()));
+
+        let disassembly = session.disassembly(6);
+        assert_eq!(vec![
+            "       0  in [16]",
+            "       2  jz [16], 15",
+            "       5  out [16]",
+            "       7  add [16], -1, [16]",
+            ">     11  jnz 1, 2",
+            "      14  data 0",
+        ], text(&disassembly));
+        assert_eq!(Style::Faded, disassembly[0][0].style);
+        assert_eq!(Style::Current, disassembly[4][0].style);
+
+        // The add just wrote [16], and the input instruction wrote it before that.
+        let memory = session.memory(3);
+        assert_eq!("     8:       16       -1       16     1105        1        2        0       99", text(&memory)[1]);
+        assert_eq!("    16:        2        0        0        0        0        0        0        0", text(&memory)[2]);
+        assert_eq!(Style::Current, memory[1][2 * 3 + 2].style);
+        assert_eq!(Style::Latest, memory[2][2].style);
+
+        session.apply(Command::Step);
+        assert_eq!(Style::Recent, session.memory(3)[2][2].style);
+
+        session.apply(Command::Hex);
+        session.apply(Command::Scroll(100));
+        assert_eq!(vec!["    10:        2        0        0        0        0        0        0        0"], text(&session.memory(1)));
+        session.apply(Command::ShowPc);
+        assert_eq!("     0:        3       10      3ee       10        f        4       10      3e9", text(&session.memory(1))[0]);
+
+        session.apply(Command::Input("hi".to_string()));
+        assert_eq!(vec![
+            "in  (   3) 104 105 10",
+            "out (   1) 3",
+        ], text(&session.io(40)));
+    }
+
+    #[test]
+    fn tail_of_values() {
+        let values = || (1..=20).map(|value: i64| value.to_string());
+        assert_eq!("... 14 15 16 17 18 19 20", tail(values(), 25));
+        assert_eq!("1 2 3", tail((1..=3).map(|value: i64| value.to_string()), 25));
+    }
+}
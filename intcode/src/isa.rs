@@ -0,0 +1,135 @@
+use std::fmt;
+
+use crate::{Computer, IntcodeError, ProgramState};
+
+// The intcode instruction set grew over several puzzles: day 2 only adds and multiplies, day 5
+// adds I/O and immediate mode and then jumps and comparisons, and day 9 adds relative mode.
+// A computer limited to a level rejects instructions and parameter modes from later levels, and a
+// strict computer also rejects write parameters in immediate mode, which the puzzles never use.
+//
+//   let mut computer = Computer::load("../day2/input.txt")?.with_isa(Isa::Day2).with_strict_writes();
+//   computer.try_run()?;
+//
+// Custom opcodes aren't part of any level, so they're always allowed.
+
+/// A level of the intcode instruction set.  Each level includes everything in the levels before it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Isa {
+    /// Add, multiply and halt, with position mode parameters.
+    Day2,
+    /// Input, output and immediate mode parameters.
+    Day5Part1,
+    /// Jumps and comparisons.
+    Day5,
+    /// Relative mode parameters and relative base offsets.
+    Full,
+}
+
+impl Isa {
+    /// Every level, from lowest to highest.
+    pub const ALL: [Isa; 4] = [Isa::Day2, Isa::Day5Part1, Isa::Day5, Isa::Full];
+
+    /// Returns whether the given built in opcode is part of this level.
+    pub fn has_opcode(self, opcode: u32) -> bool {
+        match opcode {
+            1 | 2 | 99 => true,
+            3 | 4 => self >= Isa::Day5Part1,
+            5..=8 => self >= Isa::Day5,
+            9 => self >= Isa::Full,
+            _ => false,
+        }
+    }
+
+    /// Returns whether parameters can use the given mode in this level.
+    pub fn has_mode(self, mode: u32) -> bool {
+        match mode {
+            0 => true,
+            1 => self >= Isa::Day5Part1,
+            2 => self >= Isa::Full,
+            _ => false,
+        }
+    }
+
+    /// Returns the lowest level that runs the program with the given input until it halts, in strict
+    /// mode.  Returns None if the program fails even with the full instruction set, or if it needs
+    /// more input.  A program can take different paths with different input, so a level only holds
+    /// for the input it was checked with.
+    pub fn lowest(program: &[i64], input: &[i64]) -> Option<Isa> {
+        Isa::ALL.iter().copied().find(|&isa| {
+            let mut computer = Computer::new(program.to_vec()).with_isa(isa).with_strict_writes();
+            input.iter().for_each(|&value| computer.input(value));
+
+            computer.try_run().is_ok() && *computer.state() == ProgramState::Done
+        })
+    }
+}
+
+impl fmt::Display for Isa {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Isa::Day2 => "day2",
+            Isa::Day5Part1 => "day5-part1",
+            Isa::Day5 => "day5",
+            Isa::Full => "full",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Returns an error if the instruction set doesn't include the given parameter mode.
+pub(crate) fn check_mode(isa: Isa, mode: u32) -> Result<(), IntcodeError> {
+    if mode <= 2 && !isa.has_mode(mode) {
+        return Err(IntcodeError::UnsupportedMode { mode, isa });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::read_program;
+
+    #[test]
+    fn levels() {
+        // 3,0,4,0,99 echoes its input.
+        let mut computer = Computer::new(vec![3, 0, 4, 0, 99]).with_isa(Isa::Day2);
+        computer.input(7);
+        assert_eq!(Err(IntcodeError::UnsupportedInstruction { instruction: 3, isa: Isa::Day2 }), computer.try_run());
+        assert_eq!(0, computer.pc());
+
+        let mut computer = Computer::new(vec![3, 0, 4, 0, 99]).with_isa(Isa::Day5Part1);
+        computer.input(7);
+        assert_eq!(Ok(Some(7)), computer.try_run());
+
+        let mut computer = Computer::new(vec![1002, 4, 3, 4, 33]).with_isa(Isa::Day2);
+        assert_eq!("Parameter mode 1 isn't in the day2 instruction set", computer.try_run().unwrap_err().to_string());
+
+        let mut computer = Computer::new(vec![109, 1, 204, -1, 99]).with_isa(Isa::Day5);
+        assert_eq!(Err(IntcodeError::UnsupportedInstruction { instruction: 109, isa: Isa::Day5 }), computer.try_run());
+    }
+
+    #[test]
+    fn strict_writes() {
+        // Adds 1 + 1 into its own immediate parameter.
+        let program = vec![11101, 1, 1, 3, 4, 3, 99];
+        assert_eq!(Some(2), Computer::new(program.clone()).run());
+
+        let mut computer = Computer::new(program).with_strict_writes();
+        assert_eq!(Err(IntcodeError::ImmediateWrite { instruction: 11101 }), computer.try_run());
+    }
+
+    #[test]
+    fn puzzle_programs() {
+        let day2 = read_program("../day2/input.txt").unwrap();
+        let day5 = read_program("../day5/input.txt").unwrap();
+        let day9 = read_program("../day9/input.txt").unwrap();
+
+        assert_eq!(Some(Isa::Day2), Isa::lowest(&day2, &[]));
+        assert_eq!(Some(Isa::Day5Part1), Isa::lowest(&day5, &[1]));
+        assert_eq!(Some(Isa::Day5), Isa::lowest(&day5, &[5]));
+        assert_eq!(Some(Isa::Full), Isa::lowest(&day9, &[1]));
+        assert_eq!(None, Isa::lowest(&day9, &[]));
+    }
+}
@@ -0,0 +1,30 @@
+// Shared intcode computer.  Days before this crate existed each carry their own copy of
+// computer.rs - new code that needs an intcode computer should depend on this crate instead.
+
+pub mod asm;
+pub mod circuit;
+pub mod compiler;
+pub mod computer;
+pub mod coverage;
+pub mod diagnostic;
+pub mod display;
+pub mod error;
+pub mod extension;
+pub mod frame;
+pub mod gdb;
+pub mod goal;
+pub mod heatmap;
+pub mod inspector;
+pub mod isa;
+pub mod link;
+pub mod memory;
+pub mod optimize;
+pub mod program;
+pub mod scan;
+pub mod search;
+pub mod stdlib;
+pub mod transpile;
+
+pub use computer::{Computer, Outputs, Parameter, ProgramIO, ProgramState};
+pub use error::IntcodeError;
+pub use memory::Memory;
@@ -0,0 +1,312 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::asm::{Module, Word};
+use crate::program::write_program;
+use crate::stdlib;
+
+// The linker combines assembled modules into one program.  The first module is placed at address
+// 0, so it's where the program starts.  Other modules are libraries: a library is only linked if a
+// linked module refers to a label it exports, and libraries are placed after the first module in
+// the order they were added.  Every label is then replaced by its address in the program.
+//
+//   let main = Module::parse("main", "arb stack\ncall read_int -> [rb+1]\ncall print_int, [rb+1]\nhalt")?;
+//   let linked = Linker::new().module(main).with_stdlib().link()?;
+//   linked.save("program.txt")?;
+//
+// The linker defines 'stack' as the address after the end of the program, so a program that
+// starts with 'arb stack' has its stack in the memory after it.
+
+/// Label the linker defines as the first address after the program.
+const STACK: &str = "stack";
+
+/// Modules couldn't be linked into a program.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum LinkError {
+    /// There weren't any modules to link.
+    NoModules,
+    /// A module refers to a label that no module exports.
+    Undefined { module: String, symbol: String },
+    /// Two modules export the same label.
+    Duplicate { symbol: String, modules: (String, String) },
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::NoModules => write!(f, "no modules to link"),
+            LinkError::Undefined { module, symbol } => write!(f, "module {} refers to '{}', which isn't exported by any module", module, symbol),
+            LinkError::Duplicate { symbol, modules: (first, second) } => write!(f, "'{}' is exported by both {} and {}", symbol, first, second),
+        }
+    }
+}
+
+impl Error for LinkError {}
+
+/// A linked program, and where its modules and exported labels ended up.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Linked {
+    pub program: Vec<i64>,
+    /// Address of each exported label, and of 'stack'.
+    pub symbols: BTreeMap<String, usize>,
+    /// Addresses of each linked module, in program order.
+    pub layout: Vec<(String, Range<usize>)>,
+}
+
+impl Linked {
+    /// Writes the program to the given file, in the format Computer::load reads.
+    pub fn save<P: AsRef<Path>>(&self, filename: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        write_program(&mut writer, &self.program)?;
+        writer.flush()
+    }
+}
+
+/// Combines modules into a program.
+#[derive(Debug, Clone, Default)]
+pub struct Linker {
+    modules: Vec<Module>,
+}
+
+impl Linker {
+    /// Constructs a linker without any modules.
+    pub fn new() -> Linker {
+        Linker::default()
+    }
+
+    /// Adds a module.  The first module is the start of the program, and the rest are libraries.
+    pub fn module(mut self, module: Module) -> Self {
+        self.modules.push(module);
+        self
+    }
+
+    /// Adds the standard library's modules as libraries.
+    pub fn with_stdlib(mut self) -> Self {
+        self.modules.extend(stdlib::modules());
+        self
+    }
+
+    /// Links the first module and the libraries it needs into a program.
+    pub fn link(&self) -> Result<Linked, LinkError> {
+        if self.modules.is_empty() {
+            return Err(LinkError::NoModules);
+        }
+
+        let mut exporters: HashMap<&str, usize> = HashMap::new();
+        for (index, module) in self.modules.iter().enumerate() {
+            for symbol in &module.exports {
+                if let Some(&other) = exporters.get(symbol.as_str()) {
+                    return Err(LinkError::Duplicate {
+                        symbol: symbol.clone(),
+                        modules: (self.modules[other].name.clone(), module.name.clone()),
+                    });
+                }
+                exporters.insert(symbol, index);
+            }
+        }
+
+        // Follow imports from the first module to find the libraries it needs.
+        let mut needed = vec![false; self.modules.len()];
+        let mut queue = vec![0];
+        needed[0] = true;
+
+        while let Some(index) = queue.pop() {
+            let module = &self.modules[index];
+
+            for symbol in module.imports() {
+                match exporters.get(symbol) {
+                    Some(&exporter) if !needed[exporter] => {
+                        needed[exporter] = true;
+                        queue.push(exporter);
+                    }
+                    Some(_) => {}
+                    None if symbol == STACK => {}
+                    None => return Err(LinkError::Undefined { module: module.name.clone(), symbol: symbol.to_string() }),
+                }
+            }
+        }
+
+        let mut layout = Vec::new();
+        let mut bases = vec![0; self.modules.len()];
+        let mut end = 0;
+        for (index, module) in self.modules.iter().enumerate().filter(|(index, _)| needed[*index]) {
+            bases[index] = end;
+            layout.push((module.name.clone(), end..end + module.words.len()));
+            end += module.words.len();
+        }
+
+        let mut symbols: BTreeMap<String, usize> = exporters.iter()
+            .filter(|(_, &index)| needed[index])
+            .map(|(&symbol, &index)| (symbol.to_string(), bases[index] + self.modules[index].labels[symbol]))
+            .collect();
+        symbols.entry(STACK.to_string()).or_insert(end);
+
+        let mut program = Vec::with_capacity(end);
+        for (index, module) in self.modules.iter().enumerate().filter(|(index, _)| needed[*index]) {
+            for word in &module.words {
+                program.push(match word {
+                    Word::Value(value) => *value,
+                    Word::Address { symbol, offset } => {
+                        let addr = match module.labels.get(symbol) {
+                            Some(&label) => bases[index] + label,
+                            None => symbols[symbol],
+                        };
+                        addr as i64 + offset
+                    }
+                });
+            }
+        }
+
+        Ok(Linked { program, symbols, layout })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Computer;
+
+    /// Links the main module with the standard library, and runs it with the given ASCII input.
+    fn run(main: &str, input: &str) -> String {
+        let linked = Linker::new().module(Module::parse("main", main).unwrap()).with_stdlib().link().unwrap();
+
+        let mut computer = Computer::new(linked.program);
+        computer.text_input(input);
+        computer.try_run().unwrap();
+
+        computer.dump_output().into_iter().map(|c| c as u8 as char).collect()
+    }
+
+    #[test]
+    fn relocation() {
+        let first = Module::parse("first", "
+            export value
+            jmp start
+            value: data 7
+            start: out [value]
+            call double, [value] -> [rb+1]
+            out [rb+1]
+            halt
+        ").unwrap();
+
+        let second = Module::parse("second", "
+            export double
+            double: mul [rb+1], 2, [rb+1]
+            ret
+            unused: data value
+        ").unwrap();
+
+        let linked = Linker::new().module(first).module(second).link().unwrap();
+
+        assert_eq!(vec![("first".to_string(), 0..24), ("second".to_string(), 24..32)], linked.layout);
+        assert_eq!(Some(&3), linked.symbols.get("value"));
+        assert_eq!(Some(&24), linked.symbols.get("double"));
+        assert_eq!(Some(&32), linked.symbols.get("stack"));
+        assert_eq!(&[1106, 0, 4, 7, 4, 3], &linked.program[..6]);
+        assert_eq!(&[21202, 1, 2, 1, 2106, 0, 0, 3], &linked.program[24..]);
+
+        let mut computer = Computer::new(linked.program);
+        computer.run();
+        assert_eq!(vec![7, 14], computer.dump_output());
+    }
+
+    #[test]
+    fn stdlib() {
+        let echo = "
+            arb stack
+        loop:
+            call read_int -> [rb+1]
+            call print_int, [rb+1]
+            out 10
+            jmp loop
+        ";
+
+        assert_eq!("0\n42\n-7\n9223372036854775807\n", run(echo, "0\n42\n-7\n9223372036854775807\n"));
+
+        let divide = "
+            arb stack
+        loop:
+            call read_int -> [a]
+            call read_int -> [b]
+            call divmod, [a], [b] -> [a], [b]
+            call print_int, [a]
+            out 32
+            call print_int, [b]
+            out 10
+            jmp loop
+        a:  data 0
+        b:  data 0
+        ";
+
+        let cases = [(17, 5), (-17, 5), (17, -5), (-17, -5), (3, 7), (i64::MAX, 2), (123456789012345, 1000)];
+        let input: String = cases.iter().map(|(a, b)| format!("{}\n{}\n", a, b)).collect();
+        let expected: String = cases.iter().map(|(a, b)| format!("{} {}\n", a / b, a % b)).collect();
+        assert_eq!(expected, run(divide, &input));
+
+        let power = "
+            arb stack
+            call pow, 3, 4 -> [rb+1]
+            call print_int, [rb+1]
+            call pow, -2, 0 -> [rb+1]
+            call print_int, [rb+1]
+            halt
+        ";
+
+        assert_eq!("811", run(power, ""));
+
+        // Arguments and results that read each other's cells are copied as if all at once.
+        let swapped = "
+            arb stack
+            mov 2, [rb+1]
+            mov 10, [rb+2]
+            call pow, [rb+2], [rb+1] -> [rb+1]
+            call print_int, [rb+1]
+            out 32
+            call divmod, 17, 5 -> [rb+2], [rb+1]
+            mov [rb+2], [q]
+            mov [rb+1], [r]
+            call print_int, [q]
+            call print_int, [r]
+            halt
+        q:  data 0
+        r:  data 0
+        ";
+
+        assert_eq!("100 32", run(swapped, ""));
+    }
+
+    #[test]
+    fn only_needed_libraries() {
+        let main = Module::parse("main", "arb stack\ncall pow, 2, 10 -> [rb+1]\nout [rb+1]\nhalt").unwrap();
+        let linked = Linker::new().module(main).with_stdlib().link().unwrap();
+
+        let modules: Vec<&str> = linked.layout.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(vec!["main", "pow"], modules);
+
+        let mut computer = Computer::new(linked.program);
+        assert_eq!(Some(1024), computer.run());
+    }
+
+    #[test]
+    fn errors() {
+        let module = |name: &str, source: &str| Module::parse(name, source).unwrap();
+
+        assert_eq!(Err(LinkError::NoModules), Linker::new().link());
+
+        let undefined = Linker::new().module(module("main", "jmp missing")).link();
+        assert_eq!(Err(LinkError::Undefined { module: "main".to_string(), symbol: "missing".to_string() }), undefined);
+
+        let duplicate = Linker::new()
+            .module(module("main", "halt"))
+            .module(module("a", "export f\nf: ret"))
+            .module(module("b", "export f\nf: ret"))
+            .link();
+        assert_eq!("'f' is exported by both a and b", duplicate.unwrap_err().to_string());
+    }
+}
@@ -0,0 +1,254 @@
+use core::fmt;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use crate::error::IntcodeError;
+
+/// What should happen to a value written to a hooked address.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WriteAction {
+    /// Store the given value at the address.  Hooks can store a different value than the one written.
+    Store(i64),
+    /// Leave memory unchanged and stop the program with an IntcodeError::WriteRejected.
+    Reject,
+}
+
+/// A MemoryHook is called when the program reads or writes an address in a hooked range.
+/// Hooks are shared between clones of a Memory, so any state they keep needs interior mutability.
+pub trait MemoryHook: Send + Sync {
+    /// Called when the program reads a hooked address.  Returns the value the program sees,
+    /// which defaults to the value stored in memory.
+    fn read(&self, _addr: usize, stored: i64) -> i64 {
+        stored
+    }
+
+    /// Called when the program writes a hooked address that currently holds old.  Defaults to storing the value.
+    fn write(&self, _addr: usize, _old: i64, value: i64) -> WriteAction {
+        WriteAction::Store(value)
+    }
+}
+
+/// Hook that rejects every write, for protecting code from self-modification.
+#[derive(Debug, Default)]
+pub struct ReadOnly;
+
+impl MemoryHook for ReadOnly {
+    fn write(&self, _addr: usize, _old: i64, _value: i64) -> WriteAction {
+        WriteAction::Reject
+    }
+}
+
+/// A single write recorded by a WriteWatch.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct WatchedWrite {
+    pub addr: usize,
+    pub old: i64,
+    pub value: i64,
+}
+
+/// Hook that records every write to the watched addresses.
+#[derive(Debug, Default)]
+pub struct WriteWatch {
+    writes: Mutex<Vec<WatchedWrite>>,
+}
+
+impl WriteWatch {
+    /// Returns the writes recorded since the last call to take_writes, oldest first.
+    pub fn take_writes(&self) -> Vec<WatchedWrite> {
+        std::mem::take(&mut *self.writes.lock().unwrap())
+    }
+}
+
+impl MemoryHook for WriteWatch {
+    fn write(&self, addr: usize, old: i64, value: i64) -> WriteAction {
+        self.writes.lock().unwrap().push(WatchedWrite { addr, old, value });
+        WriteAction::Store(value)
+    }
+}
+
+/// Memory contains a sparse representation of memory values.
+#[derive(Clone)]
+pub struct Memory {
+    values: HashMap<usize, i64>,
+    original_values: HashMap<usize, Option<i64>>,
+    hooks: Vec<(Range<usize>, Arc<dyn MemoryHook>)>,
+}
+
+impl Debug for Memory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut keys: Vec<_> = self.values.keys().collect();
+        keys.sort();
+
+        for (i, key) in keys.into_iter().enumerate() {
+            // The first key shouldn't have a comma before it.
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+
+            write!(f, "({}: {})", key, self.values[key])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Memory {
+    /// Constructs a new memory initialized with the instructions in the program.
+    pub(crate) fn for_program(program: &[i64]) -> Memory {
+        let values = program.iter().cloned().enumerate().collect::<HashMap<usize, i64>>();
+
+        Memory {
+            values,
+            original_values: HashMap::new(),
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Returns the value of the given memory address, passing it through any hooks on the address.
+    /// If the address has never been set, returns the default value of 0.
+    pub fn get(&self, addr: usize) -> i64 {
+        self.hooks_for(addr).fold(self.peek(addr), |value, hook| hook.read(addr, value))
+    }
+
+    /// Returns the value stored at the given address without calling hooks.  The computer uses this
+    /// to fetch instructions, so hooks only see the program's data reads.
+    pub fn peek(&self, addr: usize) -> i64 {
+        self.values.get(&addr).copied().unwrap_or(0)
+    }
+
+    /// Sets the memory at the given address.  Hooks on the address can change the stored value,
+    /// or reject the write entirely.
+    pub fn set(&mut self, addr: usize, value: i64) -> Result<(), IntcodeError> {
+        let old = self.peek(addr);
+
+        let mut stored = value;
+        for hook in self.hooks_for(addr) {
+            match hook.write(addr, old, stored) {
+                WriteAction::Store(hooked) => stored = hooked,
+                WriteAction::Reject => return Err(IntcodeError::WriteRejected { addr, value }),
+            }
+        }
+
+        let original = self.values.insert(addr, stored);
+        self.original_values.entry(addr).or_insert(original);
+
+        Ok(())
+    }
+
+    /// Calls the given hook whenever the program reads or writes an address in the range.  Hooks on
+    /// overlapping ranges are called in the order they were added, each seeing the previous hook's value.
+    pub fn add_hook(&mut self, addrs: Range<usize>, hook: Arc<dyn MemoryHook>) {
+        self.hooks.push((addrs, hook));
+    }
+
+    /// Rejects writes to the given range of addresses.
+    pub fn protect(&mut self, addrs: Range<usize>) {
+        self.add_hook(addrs, Arc::new(ReadOnly));
+    }
+
+    /// Removes the given hook from every range it was added to, leaving the other hooks in place.
+    /// Returns whether the hook was found.
+    pub fn remove_hook<H: MemoryHook + ?Sized>(&mut self, hook: &Arc<H>) -> bool {
+        let len = self.hooks.len();
+        self.hooks.retain(|(_, added)| Arc::as_ptr(added) as *const () != Arc::as_ptr(hook) as *const ());
+
+        self.hooks.len() != len
+    }
+
+    /// Removes all of the hooks from this memory.
+    pub fn clear_hooks(&mut self) {
+        self.hooks.clear();
+    }
+
+    /// Returns the hooks that apply to the given address.
+    fn hooks_for(&self, addr: usize) -> impl Iterator<Item = &Arc<dyn MemoryHook>> {
+        self.hooks.iter()
+            .filter(move |(addrs, _)| addrs.contains(&addr))
+            .map(|(_, hook)| hook)
+    }
+
+    /// Returns the contents of memory from address 0 through the highest address that has been set,
+    /// with unset addresses filled in as 0.  This is the program that would recreate this memory.
+    pub fn to_program(&self) -> Vec<i64> {
+        let len = self.values.keys().max().map_or(0, |&max| max + 1);
+
+        (0..len).map(|addr| self.peek(addr)).collect()
+    }
+
+    /// Resets the values in this memory to their original values.  Hooks stay attached, but aren't called.
+    pub(crate) fn reset(&mut self) {
+        for (addr, maybe_value) in self.original_values.drain() {
+            if let Some(value) = maybe_value {
+                self.values.insert(addr, value);
+            } else {
+                self.values.remove(&addr);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Computer;
+
+    /// Device that reads as 10 times the stored value.
+    struct TimesTen;
+
+    impl MemoryHook for TimesTen {
+        fn read(&self, _addr: usize, stored: i64) -> i64 {
+            stored * 10
+        }
+    }
+
+    #[test]
+    fn read_hook() {
+        // Outputs the value at address 5.
+        let mut computer = Computer::new(vec![4, 5, 4, 6, 99, 7, 8]);
+        computer.memory.add_hook(5..6, Arc::new(TimesTen));
+
+        computer.run();
+
+        assert_eq!(vec![70, 8], computer.dump_output());
+    }
+
+    #[test]
+    fn write_watch() {
+        // Adds 1 to address 9, then stores address 9 plus 1 in address 10.
+        let mut computer = Computer::new(vec![1001, 9, 1, 9, 1001, 9, 1, 10, 99, 5, 0]);
+        let watch = Arc::new(WriteWatch::default());
+        computer.memory.add_hook(9..11, watch.clone());
+
+        computer.run();
+
+        assert_eq!(vec![
+            WatchedWrite { addr: 9, old: 5, value: 6 },
+            WatchedWrite { addr: 10, old: 0, value: 7 },
+        ], watch.take_writes());
+        assert!(watch.take_writes().is_empty());
+
+        // Removing the watch leaves other hooks alone.
+        computer.reset();
+        computer.memory.protect(10..11);
+        assert!(computer.memory.remove_hook(&watch));
+        assert!(!computer.memory.remove_hook(&watch));
+
+        assert_eq!(Err(IntcodeError::WriteRejected { addr: 10, value: 7 }), computer.try_run());
+        assert!(watch.take_writes().is_empty());
+    }
+
+    #[test]
+    fn protect() {
+        // Overwrites its own output parameter.
+        let mut computer = Computer::new(vec![1101, 1, 1, 3, 99]);
+        computer.memory.protect(0..5);
+
+        assert_eq!(Err(IntcodeError::WriteRejected { addr: 3, value: 2 }), computer.try_run());
+        assert_eq!(3, computer.memory.get(3));
+
+        computer.memory.clear_hooks();
+        assert_eq!(Ok(None), computer.try_run());
+    }
+}
@@ -0,0 +1,655 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::ops::Range;
+
+use crate::error::IntcodeError;
+use crate::transpile::{Decoded, Operand};
+use crate::{Computer, ProgramState};
+
+// The optimizer rewrites a static intcode program into a smaller, faster program that behaves the
+// same.  Instructions stay at their addresses, since intcode can't tell code from data:
+//
+//  * Folding replaces position mode reads of cells with known values by immediates, and turns
+//    arithmetic and comparisons on immediates into a store of the result.  A cell's value is known
+//    if nothing can write it, or if an earlier instruction in the same block stored a constant in it.
+//  * Threading sends jumps straight to the end of a chain of unconditional jumps, skipping jumps
+//    that are never taken.
+//  * Dead code elimination clears cells that can't be run, read or written, and cuts zeros off the
+//    end of the program.
+//
+// The analysis is conservative about position mode parameters: any cell a reachable instruction
+// reads or writes through one is kept, and instructions that overlap those cells are never
+// changed.  It assumes jumps through position or relative mode parameters go to the start of an
+// instruction whose address appears as a constant in the program, like a return address pushed
+// before a call.  Relative mode parameters could address anything, so programs that use them are
+// left alone unless the caller promises they only address the stack past the end of the program:
+//
+//   let optimized = Optimizer::new(&program).with_stack().optimize();
+//   optimize::verify(&program, &optimized.program, &[vec![1], vec![5]])?;
+//   println!("{}", optimized);
+
+/// Most rounds of folding and threading before the optimizer stops looking for more changes.
+const MAX_ROUNDS: usize = 16;
+
+/// A change the optimizer made to the program.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Change {
+    /// Reads were replaced by constants, or an operation on constants was replaced by its result.
+    Folded { addr: usize, before: String, after: String },
+    /// A jump was sent to a different address that behaves the same.
+    Threaded { addr: usize, from: usize, to: usize },
+    /// Cells that can't be run, read or written were cleared or cut off the end of the program.
+    Removed { addrs: Range<usize> },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Folded { addr, before, after } => write!(f, "{:>5}: folded '{}' into '{}'", addr, before, after),
+            Change::Threaded { addr, from, to } => write!(f, "{:>5}: threaded jump to {} through to {}", addr, from, to),
+            Change::Removed { addrs } => write!(f, "{:>5}: removed {} cells up to {}", addrs.start, addrs.len(), addrs.end),
+        }
+    }
+}
+
+/// An optimized program, and the changes that were made to it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Optimized {
+    pub program: Vec<i64>,
+    pub changes: Vec<Change>,
+}
+
+impl fmt::Display for Optimized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = |matches: fn(&Change) -> bool| self.changes.iter().filter(|change| matches(change)).count();
+        let removed: usize = self.changes.iter().map(|change| match change {
+            Change::Removed { addrs } => addrs.len(),
+            _ => 0,
+        }).sum();
+
+        writeln!(f, "Folded {} instructions, threaded {} jumps and removed {} cells ({} cells left)",
+                 count(|change| matches!(change, Change::Folded { .. })),
+                 count(|change| matches!(change, Change::Threaded { .. })),
+                 removed, self.program.len())?;
+
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Optimizes static intcode programs.
+#[derive(Debug, Clone)]
+pub struct Optimizer<'a> {
+    program: &'a [i64],
+    stack: bool,
+}
+
+impl<'a> Optimizer<'a> {
+    /// Constructs an optimizer for the given program.
+    pub fn new(program: &'a [i64]) -> Optimizer<'a> {
+        Optimizer { program, stack: false }
+    }
+
+    /// Assumes relative mode parameters only address the stack, past the end of the program.
+    pub fn with_stack(mut self) -> Self {
+        self.stack = true;
+        self
+    }
+
+    /// Returns the optimized program.
+    pub fn optimize(&self) -> Optimized {
+        let mut program = self.program.to_vec();
+        let mut changes = Vec::new();
+
+        for _ in 0..MAX_ROUNDS {
+            let analysis = Analysis::of(&program, self.stack);
+            if !analysis.fold(&mut program, &mut changes) {
+                break;
+            }
+        }
+
+        Analysis::of(&program, self.stack).remove_dead_code(&mut program, &mut changes);
+
+        Optimized { program, changes }
+    }
+}
+
+/// Reachable code and the cells it can read and write.
+struct Analysis {
+    code: BTreeMap<usize, Decoded>,
+    /// Addresses that execution can reach other than by falling through.
+    leaders: BTreeSet<usize>,
+    reads: BTreeSet<usize>,
+    writes: BTreeSet<usize>,
+    /// Cells that are part of more than one reachable instruction.
+    overlapping: BTreeSet<usize>,
+    /// Whether an instruction could read or write any cell in the program.
+    unknown: bool,
+}
+
+impl Analysis {
+    /// Finds the code reachable from address 0, and the cells it reads and writes through
+    /// position mode parameters.
+    fn of(program: &[i64], stack: bool) -> Analysis {
+        let mut reachable = reachable(program, false);
+        let writes: BTreeSet<usize> = reachable.code.values().filter_map(position_write).collect();
+
+        // A jump whose target is written is an indirect jump.
+        let retargeted = reachable.code.values().any(|instruction| {
+            matches!(instruction.opcode, 5 | 6) && writes.contains(&(instruction.addr + 2))
+        });
+
+        if retargeted && !reachable.indirect {
+            reachable = self::reachable(program, true);
+        }
+
+        let Reachable { code, leaders, undecoded, .. } = reachable;
+        let reads: BTreeSet<usize> = code.values().flat_map(position_reads).collect();
+        let writes: BTreeSet<usize> = writes.into_iter().chain(code.values().filter_map(position_write)).collect();
+
+        let mut covered = BTreeSet::new();
+        let mut overlapping = BTreeSet::new();
+        for instruction in code.values() {
+            for addr in instruction.addr..instruction.next() {
+                if !covered.insert(addr) {
+                    overlapping.insert(addr);
+                }
+            }
+        }
+
+        // An instruction could address any cell if it uses relative mode, or if the program writes
+        // its opcode or the address in one of its position mode parameters.  Execution could also
+        // reach something the analysis can't decode, which the program might write an instruction
+        // over, or which the interpreter runs differently than the analysis expects.
+        let relative = code.values().flat_map(|instruction| &instruction.operands).any(|operand| operand.mode == 2);
+        let rewritten = !undecoded.is_empty() || code.values().any(|instruction| {
+            writes.contains(&instruction.addr) || instruction.operands.iter().enumerate().any(|(i, operand)| {
+                writes.contains(&(instruction.addr + i + 1)) && (operand.mode != 1 || position_write(instruction).is_some() && i + 1 == instruction.operands.len())
+            })
+        });
+
+        Analysis {
+            code,
+            leaders,
+            reads,
+            writes,
+            overlapping,
+            unknown: relative && !stack || rewritten,
+        }
+    }
+
+    /// Returns whether a running program could write to the cell.
+    fn may_write(&self, addr: usize) -> bool {
+        self.unknown || self.writes.contains(&addr)
+    }
+
+    /// Returns whether the optimizer can rewrite the instruction: none of its cells are read or
+    /// written as data, or part of another instruction.
+    fn can_rewrite(&self, instruction: &Decoded) -> bool {
+        !self.unknown && (instruction.addr..instruction.next())
+            .all(|addr| !self.reads.contains(&addr) && !self.writes.contains(&addr) && !self.overlapping.contains(&addr))
+    }
+
+    /// Folds constants and threads jumps through the reachable code.  Returns whether anything changed.
+    fn fold(&self, program: &mut [i64], changes: &mut Vec<Change>) -> bool {
+        let mut changed = false;
+
+        // Values stored by earlier instructions in the block.
+        let mut known: HashMap<usize, i64> = HashMap::new();
+        let mut fallthrough = None;
+
+        for instruction in self.code.values() {
+            if self.leaders.contains(&instruction.addr) || fallthrough != Some(instruction.addr) {
+                known.clear();
+            }
+            fallthrough = if instruction.falls_through() { Some(instruction.next()) } else { None };
+
+            if !self.can_rewrite(instruction) {
+                // The instruction could be different when it runs, so it could write anything.
+                known.clear();
+                continue;
+            }
+
+            let mut folded = instruction.clone();
+            let reads = match folded.opcode {
+                1 | 2 | 5 | 6 | 7 | 8 => 2,
+                4 | 9 => 1,
+                _ => 0,
+            };
+
+            for operand in &mut folded.operands[..reads] {
+                if operand.mode != 0 || operand.value < 0 {
+                    continue;
+                }
+
+                let addr = operand.value as usize;
+                let value = match known.get(&addr) {
+                    Some(&value) => value,
+                    None if !self.may_write(addr) => program.get(addr).copied().unwrap_or(0),
+                    None => continue,
+                };
+
+                *operand = Operand { mode: 1, value };
+            }
+
+            let result = match &folded.operands[..] {
+                [Operand { mode: 1, value: a }, Operand { mode: 1, value: b }, _] => match folded.opcode {
+                    1 => a.checked_add(*b),
+                    2 => a.checked_mul(*b),
+                    7 => Some((a < b) as i64),
+                    _ => Some((a == b) as i64),
+                },
+                _ => None,
+            };
+
+            // An operation on constants that already stores one of them is left as it is.
+            let substituted = folded.operands != instruction.operands;
+            let stored = result.filter(|result| substituted || !folded.operands[..2].iter().any(|operand| operand.value == *result));
+
+            if let Some(result) = stored {
+                folded = Decoded {
+                    addr: folded.addr,
+                    opcode: 1,
+                    operands: vec![Operand { mode: 1, value: result }, Operand { mode: 1, value: 0 }, folded.operands[2]],
+                };
+            }
+
+            // Stores through position (or immediate) mode parameters are known until the end of the block.
+            if matches!(folded.opcode, 1 | 2 | 3 | 7 | 8) {
+                let out = folded.operands[folded.operands.len() - 1];
+                if out.mode == 2 {
+                    known.retain(|&addr, _| addr < program.len());
+                } else if out.value >= 0 {
+                    match result {
+                        Some(value) => known.insert(out.value as usize, value),
+                        None => known.remove(&(out.value as usize)),
+                    };
+                }
+            }
+
+            if folded != *instruction {
+                changes.push(Change::Folded { addr: folded.addr, before: instruction.to_string(), after: folded.to_string() });
+            }
+
+            if matches!(folded.opcode, 5 | 6) && folded.operands[1].mode == 1 && folded.operands[1].value >= 0 {
+                let from = folded.operands[1].value as usize;
+                let to = self.thread(program, from);
+
+                if to != from {
+                    folded.operands[1].value = to as i64;
+                    changes.push(Change::Threaded { addr: folded.addr, from, to });
+                }
+            }
+
+            // Unchanged instructions keep their cells, including any mode digits the decoder ignored.
+            if folded != *instruction {
+                program[folded.addr..folded.next()].copy_from_slice(&encode(&folded));
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    /// Returns the address a jump to the given address ends up at, following unconditional jumps
+    /// and skipping jumps that are never taken.
+    fn thread(&self, program: &[i64], mut addr: usize) -> usize {
+        let mut seen = BTreeSet::new();
+
+        while seen.insert(addr) {
+            let instruction = match self.code.get(&addr) {
+                Some(instruction) if self.can_rewrite(instruction) => instruction,
+                _ => break,
+            };
+
+            // Read the jump from the program, in case this round already threaded it.
+            let (condition, target) = match Decoded::parse(program, addr) {
+                Some(Decoded { opcode: opcode @ (5 | 6), operands, .. }) if operands[0].mode == 1 && instruction.opcode == opcode => {
+                    ((operands[0].value != 0) == (opcode == 5), operands[1])
+                }
+                _ => break,
+            };
+
+            addr = match (condition, target) {
+                (false, _) => addr + 3,
+                (true, Operand { mode: 1, value }) if value >= 0 => value as usize,
+                _ => break,
+            };
+        }
+
+        addr
+    }
+
+    /// Clears cells that aren't reachable code and can't be read or written, and cuts zeros off the
+    /// end of the program.
+    fn remove_dead_code(&self, program: &mut Vec<i64>, changes: &mut Vec<Change>) {
+        if self.unknown {
+            return;
+        }
+
+        let live = |addr: usize| {
+            self.reads.contains(&addr) || self.writes.contains(&addr)
+                || self.code.range(..=addr).next_back().is_some_and(|(_, instruction)| addr < instruction.next())
+        };
+
+        let mut start = None;
+        for addr in 0..=program.len() {
+            let dead = addr < program.len() && !live(addr) && program[addr] != 0;
+            match (dead, start) {
+                (true, None) => start = Some(addr),
+                (false, Some(from)) => {
+                    changes.push(Change::Removed { addrs: from..addr });
+                    start = None;
+                }
+                _ => {}
+            }
+
+            if dead {
+                program[addr] = 0;
+            }
+        }
+
+        let len = program.iter().rposition(|&value| value != 0).map_or(0, |last| last + 1);
+        program.truncate(len);
+    }
+}
+
+/// Code reachable from address 0.
+struct Reachable {
+    code: BTreeMap<usize, Decoded>,
+    /// Addresses that execution can reach other than by falling through.
+    leaders: BTreeSet<usize>,
+    /// Addresses that execution reaches that aren't valid instructions until the program writes them.
+    undecoded: BTreeSet<usize>,
+    indirect: bool,
+}
+
+/// Returns the instructions reachable from address 0 through fallthrough and immediate jumps.  If
+/// the code has indirect jumps, or if indirect is true, any constant that's the start of an
+/// instruction can also be reached.
+fn reachable(program: &[i64], mut indirect: bool) -> Reachable {
+    let mut code: BTreeMap<usize, Decoded> = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    let mut undecoded = BTreeSet::new();
+    let mut queue = vec![(0, true)];
+    leaders.insert(0);
+
+    loop {
+        while let Some((addr, certain)) = queue.pop() {
+            if code.contains_key(&addr) {
+                continue;
+            }
+
+            let instruction = match Decoded::parse(program, addr) {
+                Some(instruction) => instruction,
+                None => {
+                    if certain {
+                        undecoded.insert(addr);
+                    }
+                    continue;
+                }
+            };
+
+            if instruction.can_jump() {
+                match instruction.jump_target() {
+                    Some(target) => {
+                        leaders.insert(target);
+                        queue.push((target, true));
+                    }
+                    None => indirect = true,
+                }
+            }
+
+            if instruction.falls_through() {
+                queue.push((instruction.next(), true));
+            }
+
+            code.insert(addr, instruction);
+        }
+
+        if !indirect {
+            break;
+        }
+
+        let inside: BTreeSet<usize> = code.values().flat_map(|instruction| instruction.addr + 1..instruction.next()).collect();
+        let constants: Vec<i64> = code.values()
+            .flat_map(|instruction| &instruction.operands)
+            .filter(|operand| operand.mode == 1)
+            .map(|operand| operand.value)
+            .chain(code.values().flat_map(position_reads).filter_map(|addr| program.get(addr).copied()))
+            .collect();
+
+        for constant in constants {
+            if constant >= 0 && (constant as usize) < program.len() && !inside.contains(&(constant as usize))
+                && leaders.insert(constant as usize) {
+                queue.push((constant as usize, false));
+            }
+        }
+
+        if queue.is_empty() {
+            break;
+        }
+    }
+
+    Reachable { code, leaders, undecoded, indirect }
+}
+
+/// Returns the cells an instruction reads through position mode parameters.
+fn position_reads(instruction: &Decoded) -> Vec<usize> {
+    let reads = match instruction.opcode {
+        1 | 2 | 5 | 6 | 7 | 8 => 2,
+        4 | 9 => 1,
+        _ => 0,
+    };
+
+    instruction.operands[..reads].iter()
+        .filter(|operand| operand.mode == 0 && operand.value >= 0)
+        .map(|operand| operand.value as usize)
+        .collect()
+}
+
+/// Returns the cell an instruction writes through a position mode parameter.  Immediate mode writes
+/// are treated as position mode, like the interpreter does.
+fn position_write(instruction: &Decoded) -> Option<usize> {
+    match instruction.opcode {
+        1 | 2 | 3 | 7 | 8 => instruction.operands.last()
+            .filter(|operand| operand.mode != 2 && operand.value >= 0)
+            .map(|operand| operand.value as usize),
+        _ => None,
+    }
+}
+
+/// Returns the cells for an instruction.
+fn encode(instruction: &Decoded) -> Vec<i64> {
+    let modes = instruction.operands.iter().rev().fold(0, |modes, operand| modes * 10 + operand.mode);
+
+    Some(modes * 100 + instruction.opcode).into_iter()
+        .chain(instruction.operands.iter().map(|operand| operand.value))
+        .collect()
+}
+
+/// What a program did with an input: its outputs, and the state it stopped in.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Behaviour {
+    pub outputs: Vec<i64>,
+    pub stopped: Result<ProgramState, IntcodeError>,
+}
+
+impl Behaviour {
+    /// Runs the program with the input until it halts, waits for more input or fails.
+    fn of(program: &[i64], input: &[i64]) -> Behaviour {
+        let mut computer = Computer::new(program.to_vec());
+        input.iter().for_each(|&value| computer.input(value));
+
+        let stopped = computer.try_run().map(|_| computer.state().clone());
+        Behaviour { outputs: computer.dump_output(), stopped }
+    }
+}
+
+/// An input that the original and optimized programs behaved differently on.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Mismatch {
+    pub input: Vec<i64>,
+    pub original: Behaviour,
+    pub optimized: Behaviour,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Input {:?}: the original program output {:?} and stopped with {:?}, but the optimized program output {:?} and stopped with {:?}",
+               self.input, self.original.outputs, self.original.stopped, self.optimized.outputs, self.optimized.stopped)
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+/// Checks that the optimized program behaves the same as the original on each of the inputs.
+/// Returns the first input they behave differently on.
+pub fn verify(original: &[i64], optimized: &[i64], inputs: &[Vec<i64>]) -> Result<(), Box<Mismatch>> {
+    for input in inputs {
+        let (before, after) = (Behaviour::of(original, input), Behaviour::of(optimized, input));
+
+        if before != after {
+            return Err(Box::new(Mismatch { input: input.clone(), original: before, optimized: after }));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::read_program;
+
+    #[test]
+    fn folds_constants() {
+        let program = vec![
+            1102, 6, 7, 17,     // 0: mul 6, 7, [17]
+            1, 17, 18, 19,      // 4: add [17], [18], [19]
+            4, 19,              // 8: out [19]
+            1008, 19, 42, 20,   // 10: eq [19], 42, [20]
+            4, 20,              // 14: out [20]
+            99,                 // 16: halt
+            0, 100, 0, 0,       // 17: data
+        ];
+
+        let optimized = Optimizer::new(&program).optimize();
+
+        assert_eq!(vec![
+            1101, 42, 0, 17,
+            1101, 142, 0, 19,
+            104, 142,
+            1101, 0, 0, 20,
+            104, 0,
+            99,
+        ], optimized.program);
+
+        assert_eq!(Change::Folded { addr: 0, before: "mul 6, 7, [17]".to_string(), after: "add 42, 0, [17]".to_string() }, optimized.changes[0]);
+        assert_eq!(Change::Removed { addrs: 18..19 }, optimized.changes[5]);
+        assert!(optimized.to_string().starts_with("Folded 5 instructions, threaded 0 jumps and removed 1 cells (17 cells left)\n"));
+
+        verify(&program, &optimized.program, &[vec![]]).unwrap();
+    }
+
+    #[test]
+    fn threads_jumps() {
+        let program = vec![
+            3, 20,              // 0: in [20]
+            1005, 20, 10,       // 2: jnz [20], 10
+            104, 1,             // 5: out 1
+            1106, 0, 13,        // 7: jz 0, 13
+            1106, 1, 3,         // 10: jz 1, 3 - never taken
+            1105, 1, 18,        // 13: jnz 1, 18
+            104, 2,             // 16: out 2 - unreachable
+            99,                 // 18: halt
+            0, 0,               // 19: data
+        ];
+
+        let optimized = Optimizer::new(&program).optimize();
+
+        assert_eq!(vec![
+            Change::Threaded { addr: 2, from: 10, to: 18 },
+            Change::Threaded { addr: 7, from: 13, to: 18 },
+            Change::Removed { addrs: 10..18 },
+        ], optimized.changes);
+        assert_eq!(vec![3, 20, 1005, 20, 18, 104, 1, 1106, 0, 18, 0, 0, 0, 0, 0, 0, 0, 0, 99], optimized.program);
+
+        verify(&program, &optimized.program, &[vec![0], vec![1]]).unwrap();
+    }
+
+    #[test]
+    fn keeps_data_and_self_modifying_code() {
+        let program = vec![
+            1101, 1, 2, 5,      // 0: add 1, 2 into the instruction at 4's parameter
+            104, 0,             // 4: out - prints 3
+            1001, 13, 0, 14,    // 6: add [13], 0, [14]
+            4, 14,              // 10: out [14]
+            99,                 // 12: halt
+            7, 0,               // 13: data
+        ];
+
+        // The written instruction and the data stay, and the data is folded into the reads.
+        let optimized = Optimizer::new(&program).optimize();
+        assert_eq!(vec![
+            Change::Folded { addr: 0, before: "add 1, 2, [5]".to_string(), after: "add 3, 0, [5]".to_string() },
+            Change::Folded { addr: 6, before: "add [13], 0, [14]".to_string(), after: "add 7, 0, [14]".to_string() },
+            Change::Folded { addr: 10, before: "out [14]".to_string(), after: "out 7".to_string() },
+            Change::Removed { addrs: 13..14 },
+        ], optimized.changes);
+        assert_eq!(vec![1101, 3, 0, 5, 104, 0, 1101, 7, 0, 14, 104, 7, 99], optimized.program);
+        verify(&program, &optimized.program, &[vec![]]).unwrap();
+
+        // Writing the address an instruction reads means it could read anything, so nothing changes.
+        let pointer = vec![1001, 9, 1, 7, 1001, 10, 0, 0, 99, 9, 5];
+        assert!(Optimizer::new(&pointer).optimize().changes.is_empty());
+
+        // Relative mode could write anywhere, so nothing is changed unless it's confined to the stack.
+        let relative = vec![
+            109, 100,           // 0: arb 100
+            1102, 2, 3, 15,     // 2: mul 2, 3, [15]
+            21101, 1, 2, 0,     // 6: add 1, 2, [rb+0]
+            204, 0,             // 10: out [rb+0]
+            4, 15,              // 12: out [15]
+            99,                 // 14: halt
+            0,                  // 15: data
+        ];
+        assert!(Optimizer::new(&relative).optimize().changes.is_empty());
+
+        let optimized = Optimizer::new(&relative).with_stack().optimize();
+        assert_eq!(vec![109, 100, 1101, 6, 0, 15, 21101, 3, 0, 0, 204, 0, 104, 6, 99], optimized.program);
+        verify(&relative, &optimized.program, &[vec![]]).unwrap();
+    }
+
+    #[test]
+    fn undecoded_code() {
+        // Extra mode digits decode like the interpreter runs them, so the code after isn't dead.
+        let program = vec![100104, 7, 104, 8, 99];
+        let optimized = Optimizer::new(&program).optimize();
+        assert_eq!(program, optimized.program);
+        verify(&program, &optimized.program, &[vec![]]).unwrap();
+
+        // Execution reaches 42, which isn't an instruction, so nothing is changed.
+        assert!(Optimizer::new(&[104, 1, 42, 104, 2, 99]).optimize().changes.is_empty());
+    }
+
+    #[test]
+    fn verify_mismatch() {
+        let mismatch = verify(&[3, 0, 4, 0, 99], &[3, 0, 104, 0, 99], &[vec![5]]).unwrap_err();
+
+        assert_eq!((vec![5], vec![0]), (mismatch.original.outputs, mismatch.optimized.outputs));
+        assert_eq!(Ok(ProgramState::Done), mismatch.optimized.stopped);
+    }
+
+    #[test]
+    fn diagnostic_programs() {
+        let boost = read_program("../day9/input.txt").unwrap();
+        let optimized = Optimizer::new(&boost).with_stack().optimize();
+
+        assert!(!optimized.changes.is_empty());
+        verify(&boost, &optimized.program, &[vec![1], vec![2]]).unwrap();
+    }
+}
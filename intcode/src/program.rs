@@ -0,0 +1,248 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+// Program files are comma separated lists of numbers.  Programs can span multiple lines, whitespace
+// around values is ignored, a trailing comma is allowed, and '#' starts a comment that runs to the
+// end of the line.  A line break doesn't separate values by itself, so values on different lines
+// still need a comma between them, at the end of one line or the start of the next:
+//
+//   # Outputs the number passed as input.
+//   3,0,    # read input into address 0
+//   4,0,    # output address 0
+//   99,
+
+/// Number of values write_program puts on each line.
+const VALUES_PER_LINE: usize = 16;
+
+/// Reason a program couldn't be parsed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseErrorKind {
+    /// A value isn't a number that fits in an i64.
+    InvalidValue(String),
+    /// Two commas in a row, or a comma before the first value.
+    EmptyValue,
+    /// Two values are separated by whitespace or a line break instead of a comma.
+    MissingComma,
+    /// The program doesn't contain any values.
+    Empty,
+}
+
+/// ParseError describes the first problem in a program file.  Line and column are 1-indexed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+
+        match &self.kind {
+            ParseErrorKind::InvalidValue(token) => write!(f, "invalid value '{}'", token),
+            ParseErrorKind::EmptyValue => write!(f, "missing value before ','"),
+            ParseErrorKind::MissingComma => write!(f, "expected ',' between values"),
+            ParseErrorKind::Empty => write!(f, "program is empty"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// ProgramError is returned when a program file can't be loaded.
+#[derive(Debug)]
+pub enum ProgramError {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgramError::Io(e) => write!(f, "couldn't read program: {}", e),
+            ProgramError::Parse(e) => write!(f, "invalid program: {}", e),
+        }
+    }
+}
+
+impl Error for ProgramError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProgramError::Io(e) => Some(e),
+            ProgramError::Parse(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ProgramError {
+    fn from(e: io::Error) -> Self {
+        ProgramError::Io(e)
+    }
+}
+
+impl From<ParseError> for ProgramError {
+    fn from(e: ParseError) -> Self {
+        ProgramError::Parse(e)
+    }
+}
+
+/// What the parser expects to see next.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+enum Expecting {
+    /// Start of the program - a value is required.
+    FirstValue,
+    /// After a comma - a value, or the end of the program for a trailing comma.
+    Value,
+    /// After a value - a comma or the end of the program.
+    Comma,
+}
+
+/// Reads the intcode program in the given file.
+pub fn read_program<P: AsRef<Path>>(filename: P) -> Result<Vec<i64>, ProgramError> {
+    let text = fs::read_to_string(filename)?;
+
+    Ok(parse_program(&text)?)
+}
+
+/// Parses the given program text into a list of values.  Returns an error pointing at the first
+/// value that isn't a number rather than skipping it, since skipping a value shifts every address after it.
+/// Values on different lines need a comma between them, so "3,0\n4,0" is a MissingComma error at the
+/// 4, while "3,0,\n4,0" and "3,0\n,4,0" are both [3, 0, 4, 0].
+pub fn parse_program(text: &str) -> Result<Vec<i64>, ParseError> {
+    let mut program = Vec::new();
+    let mut expecting = Expecting::FirstValue;
+
+    for (line_index, line) in text.lines().enumerate() {
+        let error = |column: usize, kind: ParseErrorKind| ParseError { line: line_index + 1, column: column + 1, kind };
+
+        // Everything after a '#' is a comment.
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+
+        let chars: Vec<char> = line.chars().collect();
+        let mut column = 0;
+
+        while column < chars.len() {
+            let c = chars[column];
+
+            if c.is_whitespace() {
+                column += 1;
+            } else if c == ',' {
+                if expecting != Expecting::Comma {
+                    return Err(error(column, ParseErrorKind::EmptyValue));
+                }
+
+                expecting = Expecting::Value;
+                column += 1;
+            } else {
+                if expecting == Expecting::Comma {
+                    return Err(error(column, ParseErrorKind::MissingComma));
+                }
+
+                let start = column;
+                while column < chars.len() && !chars[column].is_whitespace() && chars[column] != ',' {
+                    column += 1;
+                }
+
+                let token: String = chars[start..column].iter().collect();
+                let value = token.parse::<i64>()
+                    .map_err(|_| error(start, ParseErrorKind::InvalidValue(token)))?;
+
+                program.push(value);
+                expecting = Expecting::Comma;
+            }
+        }
+    }
+
+    if expecting == Expecting::FirstValue {
+        return Err(ParseError { line: 1, column: 1, kind: ParseErrorKind::Empty });
+    }
+
+    Ok(program)
+}
+
+/// Writes the program in the format parse_program reads, with a fixed number of values per line.
+/// Each line ends in a comment with the address of its first value.
+pub fn write_program<W: Write>(writer: &mut W, program: &[i64]) -> io::Result<()> {
+    let width = program.iter().map(|value| value.to_string().len()).max().unwrap_or(0);
+
+    for (line, values) in program.chunks(VALUES_PER_LINE).enumerate() {
+        for value in values {
+            write!(writer, "{:>width$},", value, width = width)?;
+        }
+
+        // Pad short last lines so address comments line up.
+        let padding = (VALUES_PER_LINE - values.len()) * (width + 1);
+        writeln!(writer, "{:padding$} # {}", "", line * VALUES_PER_LINE, padding = padding)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the program formatted the way write_program writes it.
+pub fn format_program(program: &[i64]) -> String {
+    let mut out = Vec::new();
+    write_program(&mut out, program).unwrap();
+
+    String::from_utf8(out).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_single_line() {
+        assert_eq!(Ok(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]), parse_program("1,9,10,3,2,3,11,0,99,30,40,50\n"));
+        assert_eq!(Ok(vec![1101, 100, -1, 4, 0]), parse_program("1101,100,-1,4,0"));
+    }
+
+    #[test]
+    fn parse_multiple_lines() {
+        let text = "# Outputs its input.\n3,0,    # read\n  4,0,  # write\n\n99,\n";
+
+        assert_eq!(Ok(vec![3, 0, 4, 0, 99]), parse_program(text));
+
+        // The comma between lines can end one line or start the next, but a line break alone isn't one.
+        assert_eq!(Ok(vec![3, 0, 4, 0]), parse_program("3,0,\n4,0"));
+        assert_eq!(Ok(vec![3, 0, 4, 0]), parse_program("3,0\n,4,0"));
+        assert_eq!(Ok(vec![3, 0, 4, 0]), parse_program("3,0 # read\n, 4,0"));
+        assert_eq!(Err(ParseError { line: 2, column: 1, kind: ParseErrorKind::MissingComma }), parse_program("3,0\n4,0"));
+    }
+
+    #[test]
+    fn parse_invalid_value() {
+        assert_eq!(
+            Err(ParseError { line: 2, column: 3, kind: ParseErrorKind::InvalidValue("1O".to_string()) }),
+            parse_program("3,0,\n4,1O,\n99"));
+
+        assert_eq!(
+            Err(ParseError { line: 1, column: 1, kind: ParseErrorKind::InvalidValue("99999999999999999999".to_string()) }),
+            parse_program("99999999999999999999"));
+    }
+
+    #[test]
+    fn parse_malformed() {
+        assert_eq!(Err(ParseError { line: 1, column: 5, kind: ParseErrorKind::EmptyValue }), parse_program("3,0,,4"));
+        assert_eq!(Err(ParseError { line: 1, column: 1, kind: ParseErrorKind::EmptyValue }), parse_program(",3"));
+        assert_eq!(Err(ParseError { line: 2, column: 1, kind: ParseErrorKind::MissingComma }), parse_program("3,0\n4,0"));
+        assert_eq!(Err(ParseError { line: 1, column: 1, kind: ParseErrorKind::Empty }), parse_program("# nothing here\n"));
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let program: Vec<i64> = (-10..30).collect();
+        let text = format_program(&program);
+
+        assert_eq!(3, text.lines().count());
+        assert!(text.lines().next().unwrap().ends_with("# 0"));
+        assert_eq!(Ok(program), parse_program(&text));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::memory::Memory;
+
+// Tools for finding where a program keeps its state, the way a cheat engine does: take a snapshot,
+// let the program run, then keep the addresses whose values changed the way the state did.
+// Once an address is found it can be read or patched directly with Memory::set.
+
+/// A copy of every value in memory at a point in time.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Snapshot {
+    values: Vec<i64>,
+}
+
+/// An address whose value is different between two snapshots.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Change {
+    pub addr: usize,
+    pub before: i64,
+    pub after: i64,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the given memory.
+    pub fn of(memory: &Memory) -> Snapshot {
+        Snapshot { values: memory.to_program() }
+    }
+
+    /// Returns the number of addresses in the snapshot - one more than the highest address that was set.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns whether no addresses were set when the snapshot was taken.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the value at the given address when the snapshot was taken.
+    pub fn get(&self, addr: usize) -> i64 {
+        self.values.get(addr).copied().unwrap_or(0)
+    }
+
+    /// Returns the addresses whose values differ between this snapshot and a later one, sorted by address.
+    pub fn diff(&self, later: &Snapshot) -> Vec<Change> {
+        (0..self.len().max(later.len()))
+            .map(|addr| Change { addr, before: self.get(addr), after: later.get(addr) })
+            .filter(|change| change.before != change.after)
+            .collect()
+    }
+}
+
+/// Filter applied to the candidates in a search, comparing their current value to the value in the
+/// previous snapshot.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Filter {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    Equals(i64),
+}
+
+impl Filter {
+    /// Returns whether an address that went from before to after passes this filter.
+    fn matches(&self, before: i64, after: i64) -> bool {
+        match *self {
+            Filter::Changed => after != before,
+            Filter::Unchanged => after == before,
+            Filter::Increased => after > before,
+            Filter::Decreased => after < before,
+            Filter::Equals(value) => after == value,
+        }
+    }
+}
+
+/// A search narrows down a set of candidate addresses by repeatedly filtering them against the
+/// program's memory.  Every address is a candidate until the first filter, including addresses
+/// the program sets after the search starts.
+#[derive(Debug, Clone)]
+pub struct Search {
+    candidates: Vec<usize>,
+    snapshot: Snapshot,
+    filtered: bool,
+}
+
+impl Search {
+    /// Starts a search over the addresses in the given memory.
+    pub fn new(memory: &Memory) -> Search {
+        Search {
+            candidates: Vec::new(),
+            snapshot: Snapshot::of(memory),
+            filtered: false,
+        }
+    }
+
+    /// Keeps the candidates whose value in memory passes the filter, then snapshots memory
+    /// so the next filter compares against the current values.  Returns the remaining candidates.
+    pub fn filter(&mut self, memory: &Memory, filter: Filter) -> &[usize] {
+        let current = Snapshot::of(memory);
+
+        if !self.filtered {
+            self.candidates = (0..self.snapshot.len().max(current.len())).collect();
+            self.filtered = true;
+        }
+
+        let before = &self.snapshot;
+        self.candidates.retain(|&addr| filter.matches(before.get(addr), current.get(addr)));
+        self.snapshot = current;
+
+        &self.candidates
+    }
+
+    /// Returns the addresses that have passed every filter so far.  Empty until the first filter.
+    pub fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+
+    /// Returns the only remaining candidate, or None if the search hasn't narrowed down to one address.
+    pub fn found(&self) -> Option<usize> {
+        match self.candidates[..] {
+            [addr] => Some(addr),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Computer;
+
+    /// Program that counts the inputs it has read at address 20 and stores the last input at address 21.
+    fn counter() -> Computer {
+        Computer::new(vec![
+            3, 21,           // read input into 21
+            1001, 20, 1, 20, // add 1 to 20
+            1105, 1, 0,      // jump to 0
+            99,
+        ])
+    }
+
+    #[test]
+    fn diff() {
+        let mut computer = counter();
+        let before = Snapshot::of(&computer.memory);
+
+        computer.input(7);
+        computer.run();
+
+        assert_eq!(vec![
+            Change { addr: 20, before: 0, after: 1 },
+            Change { addr: 21, before: 0, after: 7 },
+        ], before.diff(&Snapshot::of(&computer.memory)));
+    }
+
+    #[test]
+    fn search() {
+        let mut computer = counter();
+        let mut search = Search::new(&computer.memory);
+
+        computer.input(5);
+        computer.run();
+        search.filter(&computer.memory, Filter::Changed);
+        assert_eq!(&[20, 21], search.candidates());
+
+        computer.input(5);
+        computer.run();
+        assert_eq!(&[20], search.filter(&computer.memory, Filter::Increased));
+        assert_eq!(Some(20), search.found());
+
+        assert!(search.filter(&computer.memory, Filter::Equals(3)).is_empty());
+        assert_eq!(None, search.found());
+    }
+}
@@ -0,0 +1,223 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rayon::prelude::*;
+
+// Searches run a machine on every input vector from a generator, in parallel on the rayon pool.
+// Each input gets a fresh clone of the template machine, so evaluations don't see each other's state.
+//
+//   // Phase settings that give the highest output.
+//   let (phases, output) = InputSearch::new(&computer, permutations(&[0, 1, 2, 3, 4]))
+//       .best(|computer, phases| Some(run_amplifiers(computer, phases)))
+//       .unwrap();
+
+/// Callback for reporting the number of inputs a search has evaluated.
+type Report<'a> = Box<dyn Fn(usize) + Send + Sync + 'a>;
+
+/// Search over the inputs from a generator, running each one on a clone of the template machine.
+pub struct InputSearch<'a, M, I> {
+    template: &'a M,
+    inputs: I,
+    progress: Option<(usize, Report<'a>)>,
+}
+
+impl<'a, M, I> InputSearch<'a, M, I>
+    where M: Clone + Send + Sync, I: IntoIterator<Item = Vec<i64>>, I::IntoIter: Send {
+
+    /// Constructs a search that runs each input from the generator on a clone of the template.
+    pub fn new(template: &'a M, inputs: I) -> InputSearch<'a, M, I> {
+        InputSearch { template, inputs, progress: None }
+    }
+
+    /// Calls report with the number of inputs evaluated so far after every `every` inputs.
+    pub fn with_progress<F: Fn(usize) + Send + Sync + 'a>(mut self, every: usize, report: F) -> Self {
+        self.progress = Some((every.max(1), Box::new(report)));
+        self
+    }
+
+    /// Returns the input with the highest score, and its score.  Inputs where evaluate returns None
+    /// are skipped.  If several inputs have the highest score, any one of them is returned.
+    pub fn best<K, F>(self, evaluate: F) -> Option<(Vec<i64>, K)>
+        where K: Ord + Send, F: Fn(&mut M, &[i64]) -> Option<K> + Send + Sync {
+        let template = self.template;
+        let progress = Progress::new(&self.progress);
+
+        self.inputs.into_iter().par_bridge()
+            .filter_map(|input| {
+                let score = evaluate(&mut template.clone(), &input);
+                progress.tick();
+                score.map(|score| (input, score))
+            })
+            .max_by(|(_, a), (_, b)| a.cmp(b))
+    }
+
+    /// Returns every input that matches, sorted.
+    pub fn all<F>(self, matches: F) -> Vec<Vec<i64>>
+        where F: Fn(&mut M, &[i64]) -> bool + Send + Sync {
+        let template = self.template;
+        let progress = Progress::new(&self.progress);
+
+        let mut found: Vec<Vec<i64>> = self.inputs.into_iter().par_bridge()
+            .filter(|input| {
+                let matched = matches(&mut template.clone(), input);
+                progress.tick();
+                matched
+            })
+            .collect();
+
+        found.sort();
+        found
+    }
+
+    /// Returns the first input found where evaluate returns a value, and the value.  Stops
+    /// evaluating inputs as soon as one is found, so the input isn't necessarily the first one
+    /// from the generator.
+    pub fn find_any<K, F>(self, evaluate: F) -> Option<(Vec<i64>, K)>
+        where K: Send, F: Fn(&mut M, &[i64]) -> Option<K> + Send + Sync {
+        let template = self.template;
+        let progress = Progress::new(&self.progress);
+
+        self.inputs.into_iter().par_bridge()
+            .find_map_any(|input| {
+                let value = evaluate(&mut template.clone(), &input);
+                progress.tick();
+                value.map(|value| (input, value))
+            })
+    }
+}
+
+/// Counts evaluated inputs and reports progress.
+struct Progress<'p, 'a> {
+    done: AtomicUsize,
+    report: &'p Option<(usize, Report<'a>)>,
+}
+
+impl<'p, 'a> Progress<'p, 'a> {
+    fn new(report: &'p Option<(usize, Report<'a>)>) -> Self {
+        Progress { done: AtomicUsize::new(0), report }
+    }
+
+    fn tick(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if let Some((every, report)) = self.report {
+            if done.is_multiple_of(*every) {
+                report(done);
+            }
+        }
+    }
+}
+
+/// Returns every input vector whose values come from the given ranges - the cartesian product
+/// of the ranges.  product(&[0..2, 0..2]) returns [0, 0], [0, 1], [1, 0], [1, 1].
+pub fn product(ranges: &[Range<i64>]) -> impl Iterator<Item = Vec<i64>> + Send {
+    let ranges = ranges.to_vec();
+    let total: usize = ranges.iter().map(|range| range.clone().count()).product();
+
+    (0..total).map(move |mut index| {
+        let mut input = vec![0; ranges.len()];
+
+        // The last range changes fastest.
+        for (i, range) in ranges.iter().enumerate().rev() {
+            let len = range.clone().count();
+            input[i] = range.start + (index % len) as i64;
+            index /= len;
+        }
+
+        input
+    })
+}
+
+/// Returns every ordering of the given values.
+pub fn permutations(values: &[i64]) -> impl Iterator<Item = Vec<i64>> + Send {
+    let mut all = Vec::new();
+    permute(&mut values.to_vec(), 0, &mut all);
+
+    all.into_iter()
+}
+
+/// Adds every ordering of values[k..] to all, keeping values[..k] in place.
+fn permute(values: &mut Vec<i64>, k: usize, all: &mut Vec<Vec<i64>>) {
+    if k == values.len() {
+        all.push(values.clone());
+        return;
+    }
+
+    for i in k..values.len() {
+        values.swap(k, i);
+        permute(values, k + 1, all);
+        values.swap(k, i);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::Computer;
+
+    /// Outputs 10 * the first input + the second input.
+    fn combine() -> Computer {
+        Computer::new(vec![3, 20, 3, 21, 1002, 20, 10, 20, 1, 20, 21, 22, 4, 22, 99])
+    }
+
+    #[test]
+    fn test_product() {
+        assert_eq!(vec![vec![0, 5], vec![0, 6], vec![1, 5], vec![1, 6]], product(&[0..2, 5..7]).collect::<Vec<_>>());
+        assert_eq!(0, product(&[0..2, 0..0]).count());
+    }
+
+    #[test]
+    fn test_permutations() {
+        let mut all: Vec<_> = permutations(&[1, 2, 3]).collect();
+        all.sort();
+
+        assert_eq!(vec![vec![1, 2, 3], vec![1, 3, 2], vec![2, 1, 3], vec![2, 3, 1], vec![3, 1, 2], vec![3, 2, 1]], all);
+    }
+
+    /// Runs the combine program with the given inputs.
+    fn run(computer: &mut Computer, input: &[i64]) -> i64 {
+        input.iter().for_each(|&value| computer.input(value));
+        computer.run().unwrap()
+    }
+
+    #[test]
+    fn best() {
+        let best = InputSearch::new(&combine(), product(&[0..10, 0..10]))
+            .best(|computer, input| Some(run(computer, input)));
+
+        assert_eq!(Some((vec![9, 9], 99)), best);
+    }
+
+    #[test]
+    fn all() {
+        let found = InputSearch::new(&combine(), product(&[0..10, 0..10]))
+            .all(|computer, input| run(computer, input) % 25 == 0);
+
+        assert_eq!(vec![vec![0, 0], vec![2, 5], vec![5, 0], vec![7, 5]], found);
+    }
+
+    #[test]
+    fn find_any() {
+        let found = InputSearch::new(&combine(), product(&[0..10, 0..10]))
+            .find_any(|computer, input| Some(run(computer, input)).filter(|&output| output == 42));
+
+        assert_eq!(Some((vec![4, 2], 42)), found);
+    }
+
+    #[test]
+    fn progress() {
+        let reports = Mutex::new(Vec::new());
+
+        let found = InputSearch::new(&combine(), product(&[0..10, 0..10]))
+            .with_progress(25, |done| reports.lock().unwrap().push(done))
+            .all(|_, _| true);
+
+        let mut reports = reports.into_inner().unwrap();
+        reports.sort();
+
+        assert_eq!(100, found.len());
+        assert_eq!(vec![25, 50, 75, 100], reports);
+    }
+}
@@ -0,0 +1,27 @@
+use crate::asm::Module;
+
+// Routines for intcode programs, linked into a program with Linker::with_stdlib.  Each routine is
+// called with the calling convention in asm.rs, and keeps its working values in its own module,
+// so routines can't be called recursively.
+//
+//   print_int n                         outputs n as ASCII decimal digits
+//   read_int -> n                       reads an ASCII decimal number
+//   divmod a, b -> quotient, remainder  divides like Rust's / and %
+//   pow base, exponent -> result        raises base to a power
+//
+// Pushing and popping the stack are the push and pop instructions in the assembler.
+
+/// Source of each module in the standard library.
+const SOURCES: [(&str, &str); 4] = [
+    ("print", include_str!("../stdlib/print.asm")),
+    ("read", include_str!("../stdlib/read.asm")),
+    ("divmod", include_str!("../stdlib/divmod.asm")),
+    ("pow", include_str!("../stdlib/pow.asm")),
+];
+
+/// Returns the standard library's modules.
+pub fn modules() -> Vec<Module> {
+    SOURCES.iter()
+        .map(|(name, source)| Module::parse(name, source).unwrap_or_else(|e| panic!("stdlib module {}: {}", name, e)))
+        .collect()
+}
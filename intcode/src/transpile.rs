@@ -0,0 +1,819 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::program::{self, ProgramError};
+
+// The transpiler turns a static intcode program into a Rust module with a Computer that has the
+// same queue-based API as the interpreter (input, run, output, reset).  Code reachable from
+// address 0 through fallthrough and immediate-mode jumps is split into basic blocks, and each block
+// becomes straight-line Rust.  Everything else runs on an interpreter in the generated module:
+// jumps to addresses that aren't the start of a block, code that's only reached through a
+// position or relative mode jump, and blocks the program has overwritten.
+//
+// Build scripts generate the module into OUT_DIR and include it:
+//
+//   intcode::transpile::transpile_file("input.txt", out_dir.join("program.rs"))?;
+//
+//   mod program {
+//       include!(concat!(env!("OUT_DIR"), "/program.rs"));
+//   }
+
+/// Addresses past this are kept in a map instead of the generated computer's memory vector,
+/// so a program that writes to a huge address doesn't allocate all of the memory before it.
+const DENSE_MEMORY: usize = 1 << 20;
+
+/// A parameter as it appears in the program: its mode, and the raw value following the opcode.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct Operand {
+    pub(crate) mode: i64,
+    pub(crate) value: i64,
+}
+
+/// An instruction decoded from the static program.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Decoded {
+    pub(crate) addr: usize,
+    pub(crate) opcode: i64,
+    pub(crate) operands: Vec<Operand>,
+}
+
+impl Decoded {
+    /// Decodes the instruction at the given address, or returns None if it isn't a valid instruction.
+    /// Mode digits past the last parameter are ignored, like the interpreter ignores them.
+    pub(crate) fn parse(program: &[i64], addr: usize) -> Option<Decoded> {
+        let value = *program.get(addr)?;
+        if value < 0 {
+            return None;
+        }
+
+        let opcode = value % 100;
+        let parameters = match opcode {
+            1 | 2 | 7 | 8 => 3,
+            5 | 6 => 2,
+            3 | 4 | 9 => 1,
+            99 => 0,
+            _ => return None,
+        };
+
+        let mut modes = value / 100;
+        let mut operands = Vec::new();
+        for i in 0..parameters {
+            let mode = modes % 10;
+            modes /= 10;
+
+            if mode > 2 {
+                return None;
+            }
+
+            operands.push(Operand { mode, value: *program.get(addr + i + 1)? });
+        }
+
+        Some(Decoded { addr, opcode, operands })
+    }
+
+    /// Returns the address of the instruction after this one.
+    pub(crate) fn next(&self) -> usize {
+        self.addr + self.operands.len() + 1
+    }
+
+    /// Returns whether execution can continue to the next instruction.
+    pub(crate) fn falls_through(&self) -> bool {
+        match self.opcode {
+            99 => false,
+            // A jump with an immediate condition always or never jumps.
+            5 => !(self.operands[0].mode == 1 && self.operands[0].value != 0),
+            6 => !(self.operands[0].mode == 1 && self.operands[0].value == 0),
+            _ => true,
+        }
+    }
+
+    /// Returns whether this instruction can jump.
+    pub(crate) fn can_jump(&self) -> bool {
+        match self.opcode {
+            // A jump with an immediate condition that never jumps.
+            5 => !(self.operands[0].mode == 1 && self.operands[0].value == 0),
+            6 => !(self.operands[0].mode == 1 && self.operands[0].value != 0),
+            _ => false,
+        }
+    }
+
+    /// Returns the target of this jump if it's known statically.
+    pub(crate) fn jump_target(&self) -> Option<usize> {
+        match self.opcode {
+            5 | 6 if self.operands[1].mode == 1 && self.operands[1].value >= 0 => Some(self.operands[1].value as usize),
+            _ => None,
+        }
+    }
+
+    /// Returns whether this instruction ends a basic block.
+    pub(crate) fn ends_block(&self) -> bool {
+        matches!(self.opcode, 5 | 6 | 99)
+    }
+}
+
+impl fmt::Display for Operand {
+    /// Formats the parameter as [addr] for position mode, the value for immediate mode, and
+    /// [rb+offset] for relative mode.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            0 => write!(f, "[{}]", self.value),
+            1 => write!(f, "{}", self.value),
+            _ if self.value < 0 => write!(f, "[rb-{}]", -self.value),
+            _ => write!(f, "[rb+{}]", self.value),
+        }
+    }
+}
+
+impl fmt::Display for Decoded {
+    /// Formats the instruction like 'add [100], 5, [101]'.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.opcode {
+            1 => "add",
+            2 => "mul",
+            3 => "in",
+            4 => "out",
+            5 => "jnz",
+            6 => "jz",
+            7 => "lt",
+            8 => "eq",
+            9 => "arb",
+            _ => "halt",
+        };
+
+        write!(f, "{}", name)?;
+        for (i, operand) in self.operands.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, operand)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A basic block: instructions that always run in order, starting at the first one.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Block {
+    pub(crate) instructions: Vec<Decoded>,
+}
+
+impl Block {
+    pub(crate) fn start(&self) -> usize {
+        self.instructions[0].addr
+    }
+
+    pub(crate) fn end(&self) -> usize {
+        self.instructions.last().unwrap().next()
+    }
+}
+
+/// Splits the code reachable from the entry addresses into basic blocks, ordered by address.
+pub(crate) fn find_blocks(program: &[i64], entries: &[usize]) -> Vec<Block> {
+    // Find every instruction reachable through fallthrough and immediate jumps.
+    let mut instructions = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    let mut queue = VecDeque::new();
+
+    for &entry in entries {
+        leaders.insert(entry);
+        queue.push_back(entry);
+    }
+
+    while let Some(addr) = queue.pop_front() {
+        if instructions.contains_key(&addr) {
+            continue;
+        }
+
+        let instruction = match Decoded::parse(program, addr) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+
+        if let Some(target) = instruction.jump_target() {
+            leaders.insert(target);
+            queue.push_back(target);
+        }
+
+        if instruction.falls_through() {
+            if instruction.ends_block() {
+                leaders.insert(instruction.next());
+            }
+            queue.push_back(instruction.next());
+        }
+
+        // Input is a block leader, so the computer can resume there after waiting for input.
+        if instruction.opcode == 3 {
+            leaders.insert(addr);
+        }
+
+        instructions.insert(addr, instruction);
+    }
+
+    // Instructions that overlap another instruction's parameters can't be compiled consistently.
+    let mut covered = BTreeSet::new();
+    let mut overlapping = BTreeSet::new();
+    for instruction in instructions.values() {
+        for addr in instruction.addr..instruction.next() {
+            if !covered.insert(addr) {
+                overlapping.insert(addr);
+            }
+        }
+    }
+
+    // Walk forward from each leader until the block ends.
+    let mut blocks = Vec::new();
+    for &leader in &leaders {
+        let mut block = Vec::new();
+        let mut addr = leader;
+
+        while let Some(instruction) = instructions.get(&addr) {
+            if (!block.is_empty() && leaders.contains(&addr))
+                || (instruction.addr..instruction.next()).any(|a| overlapping.contains(&a)) {
+                break;
+            }
+
+            block.push(instruction.clone());
+            addr = instruction.next();
+
+            if instruction.ends_block() {
+                break;
+            }
+        }
+
+        if !block.is_empty() {
+            blocks.push(Block { instructions: block });
+        }
+    }
+
+    blocks
+}
+
+/// Returns a Rust expression for the address of a relative mode parameter.
+fn relative_addr(offset: i64) -> String {
+    match offset {
+        0 => "self.relative_base as usize".to_string(),
+        n if n < 0 => format!("(self.relative_base - {}) as usize", -n),
+        n => format!("(self.relative_base + {}) as usize", n),
+    }
+}
+
+/// Returns a Rust expression for the value of the given parameter.
+fn read_expr(operand: &Operand) -> String {
+    match operand.mode {
+        0 => format!("self.read({})", operand.value as usize),
+        1 => format!("{}i64", operand.value),
+        _ => format!("self.read({})", relative_addr(operand.value)),
+    }
+}
+
+/// Returns a Rust expression for the address a parameter writes to.  Immediate mode writes are
+/// treated as position mode, like the interpreter does.
+fn write_addr_expr(operand: &Operand) -> String {
+    match operand.mode {
+        2 => relative_addr(operand.value),
+        _ => format!("{}", operand.value as usize),
+    }
+}
+
+/// Returns whether a write through the given parameter could change compiled code.
+fn may_write_code(operand: &Operand, code: &[Option<usize>]) -> bool {
+    match operand.mode {
+        2 => true,
+        _ => operand.value >= 0 && code.get(operand.value as usize).is_some_and(Option::is_some),
+    }
+}
+
+/// Writes the Rust code for a single block to out.
+fn write_block(out: &mut String, index: usize, block: &Block, code: &[Option<usize>]) {
+    writeln!(out, "                {} if self.compiled[{}] => {{", block.start(), index).unwrap();
+
+    for instruction in &block.instructions {
+        let ops = &instruction.operands;
+        let (addr, next) = (instruction.addr, instruction.next());
+
+        // After writing to code, leave the block so the rest of it isn't run from a stale copy.
+        let write = |out: &mut String, operand: &Operand, value: String| {
+            writeln!(out, "                    self.write({}, {});", write_addr_expr(operand), value).unwrap();
+            if may_write_code(operand, code) {
+                writeln!(out, "                    if self.modified_code {{ self.modified_code = false; self.pc = {}; continue; }}", next).unwrap();
+            }
+        };
+
+        match instruction.opcode {
+            1 => write(out, &ops[2], format!("{} + {}", read_expr(&ops[0]), read_expr(&ops[1]))),
+            2 => write(out, &ops[2], format!("{} * {}", read_expr(&ops[0]), read_expr(&ops[1]))),
+            3 => {
+                writeln!(out, "                    let value = match self.input.pop_front() {{").unwrap();
+                writeln!(out, "                        Some(value) => value,").unwrap();
+                writeln!(out, "                        None => {{ self.pc = {}; self.state = ProgramState::WaitingForInput; return Ok(()); }}", addr).unwrap();
+                writeln!(out, "                    }};").unwrap();
+                write(out, &ops[0], "value".to_string());
+            }
+            4 => writeln!(out, "                    self.output.push_back({});", read_expr(&ops[0])).unwrap(),
+            5 | 6 => {
+                let comparison = if instruction.opcode == 5 { "!=" } else { "==" };
+                let target = match instruction.jump_target() {
+                    Some(target) => format!("{}", target),
+                    None => format!("{} as usize", read_expr(&ops[1])),
+                };
+
+                writeln!(out, "                    if {} {} 0 {{ self.pc = {}; continue; }}", read_expr(&ops[0]), comparison, target).unwrap();
+            }
+            7 => write(out, &ops[2], format!("if {} < {} {{ 1 }} else {{ 0 }}", read_expr(&ops[0]), read_expr(&ops[1]))),
+            8 => write(out, &ops[2], format!("if {} == {} {{ 1 }} else {{ 0 }}", read_expr(&ops[0]), read_expr(&ops[1]))),
+            9 => writeln!(out, "                    self.relative_base += {};", read_expr(&ops[0])).unwrap(),
+            _ => writeln!(out, "                    self.pc = {}; self.state = ProgramState::Done; return Ok(());", addr).unwrap(),
+        }
+    }
+
+    let last = block.instructions.last().unwrap();
+    if last.opcode != 99 {
+        writeln!(out, "                    self.pc = {};", last.next()).unwrap();
+    }
+
+    writeln!(out, "                }}").unwrap();
+}
+
+/// Returns the source of a Rust module that runs the given program.
+pub fn transpile(program: &[i64]) -> String {
+    let blocks = find_blocks(program, &[0]);
+
+    // Block containing each address of compiled code, used to invalidate blocks the program overwrites.
+    let code_len = blocks.iter().map(Block::end).max().unwrap_or(0);
+    let mut code = vec![None; code_len];
+    for (index, block) in blocks.iter().enumerate() {
+        for slot in &mut code[block.start()..block.end()] {
+            *slot = Some(index);
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(out, "// Generated by intcode::transpile from a {} value program.  Don't edit.", program.len()).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "use std::collections::{{HashMap, VecDeque}};").unwrap();
+    writeln!(out, "use std::fmt;").unwrap();
+    writeln!(out).unwrap();
+
+    let values: Vec<String> = program.iter().map(i64::to_string).collect();
+    writeln!(out, "const PROGRAM: [i64; {}] = [{}];", program.len(), values.join(", ")).unwrap();
+
+    let blocks_of: Vec<String> = code.iter().map(|block| match block {
+        Some(index) => index.to_string(),
+        None => "NO_BLOCK".to_string(),
+    }).collect();
+    writeln!(out, "const NO_BLOCK: usize = usize::MAX;").unwrap();
+    writeln!(out, "const CODE_BLOCKS: [usize; {}] = [{}];", code.len(), blocks_of.join(", ")).unwrap();
+    writeln!(out, "const NUM_BLOCKS: usize = {};", blocks.len()).unwrap();
+    writeln!(out, "const DENSE_MEMORY: usize = {};", DENSE_MEMORY).unwrap();
+    writeln!(out).unwrap();
+
+    out.push_str(RUNTIME);
+
+    writeln!(out).unwrap();
+    writeln!(out, "impl Computer {{").unwrap();
+    writeln!(out, "    /// Runs compiled blocks, and the interpreter for everything else, until the program halts or needs input.").unwrap();
+    writeln!(out, "    /// Stops at the instruction that caused an error if the program can't be executed.").unwrap();
+    writeln!(out, "    #[allow(clippy::all, unreachable_code, unused_parens)]").unwrap();
+    writeln!(out, "    fn run_compiled(&mut self) -> Result<(), IntcodeError> {{").unwrap();
+    writeln!(out, "        while self.state == ProgramState::Runnable {{").unwrap();
+    writeln!(out, "            match self.pc {{").unwrap();
+    for (index, block) in blocks.iter().enumerate() {
+        write_block(&mut out, index, block, &code);
+    }
+    writeln!(out, "                _ => self.step()?,").unwrap();
+    writeln!(out, "            }}").unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "        Ok(())").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    out
+}
+
+/// Transpiles the program in the input file, writing the Rust module to the output file.
+pub fn transpile_file<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output: Q) -> Result<(), ProgramError> {
+    let program = program::read_program(input)?;
+    fs::write(output, transpile(&program))?;
+
+    Ok(())
+}
+
+/// Part of the generated module that doesn't depend on the program: the computer's state, its
+/// public API, memory access and the fallback interpreter.  Errors match intcode::IntcodeError.
+const RUNTIME: &str = r#"/// State of the program at a specific program counter.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ProgramState {
+    Done,
+    Runnable,
+    WaitingForInput,
+}
+
+/// IntcodeError is returned when a program does something the computer can't execute.  The computer
+/// stops at the instruction that caused the error, so running it again returns the same error.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum IntcodeError {
+    /// The instruction at pc isn't a built in opcode.
+    UnknownOpcode { opcode: i64 },
+    /// A parameter's mode isn't position, immediate or relative.
+    InvalidParameterMode { mode: u32 },
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntcodeError::UnknownOpcode { opcode } => write!(f, "Unknown opcode {}", opcode),
+            IntcodeError::InvalidParameterMode { mode } => write!(f, "Invalid parameter mode {}", mode),
+        }
+    }
+}
+
+impl std::error::Error for IntcodeError {}
+
+/// Intcode computer running a transpiled program.
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct Computer {
+    pc: usize,
+    relative_base: i64,
+    state: ProgramState,
+    input: VecDeque<i64>,
+    pub output: VecDeque<i64>,
+    memory: Vec<i64>,
+    sparse_memory: HashMap<usize, i64>,
+    compiled: Vec<bool>,
+    modified_code: bool,
+}
+
+#[allow(dead_code)]
+impl Computer {
+    /// Constructs a new Computer that will run the transpiled program.
+    pub fn new() -> Computer {
+        Computer {
+            pc: 0,
+            relative_base: 0,
+            state: ProgramState::Runnable,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            memory: PROGRAM.to_vec(),
+            sparse_memory: HashMap::new(),
+            compiled: vec![true; NUM_BLOCKS],
+            modified_code: false,
+        }
+    }
+
+    /// Uses the given value as the next input to this computer.  Inputs will be used
+    /// in the order they were provided if input is called multiple times.
+    pub fn input(&mut self, value: i64) {
+        self.input.push_back(value);
+
+        if self.state == ProgramState::WaitingForInput {
+            self.state = ProgramState::Runnable;
+        }
+    }
+
+    /// Provides the given ascii string as input for the computer.
+    pub fn text_input(&mut self, value: &str) {
+        value.chars().for_each(|c| self.input(c as i64));
+    }
+
+    /// Returns whether this computer has input that the program hasn't read yet.
+    pub fn has_input(&self) -> bool {
+        !self.input.is_empty()
+    }
+
+    /// Returns the state of the program.
+    pub fn state(&self) -> &ProgramState {
+        &self.state
+    }
+
+    /// Returns the address of the next instruction the program will run.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Returns the last value this computer output, or empty if it hasn't output any values.
+    pub fn last_output(&self) -> Option<i64> {
+        self.output.back().cloned()
+    }
+
+    /// Returns all of the output this computer has produced, consuming it in the process.
+    pub fn dump_output(&mut self) -> Vec<i64> {
+        self.output.drain(..).collect()
+    }
+
+    /// Returns whether this computer can run - if it isn't done or waiting for input.
+    pub fn is_runnable(&self) -> bool {
+        self.state == ProgramState::Runnable
+    }
+
+    /// Runs the program in this computer until it either halts or blocks waiting for input.
+    /// Returns the last value the program output.  Panics if the program can't be executed.
+    pub fn run(&mut self) -> Option<i64> {
+        self.try_run().unwrap_or_else(|e| panic!("{} at pc {}", e, self.pc))
+    }
+
+    /// Runs the program in this computer until it either halts or blocks waiting for input, returning
+    /// the last value the program output.  Stops at the instruction that caused an error if the
+    /// program can't be executed.
+    pub fn try_run(&mut self) -> Result<Option<i64>, IntcodeError> {
+        self.run_compiled()?;
+        Ok(self.last_output())
+    }
+
+    /// Returns the value of the given memory address.
+    pub fn get(&self, addr: usize) -> i64 {
+        self.read(addr)
+    }
+
+    /// Sets the memory at the given address.
+    pub fn set(&mut self, addr: usize, value: i64) {
+        self.write(addr, value);
+        self.modified_code = false;
+    }
+
+    /// Resets this program to its original state.
+    pub fn reset(&mut self) {
+        *self = Computer::new();
+    }
+
+    fn read(&self, addr: usize) -> i64 {
+        if addr < self.memory.len() {
+            self.memory[addr]
+        } else {
+            self.sparse_memory.get(&addr).copied().unwrap_or(0)
+        }
+    }
+
+    fn write(&mut self, addr: usize, value: i64) {
+        // Overwriting compiled code sends that block to the interpreter from now on.
+        if addr < CODE_BLOCKS.len() && CODE_BLOCKS[addr] != NO_BLOCK && self.memory[addr] != value {
+            self.compiled[CODE_BLOCKS[addr]] = false;
+            self.modified_code = true;
+        }
+
+        if addr < self.memory.len() {
+            self.memory[addr] = value;
+        } else if addr < DENSE_MEMORY {
+            self.memory.resize(addr + 1, 0);
+            self.memory[addr] = value;
+        } else {
+            self.sparse_memory.insert(addr, value);
+        }
+    }
+
+    /// Returns the value of the given parameter of the instruction at pc.  Modes have been checked.
+    fn parameter(&self, parameter: usize) -> i64 {
+        let value = self.read(self.pc + parameter + 1);
+
+        match self.mode(parameter) {
+            0 => self.read(value as usize),
+            2 => self.read((value + self.relative_base) as usize),
+            _ => value,
+        }
+    }
+
+    /// Returns the address the given parameter of the instruction at pc writes to.  Modes have been checked.
+    fn index_parameter(&self, parameter: usize) -> usize {
+        let value = self.read(self.pc + parameter + 1);
+
+        match self.mode(parameter) {
+            2 => (value + self.relative_base) as usize,
+            _ => value as usize,
+        }
+    }
+
+    /// Returns the mode of the given parameter of the instruction at pc.
+    fn mode(&self, parameter: usize) -> i64 {
+        (self.read(self.pc) / 10i64.pow(parameter as u32 + 2)) % 10
+    }
+
+    /// Interprets the instruction at pc.  Checks the opcode and the modes of all of its parameters
+    /// before running it, so an instruction that fails leaves the computer unchanged.
+    fn step(&mut self) -> Result<(), IntcodeError> {
+        let pc = self.pc;
+        let instruction = self.read(pc);
+
+        let parameters = match instruction % 100 {
+            1 | 2 | 7 | 8 => 3,
+            5 | 6 => 2,
+            3 | 4 | 9 => 1,
+            99 => 0,
+            _ => return Err(IntcodeError::UnknownOpcode { opcode: instruction }),
+        };
+
+        if let Some(mode) = (0..parameters).map(|parameter| self.mode(parameter)).find(|&mode| mode > 2) {
+            return Err(IntcodeError::InvalidParameterMode { mode: mode as u32 });
+        }
+
+        self.pc = match instruction % 100 {
+            1 => { let value = self.parameter(0) + self.parameter(1); self.write(self.index_parameter(2), value); pc + 4 }
+            2 => { let value = self.parameter(0) * self.parameter(1); self.write(self.index_parameter(2), value); pc + 4 }
+            3 => match self.input.pop_front() {
+                Some(value) => { self.write(self.index_parameter(0), value); pc + 2 }
+                None => { self.state = ProgramState::WaitingForInput; pc }
+            },
+            4 => { self.output.push_back(self.parameter(0)); pc + 2 }
+            5 => if self.parameter(0) != 0 { self.parameter(1) as usize } else { pc + 3 },
+            6 => if self.parameter(0) == 0 { self.parameter(1) as usize } else { pc + 3 },
+            7 => { let value = (self.parameter(0) < self.parameter(1)) as i64; self.write(self.index_parameter(2), value); pc + 4 }
+            8 => { let value = (self.parameter(0) == self.parameter(1)) as i64; self.write(self.index_parameter(2), value); pc + 4 }
+            9 => { self.relative_base += self.parameter(0); pc + 2 }
+            _ => { self.state = ProgramState::Done; pc }
+        };
+
+        self.modified_code = false;
+        Ok(())
+    }
+}
+
+impl Default for Computer {
+    fn default() -> Self {
+        Computer::new()
+    }
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    use super::*;
+    use crate::Computer;
+
+    /// Prints a run's output, then its state and pc or the error that stopped it.
+    const HARNESS: &str = r#"
+        mod program {
+            include!("program.rs");
+        }
+
+        fn main() {
+            let mut computer = program::Computer::new();
+            std::env::args().skip(1).for_each(|value| computer.input(value.parse().unwrap()));
+
+            let result = computer.try_run();
+            println!("{:?}", computer.dump_output());
+            match result {
+                Ok(_) => println!("{:?} at pc {}", computer.state(), computer.pc()),
+                Err(e) => println!("{} at pc {}", e, computer.pc()),
+            }
+        }
+    "#;
+
+    /// Transpiles the program, and compiles it into an executable that runs it with its arguments
+    /// as input.  Returns the executable's path.
+    fn build(name: &str, program: &[i64]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("intcode-transpile-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("program.rs"), transpile(program)).unwrap();
+        fs::write(dir.join("main.rs"), HARNESS).unwrap();
+
+        let executable = dir.join("program");
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let status = Command::new(rustc)
+            .args(["--edition", "2018", "--cap-lints", "allow", "-o"])
+            .arg(&executable)
+            .arg(dir.join("main.rs"))
+            .status()
+            .unwrap();
+        assert!(status.success(), "transpiled {} didn't compile", name);
+
+        executable
+    }
+
+    /// Runs the program on the interpreter and the transpiled executable with the input, and
+    /// checks that both produce the same output and stop the same way.  Returns the transpiled run.
+    fn compare(executable: &Path, program: &[i64], input: &[i64]) -> String {
+        let mut computer = Computer::new(program.to_vec());
+        input.iter().for_each(|&value| computer.input(value));
+
+        let result = computer.try_run();
+        let mut expected = format!("{:?}\n", computer.dump_output());
+        match result {
+            Ok(_) => expected += &format!("{:?} at pc {}\n", computer.state(), computer.pc()),
+            Err(e) => expected += &format!("{} at pc {}\n", e, computer.pc()),
+        }
+
+        let output = Command::new(executable).args(input.iter().map(i64::to_string)).output().unwrap();
+        let actual = String::from_utf8(output.stdout).unwrap();
+
+        assert_eq!(expected, actual, "input {:?}", input);
+        actual
+    }
+
+    #[test]
+    fn blocks() {
+        // Reads input, then counts down to 1 outputting each value.
+        let program = vec![
+            3, 12,            // 0: read into 12
+            4, 12,            // 2: output 12
+            1001, 12, -1, 12, // 4: subtract 1 from 12
+            1005, 12, 2,      // 8: loop to 2 while 12 isn't 0
+            99,               // 11: halt
+            0,                // 12: counter
+        ];
+
+        let starts: Vec<usize> = find_blocks(&program, &[0]).iter().map(Block::start).collect();
+        assert_eq!(vec![0, 2, 11], starts);
+    }
+
+    #[test]
+    fn indirect_jump() {
+        // Jumps to 3, then to the address stored at 7.  The halt at 6 is only reached indirectly.
+        let program = vec![1105, 1, 3, 105, 1, 7, 99, 6];
+
+        let blocks = find_blocks(&program, &[0]);
+        assert_eq!(vec![0, 3], blocks.iter().map(Block::start).collect::<Vec<_>>());
+        assert_eq!(6, blocks[1].end());
+    }
+
+    #[test]
+    fn self_modifying() {
+        // Outputs 0, 1 and 2 by incrementing the immediate operand of its own output instruction.
+        let counter = vec![
+            104, 0,            // 0: output 0
+            1001, 1, 1, 1,     // 2: add 1 to the output's operand
+            1007, 1, 3, 20,    // 6: [20] = operand < 3
+            1005, 20, 0,       // 10: loop while [20] isn't 0
+            99,                // 13: halt
+        ];
+        let executable = build("counter", &counter);
+        assert_eq!("[0, 1, 2]\nDone at pc 13\n", compare(&executable, &counter, &[]));
+
+        // Overwrites the halt at 6 with an output of [0], which reads input first.
+        let patched = vec![1101, 2, 2, 6, 104, 7, 99, 0, 3, 0, 99];
+        let executable = build("patched", &patched);
+        assert_eq!("[7, 1101]\nWaitingForInput at pc 8\n", compare(&executable, &patched, &[]));
+        compare(&executable, &patched, &[5]);
+    }
+
+    #[test]
+    fn extra_modes() {
+        // The interpreter ignores the mode digits past the output's one parameter.
+        let program = vec![100104, 7, 104, 8, 99];
+        assert_eq!(Some(vec![Operand { mode: 1, value: 7 }]), Decoded::parse(&program, 0).map(|decoded| decoded.operands));
+
+        let executable = build("extra_modes", &program);
+        assert_eq!("[7, 8]\nDone at pc 4\n", compare(&executable, &program, &[]));
+    }
+
+    #[test]
+    fn indirect_jumps() {
+        // Jumps to the address it reads.  Only the output at 5 is reachable statically.
+        let program = vec![
+            3, 30,             // 0: read the target into 30
+            105, 1, 30,        // 2: jump to [30]
+            104, 1, 99,        // 5: output 1 and halt
+            104, 2, 99,        // 8: output 2 and halt
+            304, 0,            // 11: invalid mode
+            3005, 29, 0,       // 13: jump that isn't taken, with an invalid mode for its target
+            -5,                // 16: invalid opcode
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let executable = build("indirect", &program);
+
+        assert_eq!("[]\nWaitingForInput at pc 0\n", compare(&executable, &program, &[]));
+        assert_eq!("[1]\nDone at pc 7\n", compare(&executable, &program, &[5]));
+        assert_eq!("[2]\nDone at pc 10\n", compare(&executable, &program, &[8]));
+        assert_eq!("[]\nInvalid parameter mode 3 at pc 11\n", compare(&executable, &program, &[11]));
+        assert_eq!("[]\nInvalid parameter mode 3 at pc 13\n", compare(&executable, &program, &[13]));
+        assert_eq!("[]\nUnknown opcode -5 at pc 16\n", compare(&executable, &program, &[16]));
+
+        // Into the middle of an instruction, and past the end of the program.
+        compare(&executable, &program, &[6]);
+        assert_eq!("[]\nUnknown opcode 0 at pc 100\n", compare(&executable, &program, &[100]));
+    }
+
+    /// The transpiled TEST and BOOST programs have to pass their self-tests, like the interpreter in
+    /// diagnostic's gate, and match the interpreter's outputs.
+    #[test]
+    fn self_tests() {
+        for (name, filename, runs) in [
+            ("test", "../day5/input.txt", [(1, 13346482), (5, 12111395)]),
+            ("boost", "../day9/input.txt", [(1, 3497884671), (2, 46470)]),
+        ] {
+            let program = program::read_program(filename).unwrap();
+            let executable = build(name, &program);
+
+            for (system_id, code) in runs {
+                // Every output but the diagnostic code is a passing check.
+                let output = compare(&executable, &program, &[system_id]);
+                let values: Vec<i64> = output.lines().next().unwrap().trim_matches(&['[', ']'][..]).split(", ").map(|value| value.parse().unwrap()).collect();
+                assert_eq!((Some(&code), true), (values.last(), values[..values.len() - 1].iter().all(|&value| value == 0)), "{}", output);
+                assert!(output.contains("Done"), "{}", output);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_module() {
+        let source = transpile(&[3, 0, 4, 0, 99]);
+
+        assert!(source.contains("const PROGRAM: [i64; 5] = [3, 0, 4, 0, 99];"));
+        assert!(source.contains("const CODE_BLOCKS: [usize; 5] = [0, 0, 0, 0, 0];"));
+        assert!(source.contains("0 if self.compiled[0] => {"));
+    }
+}
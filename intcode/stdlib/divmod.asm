@@ -0,0 +1,64 @@
+# Divides a by b, rounding towards zero like Rust's / and %.  b can't be 0.
+#   call divmod, a, b -> quotient, remainder
+export divmod
+
+divmod:
+    mov [rb+1], [remainder]
+    mov [rb+2], [divisor]
+    arb 3                           # doubled divisors go on the stack above the arguments
+    mov 0, [quotient]
+
+    # Divide the magnitudes, and fix the signs at the end.
+    lt [remainder], 0, [negative_a]
+    jz [negative_a], positive_a
+    mul [remainder], -1, [remainder]
+positive_a:
+    lt [divisor], 0, [negative_b]
+    jz [negative_b], positive_b
+    mul [divisor], -1, [divisor]
+positive_b:
+    mov 1, [bit]
+
+# Push the divisor times each power of 2 until it's bigger than the remainder, or would overflow.
+double:
+    push [divisor]
+    push [bit]
+    lt [divisor], 4611686018427387904, [test]
+    jz [test], halve
+    mul [divisor], 2, [divisor]
+    mul [bit], 2, [bit]
+    lt [remainder], [divisor], [test]
+    jz [test], double
+
+# Pop them in reverse, subtracting each one that fits from the remainder.
+halve:
+    pop [bit]
+    pop [divisor]
+    lt [remainder], [divisor], [test]
+    jnz [test], next
+    mul [divisor], -1, [test]
+    add [remainder], [test], [remainder]
+    add [quotient], [bit], [quotient]
+next:
+    eq [bit], 1, [test]
+    jz [test], halve
+
+    eq [negative_a], [negative_b], [test]
+    jnz [test], remainder_sign
+    mul [quotient], -1, [quotient]
+remainder_sign:
+    jz [negative_a], done
+    mul [remainder], -1, [remainder]
+done:
+    arb -3
+    mov [quotient], [rb+1]
+    mov [remainder], [rb+2]
+    ret
+
+quotient:   data 0
+remainder:  data 0
+divisor:    data 0
+bit:        data 0
+negative_a: data 0
+negative_b: data 0
+test:       data 0
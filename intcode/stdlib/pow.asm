@@ -0,0 +1,20 @@
+# Raises base to a power that isn't negative, by multiplying it in a loop.
+#   call pow, base, exponent -> result
+export pow
+
+pow:
+    mov 1, [result]
+    mov [rb+2], [count]
+loop:
+    lt 0, [count], [test]
+    jz [test], done
+    mul [result], [rb+1], [result]
+    add [count], -1, [count]
+    jmp loop
+done:
+    mov [result], [rb+1]
+    ret
+
+result: data 0
+count:  data 0
+test:   data 0
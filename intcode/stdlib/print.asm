@@ -0,0 +1,46 @@
+# Outputs a number as ASCII decimal digits, with a '-' in front if it's negative.
+#   call print_int, n
+export print_int
+
+print_int:
+    mov [rb+1], [n]
+    arb 2                           # powers of 10 go on the stack above the argument
+    lt [n], 0, [test]
+    jz [test], first
+    out 45
+    mul [n], -1, [n]
+first:
+    mov 1, [power]
+
+# Push powers of 10 until the next one is bigger than n, or would overflow.
+powers:
+    push [power]
+    eq [power], 1000000000000000000, [test]
+    jnz [test], digits
+    mul [power], 10, [power]
+    lt [n], [power], [test]
+    jz [test], powers
+
+# Pop each power, and subtract it from n until n is smaller to find its digit.
+digits:
+    pop [power]
+    mov 48, [digit]
+subtract:
+    lt [n], [power], [test]
+    jnz [test], emit
+    mul [power], -1, [test]
+    add [n], [test], [n]
+    add [digit], 1, [digit]
+    jmp subtract
+emit:
+    out [digit]
+    eq [power], 1, [test]
+    jz [test], digits
+
+    arb -2
+    ret
+
+n:      data 0
+power:  data 0
+digit:  data 0
+test:   data 0
@@ -0,0 +1,31 @@
+# Reads an ASCII decimal number, with an optional '-' in front.  Reads up to and including the
+# first character that isn't a digit, like the newline after the number.
+#   call read_int -> n
+export read_int
+
+read_int:
+    mov 0, [n]
+    mov 1, [sign]
+    in [char]
+    eq [char], 45, [test]
+    jz [test], digit
+    mov -1, [sign]
+next:
+    in [char]
+digit:
+    lt [char], 48, [test]
+    jnz [test], done
+    lt 57, [char], [test]
+    jnz [test], done
+    add [char], -48, [char]
+    mul [n], 10, [n]
+    add [n], [char], [n]
+    jmp next
+done:
+    mul [n], [sign], [rb+1]
+    ret
+
+n:      data 0
+sign:   data 0
+char:   data 0
+test:   data 0